@@ -0,0 +1,44 @@
+//! Ctrl-C to stop the program run by the monitor
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// The same number on Unix and on Windows
+const SIGINT: i32 = 2;
+
+static PRESSED: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    // signal() of the C library, available on Unix and on Windows
+    fn signal(signum: i32, handler: usize) -> usize;
+}
+
+extern "C" fn on_interrupt(_signum: i32) {
+    PRESSED.store(true, Ordering::Relaxed);
+}
+
+/// Catches Ctrl-C while alive, the previous handler is restored when
+/// dropped
+pub struct CtrlC {
+    previous: usize,
+}
+
+impl CtrlC {
+    pub fn catch() -> CtrlC {
+        PRESSED.store(false, Ordering::Relaxed);
+        // SAFETY: the handler only stores an atomic
+        let previous = unsafe { signal(SIGINT, on_interrupt as *const () as usize) };
+        CtrlC { previous }
+    }
+
+    /// Returns true if Ctrl-C was pressed since the catch started
+    pub fn is_pressed(&self) -> bool {
+        PRESSED.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for CtrlC {
+    fn drop(&mut self) {
+        // SAFETY: restores the handler signal() returned
+        unsafe { signal(SIGINT, self.previous) };
+    }
+}
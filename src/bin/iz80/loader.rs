@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::Path;
+
+use iz80::Machine;
+
+use crate::hex::parse_hex_bytes;
+
+/// Load address of the CP/M programs
+pub const TPA: u16 = 0x0100;
+
+/// Formats of the images
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Binary loaded at the origin
+    Raw,
+    /// Intel HEX records
+    IntelHex,
+    /// CP/M program, loaded at 0100h
+    Com,
+}
+
+impl Format {
+    /// Returns the format from the file extension: .hex and .ihx are
+    /// Intel HEX, .com is CP/M, the rest are raw.
+    pub fn from_path(path: &Path) -> Format {
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("hex") | Some("ihx") => Format::IntelHex,
+            Some("com") => Format::Com,
+            _ => Format::Raw,
+        }
+    }
+}
+
+/// Image loaded
+pub struct Image {
+    pub format: Format,
+    /// Lowest address loaded
+    pub start: u16,
+    /// Bytes loaded
+    pub size: usize,
+    /// Start address of the program
+    pub entry: u16,
+}
+
+/// Loads the image in memory. The origin is only used by the raw images.
+pub fn load(path: &Path, origin: u16, sys: &mut dyn Machine) -> Result<Image, String> {
+    let data = fs::read(path)
+        .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+    let format = Format::from_path(path);
+    match format {
+        Format::Raw => load_binary(&data, origin, format, sys),
+        Format::Com => load_binary(&data, TPA, format, sys),
+        Format::IntelHex => {
+            let text = String::from_utf8(data)
+                .map_err(|_| "the Intel HEX file is not text".to_string())?;
+            load_hex(&text, sys)
+        }
+    }
+}
+
+fn load_binary(data: &[u8], origin: u16, format: Format, sys: &mut dyn Machine) -> Result<Image, String> {
+    if data.len() > 0x10000 - origin as usize {
+        return Err(format!("the image of {} bytes does not fit at {origin:04x}h", data.len()));
+    }
+    for (i, b) in data.iter().enumerate() {
+        sys.poke(origin.wrapping_add(i as u16), *b);
+    }
+    Ok(Image {
+        format,
+        start: origin,
+        size: data.len(),
+        entry: origin,
+    })
+}
+
+/// Loads the data records of an Intel HEX file. The start address is
+/// the one of the start records or the lowest address loaded.
+pub fn load_hex(text: &str, sys: &mut dyn Machine) -> Result<Image, String> {
+    let mut start: Option<u16> = None;
+    let mut entry = None;
+    let mut size = 0;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {message}", n + 1);
+
+        let record = line.strip_prefix(':')
+            .and_then(parse_hex_bytes)
+            .ok_or_else(|| error("invalid record"))?;
+        if record.len() < 5 || record.len() != record[0] as usize + 5 {
+            return Err(error("invalid record length"));
+        }
+        if record.iter().fold(0_u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(error("invalid checksum"));
+        }
+
+        let address = u16::from_be_bytes([record[1], record[2]]);
+        let data = &record[4..record.len() - 1];
+        match record[3] {
+            0x00 => {
+                if address as usize + data.len() > 0x10000 {
+                    return Err(error("data beyond 64K"));
+                }
+                for (i, b) in data.iter().enumerate() {
+                    sys.poke(address + i as u16, *b);
+                }
+                size += data.len();
+                start = Some(start.map_or(address, |s| s.min(address)));
+            },
+            0x01 => break,
+            // Extended addresses, only the first 64K are supported
+            0x02 | 0x04 => {
+                if data.iter().any(|b| *b != 0) {
+                    return Err(error("extended address beyond 64K"));
+                }
+            },
+            // Start addresses, the lower 16 bits
+            0x03 | 0x05 if data.len() == 4 => {
+                entry = Some(u16::from_be_bytes([data[2], data[3]]));
+            },
+            _ => return Err(error("unsupported record type")),
+        }
+    }
+
+    let start = start.ok_or_else(|| "no data in the Intel HEX file".to_string())?;
+    Ok(Image {
+        format: Format::IntelHex,
+        start,
+        size,
+        entry: entry.unwrap_or(start),
+    })
+}
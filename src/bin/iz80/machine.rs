@@ -0,0 +1,121 @@
+use std::io::{self, Write};
+
+use iz80::{Machine, PlainMachine};
+
+/// CP/M warm boot, jumping there ends the program
+const WARM_BOOT: u16 = 0x0000;
+/// CP/M BDOS entry point
+const BDOS: u16 = 0x0005;
+/// Top of the memory available to the CP/M programs, the BDOS code is
+/// placed there
+const BDOS_BASE: u16 = 0xfe00;
+/// DE of the BDOS call, saved by the BDOS code
+const BDOS_DE: u16 = 0xfef0;
+
+/// Port written with the BDOS function by the BDOS code
+const BDOS_PORT: u8 = 0xfe;
+/// Port written by the warm boot code
+const EXIT_PORT: u8 = 0xff;
+
+/// PlainMachine with the console output of the CP/M BDOS. The BDOS code
+/// placed in memory signals the calls with OUT, as the programs are
+/// debugged with it.
+pub struct MonitorMachine {
+    memory: PlainMachine,
+    cpm: bool,
+    exited: bool,
+}
+
+impl MonitorMachine {
+    pub fn new() -> MonitorMachine {
+        MonitorMachine {
+            memory: PlainMachine::new(),
+            cpm: false,
+            exited: false,
+        }
+    }
+
+    /// Prepares the zero page and the BDOS for a CP/M program at 0100h.
+    /// Returns the stack pointer for the program, returning from the
+    /// program exits.
+    pub fn setup_cpm(&mut self) -> u16 {
+        self.cpm = true;
+        let warm_boot = [
+            0xd3, EXIT_PORT, // OUT (EXIT_PORT), A
+            0x76, // HALT
+        ];
+        let bdos = [
+            0xeb, // EX DE, HL
+            0x22, BDOS_DE as u8, (BDOS_DE >> 8) as u8, // LD (BDOS_DE), HL
+            0xeb, // EX DE, HL
+            0x79, // LD A, C
+            0xd3, BDOS_PORT, // OUT (BDOS_PORT), A
+            0xb7, // OR A
+            0xc0, // RET NZ
+            0xc3, WARM_BOOT as u8, (WARM_BOOT >> 8) as u8, // JP WARM_BOOT
+        ];
+        for (i, b) in warm_boot.iter().enumerate() {
+            self.poke(WARM_BOOT + i as u16, *b);
+        }
+        // The programs read the top of the memory on the jump
+        self.poke(BDOS, 0xc3); // JP BDOS_BASE
+        self.poke16(BDOS + 1, BDOS_BASE);
+        for (i, b) in bdos.iter().enumerate() {
+            self.poke(BDOS_BASE + i as u16, *b);
+        }
+
+        let sp = BDOS_BASE - 2;
+        self.poke16(sp, WARM_BOOT);
+        sp
+    }
+
+    /// Returns true once after the CP/M program exits
+    pub fn take_exited(&mut self) -> bool {
+        std::mem::take(&mut self.exited)
+    }
+
+    fn bdos(&mut self, function: u8) {
+        let de = self.peek16(BDOS_DE);
+        let mut stdout = io::stdout();
+        match function {
+            2 => {
+                let _ = stdout.write_all(&[de as u8]);
+            },
+            9 => {
+                let mut address = de;
+                loop {
+                    let c = self.peek(address);
+                    if c == b'$' {
+                        break;
+                    }
+                    let _ = stdout.write_all(&[c]);
+                    address = address.wrapping_add(1);
+                }
+            },
+            _ => {}, // Not supported
+        }
+        let _ = stdout.flush();
+    }
+}
+
+impl Machine for MonitorMachine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.memory.peek(address)
+    }
+
+    fn poke(&mut self, address: u16, value: u8) {
+        self.memory.poke(address, value);
+    }
+
+    fn port_in(&mut self, address: u16) -> u8 {
+        self.memory.port_in(address)
+    }
+
+    fn port_out(&mut self, address: u16, value: u8) {
+        match address as u8 {
+            BDOS_PORT if self.cpm => self.bdos(value),
+            EXIT_PORT if self.cpm => self.exited = true,
+            _ => self.memory.port_out(address, value),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Monitor to run and debug Z80 and 8080 programs
+//!
+//! Loads a raw binary, an Intel HEX file or a CP/M .COM program and
+//! opens a prompt to step, run and examine it. See `h` on the prompt.
+
+use std::io;
+use std::path::PathBuf;
+use std::process::exit;
+
+use iz80::*;
+
+#[path = "../../hex.rs"]
+mod hex;
+mod interrupt;
+mod loader;
+mod machine;
+mod monitor;
+mod symbols;
+
+use loader::Format;
+use machine::MonitorMachine;
+use monitor::{Monitor, Stop};
+use symbols::parse_number;
+
+const USAGE: &str = "\
+Usage: iz80 [OPTIONS] IMAGE
+
+Loads IMAGE and opens the monitor. Images ending in .hex or .ihx are
+Intel HEX, .com are CP/M programs loaded at 0100h with the console
+output of the BDOS emulated. Any other file is loaded raw.
+
+Options:
+  --8080          emulate an Intel 8080, with the Intel mnemonics
+  --org ADDR      load address of a raw image, 0 by default
+  --pc ADDR       start address, the entry point of the image by default
+  --symbols FILE  load a symbol file
+  --run           run until the program halts or exits, without the monitor";
+
+struct Options {
+    image: PathBuf,
+    is_8080: bool,
+    origin: u16,
+    pc: Option<u16>,
+    symbols: Option<PathBuf>,
+    run: bool,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut image = None;
+    let mut options = Options {
+        image: PathBuf::new(),
+        is_8080: false,
+        origin: 0,
+        pc: None,
+        symbols: None,
+        run: false,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut address = |name: &str| {
+            let value = args.next().ok_or_else(|| format!("{name} needs an address"))?;
+            parse_number(&value).ok_or_else(|| format!("invalid address {value}"))
+        };
+        match arg.as_str() {
+            "--8080" => options.is_8080 = true,
+            "--org" => options.origin = address("--org")?,
+            "--pc" => options.pc = Some(address("--pc")?),
+            "--symbols" => {
+                let path = args.next().ok_or("--symbols needs a file")?;
+                options.symbols = Some(path.into());
+            },
+            "--run" => options.run = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0);
+            },
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ if image.is_none() => image = Some(arg.into()),
+            _ => return Err("only one image can be loaded".to_string()),
+        }
+    }
+    options.image = image.ok_or("no image to load")?;
+    Ok(options)
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        eprintln!("{USAGE}");
+        exit(2);
+    });
+
+    let mut cpu = if options.is_8080 {
+        let mut cpu = Cpu::new_8080();
+        cpu.set_syntax(Syntax::Intel);
+        cpu
+    } else {
+        Cpu::new()
+    };
+    let mut sys = MonitorMachine::new();
+    let image = loader::load(&options.image, options.origin, &mut sys).unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        exit(1);
+    });
+    cpu.registers().set_pc(options.pc.unwrap_or(image.entry));
+
+    let mut monitor = Monitor::new(cpu, sys);
+    if image.format == Format::Com {
+        monitor.setup_cpm();
+    }
+    if let Some(path) = &options.symbols {
+        if let Err(e) = monitor.symbols().load(path) {
+            eprintln!("Error: {e}");
+            exit(1);
+        }
+    }
+
+    if options.run {
+        match monitor.run_to_end() {
+            Stop::Exited => {},
+            stop => monitor.report(stop, None),
+        }
+    } else {
+        println!("Loaded {} bytes at {:04x}h", image.size, image.start);
+        monitor.repl(&mut io::stdin().lock());
+    }
+}
@@ -0,0 +1,459 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use iz80::*;
+
+use super::interrupt::CtrlC;
+use super::machine::MonitorMachine;
+use super::symbols::{parse_number, Symbols};
+
+/// Instructions executed by go and the steps over and out before
+/// returning to the prompt
+const GO_LIMIT: u64 = 100_000_000;
+
+/// Instructions executed by go between the checks for Ctrl-C
+const GO_SLICE: u64 = 100_000;
+
+const DISASM_LINES: u16 = 16;
+const DUMP_BYTES: u16 = 128;
+
+const HELP: &str = "\
+Commands:
+  s [N]            step N instructions, 1 by default
+  n                step over calls
+  o                step out of the current subroutine
+  g [ADDR]         go, until ADDR if given or Ctrl-C
+  r [REG VALUE]    show the registers or set one
+  m [ADDR [LEN]]   dump memory
+  e ADDR BYTE...   edit memory
+  d [ADDR [N]]     disassemble N instructions
+  b [ADDR]         list the breakpoints or add one
+  bc ADDR|*        clear a breakpoint or all of them
+  sym FILE         load a symbol file
+  save FILE        save the memory and the CPU state
+  restore FILE     restore the memory and the CPU state
+  q                quit
+An empty line repeats the last command. Addresses and values are
+hexadecimal or symbol names.";
+
+/// Reason a command stopped the execution
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Cpu(StopReason),
+    /// The CP/M program exited
+    Exited,
+    /// Ctrl-C was pressed
+    Interrupted,
+}
+
+/// Interactive monitor of a CPU and its memory
+pub struct Monitor {
+    cpu: Cpu,
+    sys: MonitorMachine,
+    symbols: Symbols,
+    last_command: String,
+    next_disasm: Option<u16>,
+    next_dump: Option<u16>,
+}
+
+impl Monitor {
+    pub fn new(cpu: Cpu, sys: MonitorMachine) -> Monitor {
+        Monitor {
+            cpu,
+            sys,
+            symbols: Symbols::default(),
+            last_command: String::new(),
+            next_disasm: None,
+            next_dump: None,
+        }
+    }
+
+    pub fn symbols(&mut self) -> &mut Symbols {
+        &mut self.symbols
+    }
+
+    /// Prepares the memory and the stack for a CP/M program at 0100h
+    pub fn setup_cpm(&mut self) {
+        let sp = self.sys.setup_cpm();
+        self.cpu.registers().set16(Reg16::SP, sp);
+    }
+
+    /// Runs until the program halts or exits, without the prompt
+    pub fn run_to_end(&mut self) -> Stop {
+        loop {
+            match self.run(u64::MAX) {
+                Stop::Cpu(StopReason::Breakpoint(_)) => continue,
+                stop => return stop,
+            }
+        }
+    }
+
+    /// Reads commands from the input until quit or the end of the input
+    pub fn repl(&mut self, input: &mut dyn io::BufRead) {
+        self.show_position();
+        loop {
+            print!("> ");
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {},
+            }
+            let mut line = line.trim().to_string();
+            if line.is_empty() {
+                line = self.last_command.clone();
+            } else {
+                self.last_command = line.clone();
+            }
+            match self.execute(&line) {
+                Ok(true) => break,
+                Ok(false) => {},
+                Err(e) => println!("Error: {e}"),
+            }
+        }
+    }
+
+    /// Executes a command, returns true to quit
+    pub fn execute(&mut self, line: &str) -> Result<bool, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = words.split_first() else {
+            return Ok(false);
+        };
+        match command {
+            "s" => {
+                let count = self.optional_value(args.first(), 1)? as u64;
+                let stop = self.run(count.max(1));
+                self.report(stop, None);
+            },
+            "n" => {
+                let reason = self.cpu.step_over(&mut self.sys, GO_LIMIT);
+                let stop = self.stop(reason);
+                self.report_run(stop, None);
+            },
+            "o" => {
+                let reason = self.cpu.step_out(&mut self.sys, GO_LIMIT);
+                let stop = self.stop(reason);
+                self.report_run(stop, None);
+            },
+            "g" => {
+                let target = match args.first() {
+                    Some(a) => Some(self.address(a)?),
+                    None => None,
+                };
+                let stop = self.go(target);
+                self.report_run(stop, target);
+            },
+            "r" => match args {
+                [] => self.show_registers(),
+                [reg, value] => self.set_register(reg, self.value(value)?)?,
+                _ => return Err("usage: r [REG VALUE]".to_string()),
+            },
+            "m" => {
+                let start = self.optional_address(args.first(), self.next_dump)?;
+                let length = self.optional_value(args.get(1), DUMP_BYTES)?;
+                self.dump(start, length);
+            },
+            "e" => {
+                let (address, bytes) = args.split_first()
+                    .ok_or("usage: e ADDR BYTE...")?;
+                let mut address = self.address(address)?;
+                for b in bytes {
+                    let value = self.value(b)?;
+                    if value > 0xff {
+                        return Err(format!("{b} is not a byte"));
+                    }
+                    self.sys.poke(address, value as u8);
+                    address = address.wrapping_add(1);
+                }
+            },
+            "d" => {
+                let start = self.optional_address(args.first(), self.next_disasm)?;
+                let count = self.optional_value(args.get(1), DISASM_LINES)?;
+                let mut address = start;
+                for _ in 0..count {
+                    address = address.wrapping_add(self.show_instruction(address));
+                }
+                self.next_disasm = Some(address);
+            },
+            "b" => match args.first() {
+                None => {
+                    for address in self.breakpoint_addresses() {
+                        println!("{}", self.label(address as u16));
+                    }
+                },
+                Some(a) => {
+                    let address = self.address(a)?;
+                    self.cpu.debugger().add_breakpoint(address.into());
+                },
+            },
+            "bc" => match args.first() {
+                Some(&"*") => {
+                    for address in self.breakpoint_addresses() {
+                        self.cpu.debugger().remove_breakpoint(address);
+                    }
+                },
+                Some(a) => {
+                    let address = self.address(a)?;
+                    self.cpu.debugger().remove_breakpoint(address.into());
+                },
+                None => return Err("usage: bc ADDR|*".to_string()),
+            },
+            "sym" => {
+                let path = args.first().ok_or("usage: sym FILE")?;
+                let count = self.symbols.load(Path::new(path))?;
+                println!("{count} symbols loaded");
+            },
+            "save" => {
+                let path = args.first().ok_or("usage: save FILE")?;
+                self.save(Path::new(path))?;
+            },
+            "restore" => {
+                let path = args.first().ok_or("usage: restore FILE")?;
+                self.restore(Path::new(path))?;
+                self.show_position();
+            },
+            "h" | "?" => println!("{HELP}"),
+            "q" => return Ok(true),
+            _ => return Err(format!("unknown command {command}, h for help")),
+        }
+        Ok(false)
+    }
+
+    /// Executes up to `instructions`
+    fn run(&mut self, instructions: u64) -> Stop {
+        let reason = self.cpu.run(&mut self.sys, instructions);
+        self.stop(reason)
+    }
+
+    /// Executes up to `GO_LIMIT` instructions, until `target` if present.
+    /// Runs in slices to stop on Ctrl-C.
+    fn go(&mut self, target: Option<u16>) -> Stop {
+        let target = target.map(u32::from);
+        let ctrl_c = CtrlC::catch();
+        let mut reason = match target {
+            Some(address) => self.cpu.run_to(&mut self.sys, address, GO_SLICE),
+            None => self.cpu.run(&mut self.sys, GO_SLICE),
+        };
+        let mut executed = GO_SLICE;
+        while reason == StopReason::InstructionLimit && executed < GO_LIMIT {
+            if ctrl_c.is_pressed() {
+                return Stop::Interrupted;
+            }
+            reason = self.cpu.run_slice(&mut self.sys, target, GO_SLICE);
+            executed += GO_SLICE;
+        }
+        self.stop(reason)
+    }
+
+    fn stop(&mut self, reason: StopReason) -> Stop {
+        if self.sys.take_exited() {
+            Stop::Exited
+        } else {
+            Stop::Cpu(reason)
+        }
+    }
+
+    fn breakpoint_addresses(&mut self) -> Vec<u32> {
+        self.cpu.debugger().breakpoints().iter().map(|b| b.address).collect()
+    }
+
+    // As report(), the instruction limit is not expected
+    fn report_run(&mut self, stop: Stop, target: Option<u16>) {
+        if stop == Stop::Cpu(StopReason::InstructionLimit) {
+            println!("Stopped after {GO_LIMIT} instructions");
+        }
+        self.report(stop, target);
+    }
+
+    pub fn report(&mut self, stop: Stop, target: Option<u16>) {
+        match stop {
+            Stop::Exited => {
+                println!();
+                println!("Program exited");
+                return;
+            },
+            Stop::Cpu(StopReason::Breakpoint(address)) if target.map(u32::from) != Some(address) => {
+                println!("Breakpoint at {}", self.label(address as u16));
+            },
+            Stop::Interrupted => println!("Interrupted"),
+            Stop::Cpu(StopReason::Watchpoint(access)) => println!("Watchpoint {access:?}"),
+            Stop::Cpu(StopReason::Halted) => println!("Halted"),
+            Stop::Cpu(_) => {},
+        }
+        self.show_position();
+    }
+
+    fn show_position(&mut self) {
+        self.show_registers();
+        let pc = self.cpu.registers().pc();
+        let length = self.show_instruction(pc);
+        self.next_disasm = Some(pc.wrapping_add(length));
+    }
+
+    fn show_registers(&mut self) {
+        let reg = self.cpu.immutable_registers();
+        let mut flags = String::new();
+        for (flag, name) in [(Flag::S, 'S'), (Flag::Z, 'Z'), (Flag::_5, '5'), (Flag::H, 'H'),
+                (Flag::_3, '3'), (Flag::P, 'P'), (Flag::N, 'N'), (Flag::C, 'C')] {
+            flags.push(if reg.get_flag(flag) { name } else { '-' });
+        }
+        let mut line = format!("PC={:04x} SP={:04x} AF={:04x} BC={:04x} DE={:04x} HL={:04x}",
+            reg.pc(), reg.get16(Reg16::SP), reg.get16(Reg16::AF),
+            reg.get16(Reg16::BC), reg.get16(Reg16::DE), reg.get16(Reg16::HL));
+        if self.cpu.syntax() == Syntax::Zilog {
+            line += &format!(" IX={:04x} IY={:04x} AF'={:04x} BC'={:04x} DE'={:04x} HL'={:04x} I={:02x} R={:02x}",
+                reg.get16(Reg16::IX), reg.get16(Reg16::IY),
+                reg.get16_shadow(Reg16::AF), reg.get16_shadow(Reg16::BC),
+                reg.get16_shadow(Reg16::DE), reg.get16_shadow(Reg16::HL),
+                reg.get8(Reg8::I), reg.get8(Reg8::R));
+        }
+        println!("{line} {flags}");
+    }
+
+    fn set_register(&mut self, name: &str, value: u16) -> Result<(), String> {
+        let upper = name.to_ascii_uppercase();
+        let reg = self.cpu.registers();
+        let reg16 = match upper.trim_end_matches('\'') {
+            "AF" => Some(Reg16::AF),
+            "BC" => Some(Reg16::BC),
+            "DE" => Some(Reg16::DE),
+            "HL" => Some(Reg16::HL),
+            "IX" => Some(Reg16::IX),
+            "IY" => Some(Reg16::IY),
+            "SP" => Some(Reg16::SP),
+            _ => None,
+        };
+        if let Some(rr) = reg16 {
+            if upper.ends_with('\'') {
+                reg.set16_shadow(rr, value);
+            } else {
+                reg.set16(rr, value);
+            }
+            return Ok(());
+        }
+        if upper == "PC" {
+            reg.set_pc(value);
+            return Ok(());
+        }
+
+        let r = match upper.as_str() {
+            "A" => Reg8::A,
+            "F" => Reg8::F,
+            "B" => Reg8::B,
+            "C" => Reg8::C,
+            "D" => Reg8::D,
+            "E" => Reg8::E,
+            "H" => Reg8::H,
+            "L" => Reg8::L,
+            "I" => Reg8::I,
+            "R" => Reg8::R,
+            _ => return Err(format!("unknown register {name}")),
+        };
+        if value > 0xff {
+            return Err(format!("{r:?} is an 8 bits register"));
+        }
+        reg.set8(r, value as u8);
+        Ok(())
+    }
+
+    /// Shows the instruction at the address, returns its length
+    fn show_instruction(&mut self, address: u16) -> u16 {
+        if let Some(name) = self.symbols.name(address) {
+            println!("{name}:");
+        }
+        let (disasm, length) = self.cpu.disasm_at(&mut self.sys, address);
+        let bytes: Vec<String> = (0..length)
+            .map(|i| format!("{:02x}", self.sys.peek(address.wrapping_add(i))))
+            .collect();
+        println!("  {address:04x}  {:<12}  {}", bytes.join(" "), self.with_symbols(&disasm));
+        length
+    }
+
+    /// Replaces the addresses of the disassembly with their names
+    fn with_symbols(&self, disasm: &str) -> String {
+        let mut result = String::new();
+        let mut rest = disasm;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_hexdigit()) {
+            let (before, from) = rest.split_at(start);
+            result += before;
+            let end = from.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(from.len());
+            let (word, after) = from.split_at(end);
+            let name = word.strip_suffix('h')
+                .filter(|digits| digits.len() == 4)
+                .and_then(|digits| u16::from_str_radix(digits, 16).ok())
+                .and_then(|address| self.symbols.name(address));
+            result += name.unwrap_or(word);
+            rest = after;
+        }
+        result + rest
+    }
+
+    fn dump(&mut self, start: u16, length: u16) {
+        let mut address = start;
+        let mut remaining = length as u32;
+        while remaining > 0 {
+            let count = remaining.min(16) as u16;
+            let bytes: Vec<u8> = (0..count).map(|i| self.sys.peek(address.wrapping_add(i))).collect();
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+            let ascii: String = bytes.iter()
+                .map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' })
+                .collect();
+            println!("{address:04x}  {:<47}  {ascii}", hex.join(" "));
+            address = address.wrapping_add(count);
+            remaining -= count as u32;
+        }
+        self.next_dump = Some(address);
+    }
+
+    /// Saves the 64K of memory followed by the CPU state
+    fn save(&mut self, path: &Path) -> Result<(), String> {
+        let mut data: Vec<u8> = (0..=0xffff).map(|a| self.sys.peek(a)).collect();
+        data.extend(self.cpu.serialize());
+        fs::write(path, data).map_err(|e| format!("cannot write {}: {e}", path.display()))
+    }
+
+    fn restore(&mut self, path: &Path) -> Result<(), String> {
+        let data = fs::read(path).map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+        if data.len() <= 0x10000 {
+            return Err(format!("{} is not a saved state", path.display()));
+        }
+        let (memory, state) = data.split_at(0x10000);
+        self.cpu.deserialize(state).map_err(|e| format!("invalid state: {e}"))?;
+        for (address, value) in memory.iter().enumerate() {
+            self.sys.poke(address as u16, *value);
+        }
+        Ok(())
+    }
+
+    fn label(&self, address: u16) -> String {
+        match self.symbols.name(address) {
+            Some(name) => format!("{address:04x} ({name})"),
+            None => format!("{address:04x}"),
+        }
+    }
+
+    fn address(&self, s: &str) -> Result<u16, String> {
+        self.symbols.address(s)
+            .or_else(|| parse_number(s))
+            .ok_or_else(|| format!("invalid address {s}"))
+    }
+
+    fn value(&self, s: &str) -> Result<u16, String> {
+        self.address(s).map_err(|_| format!("invalid value {s}"))
+    }
+
+    fn optional_address(&mut self, s: Option<&&str>, default: Option<u16>) -> Result<u16, String> {
+        match s {
+            Some(s) => self.address(s),
+            None => Ok(default.unwrap_or_else(|| self.cpu.registers().pc())),
+        }
+    }
+
+    fn optional_value(&self, s: Option<&&str>, default: u16) -> Result<u16, String> {
+        match s {
+            Some(s) => self.value(s),
+            None => Ok(default),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Parses a number as the assemblers write it: 1234h, 0x1234, $1234 or
+/// 1234, hexadecimal by default. As on the assemblers, the h suffix
+/// needs a leading digit, 0cafeh, and `each` is not a number.
+pub fn parse_number(s: &str) -> Option<u16> {
+    let lower = s.to_ascii_lowercase();
+    let digits = if let Some(d) = lower.strip_prefix("0x") {
+        d
+    } else if let Some(d) = lower.strip_prefix('$') {
+        d
+    } else if let Some(d) = lower.strip_suffix('h').filter(|d| d.starts_with(|c: char| c.is_ascii_digit())) {
+        d
+    } else {
+        &lower
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    u16::from_str_radix(digits, 16).ok()
+}
+
+/// Names of the addresses, shown on the disassembly and usable instead
+/// of the addresses.
+#[derive(Default)]
+pub struct Symbols {
+    by_name: HashMap<String, u16>,
+    by_address: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    /// Loads a symbol file, returns the number of symbols loaded.
+    ///
+    /// Each line has a name and an address, in any order, optionally
+    /// with EQU or = as the assemblers and linkers write them:
+    /// `START EQU 0100H`, `START = $0100` or `0100 START`. Text after a
+    /// semicolon is ignored.
+    pub fn load(&mut self, path: &Path) -> Result<usize, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+        let mut count = 0;
+        for line in text.lines() {
+            if let Some((name, address)) = parse_line(line) {
+                self.add(&name, address);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn add(&mut self, name: &str, address: u16) {
+        if let Some(previous) = self.by_name.insert(name.to_string(), address) {
+            self.by_address.remove(&previous);
+        }
+        self.by_address.insert(address, name.to_string());
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.by_address.get(&address).map(|n| n.as_str())
+    }
+}
+
+fn parse_line(line: &str) -> Option<(String, u16)> {
+    let line = line.split(';').next().unwrap_or("");
+    let tokens: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == '=')
+        .map(|t| t.trim_end_matches(':'))
+        .filter(|t| !t.is_empty() && !t.eq_ignore_ascii_case("equ"))
+        .collect();
+    if tokens.len() != 2 {
+        return None;
+    }
+    // Names as CAFE are also hex numbers, the name is the other token
+    let [first, second] = [tokens[0], tokens[1]];
+    if let Some(address) = parse_number(second).filter(|_| is_name(first)) {
+        Some((first.to_string(), address))
+    } else {
+        let address = parse_number(first)?;
+        is_name(second).then(|| (second.to_string(), address))
+    }
+}
+
+fn is_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '@')
+}
@@ -0,0 +1,161 @@
+use super::timing::BusCycle;
+
+/// Model of the delays the hardware adds to the bus cycles
+///
+/// It can be consulted from `Machine::wait_states()` to stretch the
+/// accesses as the memory and I/O contention of some machines do.
+pub trait ContentionModel {
+    /// Returns the T-states the cycle starting at `t_state` is delayed
+    fn delay(&self, cycle: BusCycle, address: u16, t_state: u64) -> u8;
+}
+
+/// Memory and I/O contention of the ZX Spectrum ULA
+///
+/// While the ULA reads the screen, the CPU accesses to the memory at
+/// 0x4000-0x7fff are delayed. On the 128K the odd banks are contended
+/// too when paged at 0xc000. Internal cycles with a contended address on
+/// the bus are delayed as well.
+///
+/// The frame T-state is the T-state since the start of the frame, as
+/// set with `set_frame_start()`, usually when the interrupt is raised.
+pub struct SpectrumContention {
+    frame_length: u64,
+    first_contended: u64,
+    line_length: u64,
+    paging: bool,
+    bank_contended: bool,
+    frame_start: u64,
+}
+
+const SCREEN_LINES: u64 = 192;
+const CONTENDED_LINE_LENGTH: u64 = 128;
+const DELAY_PATTERN: [u8; 8] = [6, 5, 4, 3, 2, 1, 0, 0];
+
+impl SpectrumContention {
+    /// Returns the contention model of the ZX Spectrum 16K and 48K
+    pub fn new_48k() -> SpectrumContention {
+        SpectrumContention {
+            frame_length: 69888,
+            first_contended: 14335,
+            line_length: 224,
+            paging: false,
+            bank_contended: false,
+            frame_start: 0,
+        }
+    }
+
+    /// Returns the contention model of the ZX Spectrum 128K and +2
+    pub fn new_128k() -> SpectrumContention {
+        SpectrumContention {
+            frame_length: 70908,
+            first_contended: 14361,
+            line_length: 228,
+            paging: true,
+            bank_contended: false,
+            frame_start: 0,
+        }
+    }
+
+    /// Sets the T-state where the current frame starts
+    pub fn set_frame_start(&mut self, t_state: u64) {
+        self.frame_start = t_state;
+    }
+
+    /// Sets the RAM bank paged at 0xc000 on the 128K. Ignored on the 48K.
+    pub fn set_paged_bank(&mut self, bank: u8) {
+        self.bank_contended = self.paging && bank & 1 == 1;
+    }
+
+    /// Returns the number of T-states of a frame
+    pub fn frame_length(&self) -> u64 {
+        self.frame_length
+    }
+
+    /// Returns the T-state within the frame
+    pub fn frame_t_state(&self, t_state: u64) -> u64 {
+        if t_state >= self.frame_start {
+            (t_state - self.frame_start) % self.frame_length
+        } else {
+            // Before the frame start, on the previous frames
+            self.frame_length - 1 - (self.frame_start - t_state - 1) % self.frame_length
+        }
+    }
+
+    /// Returns if the address is on contended memory
+    pub fn is_contended(&self, address: u16) -> bool {
+        match address >> 14 {
+            1 => true,
+            3 => self.bank_contended,
+            _ => false,
+        }
+    }
+
+    /// Returns the delay of an access to contended memory on a frame
+    /// T-state
+    pub fn delay_at(&self, frame_t_state: u64) -> u8 {
+        if frame_t_state < self.first_contended {
+            return 0;
+        }
+        let t = frame_t_state - self.first_contended;
+        if t >= SCREEN_LINES * self.line_length {
+            return 0;
+        }
+        let t_line = t % self.line_length;
+        if t_line >= CONTENDED_LINE_LENGTH {
+            return 0;
+        }
+        DELAY_PATTERN[(t_line % 8) as usize]
+    }
+
+    fn memory_delay(&self, address: u16, t_state: u64) -> u8 {
+        if self.is_contended(address) {
+            self.delay_at(self.frame_t_state(t_state))
+        } else {
+            0
+        }
+    }
+
+    fn io_delay(&self, port: u16, t_state: u64) -> u8 {
+        /*
+        The high byte of the port is on the address bus as on a memory
+        access, and the ULA ports are the even ones. The 4 T-states of the
+        cycle are contended depending on both:
+            High byte contended, even port:  C:1, C:3
+            High byte contended, odd port:   C:1, C:1, C:1, C:1
+            High byte uncontended, even:     N:1, C:3
+            High byte uncontended, odd:      N:4
+        */
+        let contended = self.is_contended(port);
+        let ula = port & 1 == 0;
+        let mut t = t_state;
+        if contended {
+            t += self.memory_delay(port, t) as u64;
+        }
+        t += 1;
+        if ula {
+            t += self.delay_at(self.frame_t_state(t)) as u64;
+            t += 3;
+        } else if contended {
+            for _ in 0..3 {
+                t += self.memory_delay(port, t) as u64;
+                t += 1;
+            }
+        } else {
+            t += 3;
+        }
+        (t - t_state - 4) as u8
+    }
+}
+
+impl ContentionModel for SpectrumContention {
+    fn delay(&self, cycle: BusCycle, address: u16, t_state: u64) -> u8 {
+        match cycle {
+            BusCycle::OpcodeFetch
+            | BusCycle::MemoryRead
+            | BusCycle::MemoryWrite
+            | BusCycle::Internal => self.memory_delay(address, t_state),
+            BusCycle::IoRead | BusCycle::IoWrite => self.io_delay(address, t_state),
+            BusCycle::InterruptAck | BusCycle::Refresh => 0,
+        }
+    }
+}
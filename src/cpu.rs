@@ -1,23 +1,49 @@
 use std::io;
 
+use super::debugger::{breakpoint_pc, Debugger, StopReason};
 use super::decoder_z80::DecoderZ80;
 use super::decoder_8080::Decoder8080;
+use super::decoder_8085::Decoder8085;
+use super::decoder_lr35902::DecoderLr35902;
 use super::environment::Environment;
 use super::machine::Machine;
-use super::opcode::Opcode;
-use super::registers::{Reg16, Reg8, Registers};
-use super::state::State;
+use super::opcode::{Flow, Opcode};
+use super::registers::{Flag, Reg16, Reg8, Registers};
+use super::state::{State, INT_DATA_SIZE};
+use super::tracer::{DefaultFormat, StdoutTracer, TraceRecord, Tracer};
+use super::timing::{TimingModel, TIMING_8080, TIMING_8085, TIMING_EZ80, TIMING_LR35902, TIMING_R800, TIMING_Z180, TIMING_Z80};
+use super::z180::Z180;
 
 const IRQ_ADDRESS: u16 = 0x0038;
 const NMI_ADDRESS: u16 = 0x0066;
+const TRAP_ADDRESS: u16 = 0x0024;
+const RST5_5_ADDRESS: u16 = 0x002c;
+const VBLANK_ADDRESS: u16 = 0x0040;
+const IE_ADDRESS: u16 = 0xffff;
+const IF_ADDRESS: u16 = 0xff0f;
+
+// End of a step over or out
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum StepEnd {
+    // An instruction executed out of the interrupts taken meanwhile
+    Instruction,
+    // PC is on the address with the stack as it was
+    Address(u16),
+    // Only a return leaving the subroutine
+    Return,
+}
 
 /// The Z80 cpu emulator.
 ///
 /// Executes Z80 instructions changing the cpu State and Machine
 pub struct Cpu {
     state: State,
-    trace: bool,
+    tracer: Option<Box<dyn Tracer + Send + Sync>>,
+    debugger: Debugger,
+    strict: bool,
+    syntax: Syntax,
     decoder: Box<dyn Decoder + Send + Sync>,
+    timing: &'static TimingModel,
 }
 
 // Ensure that the Cpu is Send and Sync and can be used with async code
@@ -26,8 +52,47 @@ const _: () = {
     let _ = assert_send::<Cpu>;
 };
 
+/// Z80 variants emulated. They differ on undocumented behaviour:
+/// the flags 3 and 5 on SCF and CCF, the value written by OUT (C), 0 and
+/// P/V when an interrupt is accepted after LD A, I or LD A, R. P/V is
+/// reset then on the NMOS parts and kept on the CMOS parts, see
+/// `is_cmos()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CpuModel {
+    /// Zilog NMOS Z80, the default
+    ZilogNmos,
+    /// Zilog CMOS Z84C00
+    ZilogCmos,
+    /// NEC NMOS clones like the uPD780C
+    NecNmos,
+    /// ST CMOS clones like the Z84C00
+    StCmos,
+}
+
+impl CpuModel {
+    /// Returns true for the CMOS parts, the Zilog and the ST ones
+    pub fn is_cmos(self) -> bool {
+        matches!(self, CpuModel::ZilogCmos | CpuModel::StCmos)
+    }
+}
+
+/// Assembly syntax used to disassemble
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    /// Zilog mnemonics, as `LD A, B` or `JP nn`. The default
+    Zilog,
+    /// Intel mnemonics, as `MOV A,B` or `JMP nn`. Only for the 8080 and
+    /// the 8085, the other CPUs use the Zilog mnemonics.
+    Intel,
+}
+
 pub(crate) trait Decoder {
     fn decode(&self, env: &mut Environment) -> &Opcode;
+
+    // Mnemonics indexed by the opcode byte, for the CPUs with Intel syntax
+    fn intel_mnemonics(&self) -> Option<&'static [&'static str; 256]> {
+        None
+    }
 }
 
 impl Cpu {
@@ -41,8 +106,12 @@ impl Cpu {
     pub fn new_z80() -> Cpu {
         Cpu {
             state: State::new(),
-            trace: false,
-            decoder: Box::new(DecoderZ80::new())
+            tracer: None,
+            debugger: Debugger::default(),
+            strict: false,
+            syntax: Syntax::Zilog,
+            decoder: Box::new(DecoderZ80::new()),
+            timing: &TIMING_Z80,
         }
     }
 
@@ -50,14 +119,124 @@ impl Cpu {
     pub fn new_8080() -> Cpu {
         let mut cpu = Cpu {
             state: State::new(),
-            trace: false,
-            decoder: Box::new(Decoder8080::new())
+            tracer: None,
+            debugger: Debugger::default(),
+            strict: false,
+            syntax: Syntax::Zilog,
+            decoder: Box::new(Decoder8080::new()),
+            timing: &TIMING_8080,
         };
 
         cpu.state.reg.set_8080();
         cpu
     }
 
+    /// Returns an Intel 8085 Cpu instance
+    pub fn new_8085() -> Cpu {
+        let mut cpu = Cpu {
+            state: State::new(),
+            tracer: None,
+            debugger: Debugger::default(),
+            strict: false,
+            syntax: Syntax::Zilog,
+            decoder: Box::new(Decoder8085::new()),
+            timing: &TIMING_8085,
+        };
+
+        cpu.state.reg.set_8085();
+        cpu
+    }
+
+    /// Returns a Zilog Z180 Cpu instance, also Hitachi HD64180. It
+    /// includes the MMU, the ASCI, the PRT timers and the DMA
+    /// controller. The physical memory is accessed with
+    /// `Machine::peek_physical()` and `Machine::poke_physical()`.
+    pub fn new_z180() -> Cpu {
+        let mut cpu = Cpu {
+            state: State::new(),
+            tracer: None,
+            debugger: Debugger::default(),
+            strict: false,
+            syntax: Syntax::Zilog,
+            decoder: Box::new(DecoderZ80::new_z180()),
+            timing: &TIMING_Z180,
+        };
+
+        cpu.state.z180 = Some(Z180::new());
+        cpu
+    }
+
+    /// Returns a Z80N Cpu instance, the Z80 of the ZX Spectrum Next with
+    /// its extended opcodes. NEXTREG calls `Machine::nextreg()`.
+    pub fn new_z80n() -> Cpu {
+        Cpu {
+            state: State::new(),
+            tracer: None,
+            debugger: Debugger::default(),
+            strict: false,
+            syntax: Syntax::Zilog,
+            decoder: Box::new(DecoderZ80::new_z80n()),
+            timing: &TIMING_Z80,
+        }
+    }
+
+    /// Returns an ASCII R800 Cpu instance, the CPU of the MSX turboR.
+    /// It adds MULUB and MULUW to the Z80. The cycles are clocks, the
+    /// memory accesses take one more when they are on a 256 byte page
+    /// other than the one of the previous access.
+    pub fn new_r800() -> Cpu {
+        Cpu {
+            state: State::new(),
+            tracer: None,
+            debugger: Debugger::default(),
+            strict: false,
+            syntax: Syntax::Zilog,
+            decoder: Box::new(DecoderZ80::new_r800()),
+            timing: &TIMING_R800,
+        }
+    }
+
+    /// Returns a Zilog eZ80 Cpu instance, as on the Agon Light. It starts
+    /// on Z80 mode, with 16 bit registers and MBASE as the upper byte of
+    /// the addresses. In ADL mode the registers, the PC and the addresses
+    /// are 24 bits wide. The memory is accessed with
+    /// `Machine::peek_physical()` and `Machine::poke_physical()`. The
+    /// cycles are clocks without wait states.
+    pub fn new_ez80() -> Cpu {
+        let mut cpu = Cpu {
+            state: State::new(),
+            tracer: None,
+            debugger: Debugger::default(),
+            strict: false,
+            syntax: Syntax::Zilog,
+            decoder: Box::new(DecoderZ80::new_ez80()),
+            timing: &TIMING_EZ80,
+        };
+
+        cpu.state.reg.set_ez80();
+        cpu
+    }
+
+    /// Returns a Sharp LR35902 Cpu instance, the CPU of the Game Boy.
+    /// The interrupt enable register IE at FFFFh and the interrupt flags
+    /// IF at FF0Fh are accessed with `Machine::peek()` and
+    /// `Machine::poke()`, the devices request interrupts setting the
+    /// bits of IF. The cycles are machine cycles of 4 clocks.
+    pub fn new_lr35902() -> Cpu {
+        let mut cpu = Cpu {
+            state: State::new(),
+            tracer: None,
+            debugger: Debugger::default(),
+            strict: false,
+            syntax: Syntax::Zilog,
+            decoder: Box::new(DecoderLr35902::new()),
+            timing: &TIMING_LR35902,
+        };
+
+        cpu.state.reg.set_lr35902();
+        cpu
+    }
+
 }
 
 impl Default for Cpu {
@@ -79,87 +258,496 @@ impl Cpu {
             return
         }
 
-        let mut env = Environment::new(&mut self.state, sys);
+        let mut env = Environment::new(&mut self.state, sys, Some(self.timing));
+        env.set_watchpoints(self.debugger.watchpoints());
         if env.state.reset_pending {
             env.state.reset_pending = false;
             env.state.nmi_pending = false;
             env.state.halted = false;
             env.state.reg.reset();
-            env.state.cycle = env.state.cycle.wrapping_add(3);
-        }
-        else if env.state.nmi_pending {
+            env.state.rst_mask = 0x07;
+            env.state.rst_pending &= !0x04;
+            let cycle = env.state.cycle;
+            if let Some(z180) = &mut env.state.z180 {
+                z180.reset(cycle);
+            }
+            env.add_cycles(3);
+        } else if env.state.reg.is_lr35902() {
+            // The requests on IF enabled on IE wake up a HALT even with
+            // IME, the interrupt master enable, off
+            let flags = env.sys.peek(IF_ADDRESS);
+            let pending = env.sys.peek(IE_ADDRESS) & flags & 0x1f;
+            if pending != 0 {
+                if env.state.halted {
+                    env.state.halted = false;
+                    env.add_cycles(1);
+                }
+                if env.state.reg.get_interrupt_mode().0 && !env.state.int_just_enabled {
+                    // VBlank, LCD STAT, timer, serial and joypad, in order
+                    // of priority
+                    let line = pending.trailing_zeros() as u16;
+                    env.sys.poke(IF_ADDRESS, flags & !(1 << line));
+                    env.state.reg.set_interrupts(false);
+                    env.subroutine_call(VBLANK_ADDRESS + line * 8);
+                    env.add_cycles(5);
+                }
+            }
+        } else if env.state.nmi_pending && env.state.reg.is_8085() {
+            // TRAP
             env.state.nmi_pending = false;
             env.state.halted = false;
             env.state.reg.start_nmi();
-            env.state.cycle = env.state.cycle.wrapping_add(11);
+            Self::restart_8085(&mut env, TRAP_ADDRESS);
+        } else if env.state.nmi_pending {
+            env.state.nmi_pending = false;
+            env.state.halted = false;
+            env.state.reg.start_nmi();
+            if let Some(z180) = &mut env.state.z180 {
+                z180.nmi();
+            }
+            env.discard_opcode_fetch();
+            env.state.reg.increment_r();
+            env.internal_cycles(env.ir(), 1);
             env.subroutine_call(NMI_ADDRESS);
-        } else if env.state.int_signaled {
+            env.add_cycles(11);
+        } else if env.state.rst_pending & !env.state.rst_mask != 0
+                && env.state.reg.get_interrupt_mode().0 && !env.state.int_just_enabled {
+            // 8085 RST 7.5, 6.5 and 5.5, in order of priority
+            let pending = env.state.rst_pending & !env.state.rst_mask;
+            let line = 7 - pending.leading_zeros() as u16;
+            if line == 2 {
+                // Reset the RST 7.5 latch
+                env.state.rst_pending &= !0x04;
+            }
+            env.state.halted = false;
+            env.state.reg.set_interrupts(false);
+            Self::restart_8085(&mut env, RST5_5_ADDRESS + line * 8);
+        } else if env.state.int_signaled
+                && !matches!(&env.state.z180, Some(z180) if !z180.int0_enabled()) {
             let (int_enabled, int_mode) = env.state.reg.get_interrupt_mode();
             if int_enabled && !env.state.int_just_enabled {
+                if env.state.ld_a_ir && !env.state.model.is_cmos() {
+                    // On the NMOS parts IFF2 is cleared before P/V is
+                    // set by the LD A, I or LD A, R just executed
+                    env.state.reg.clear_flag(Flag::P);
+                }
                 env.state.halted = false;
                 env.state.reg.set_interrupts(false);
+                env.state.int_data_index = Some(0);
                 match int_mode {
-                    0 => panic!("Interrupt mode 0 not implemented"),
+                    0 => {
+                        // The device places an instruction on the data bus,
+                        // usually a RST or a CALL. The acknowledge cycle is
+                        // longer than the opcode fetch on the Z80. R is
+                        // incremented by the decoder as on a regular opcode
+                        // fetch.
+                        let opcode = self.decoder.decode(&mut env);
+                        env.clear_branch_taken();
+                        env.state.reg.clear_flags_modified();
+                        opcode.execute(&mut env);
+                        env.state.q = env.state.reg.q();
+                        env.add_cycles((self.timing.interrupt_ack - self.timing.opcode_fetch) as u64);
+                        env.advance_cycles(opcode);
+                        env.clear_index();
+                        env.clear_suffix();
+                    },
                     1 => {
-                        env.state.cycle = env.state.cycle.wrapping_add(13);
+                        // The data bus is ignored
+                        env.interrupt_ack();
+                        env.state.reg.increment_r();
+                        env.internal_cycles(env.ir(), 1);
                         env.subroutine_call(IRQ_ADDRESS);
+                        env.add_cycles(13);
+                    },
+                    2 => {
+                        // The vector is built with I as the high byte and
+                        // the byte on the data bus as the low byte
+                        let vector = ((env.state.reg.get8(Reg8::I) as u16) << 8)
+                            | env.interrupt_ack() as u16;
+                        env.state.reg.increment_r();
+                        env.internal_cycles(env.ir(), 1);
+                        env.vectored_call(vector);
+                        env.add_cycles(19);
                     },
-                    2 => panic!("Interrupt mode 2 not implemented"),
                     _ => panic!("Invalid interrupt mode")
                 }
+                env.state.int_data_index = None;
+            }
+        } else if let Some(vector) = env.state.z180.as_ref().and_then(Z180::interrupt_vector) {
+            // Z180 INT1, INT2 and the internal interrupts, vectored as
+            // IM 2 without acknowledge, with IL on the low byte
+            if env.state.reg.get_interrupt_mode().0 && !env.state.int_just_enabled {
+                env.state.halted = false;
+                env.state.reg.set_interrupts(false);
+                let vector = ((env.state.reg.get8(Reg8::I) as u16) << 8) | vector as u16;
+                env.vectored_call(vector);
+                env.add_cycles(18);
             }
         }
 
+        if env.state.halted && env.state.reg.is_lr35902() {
+            // HALT and STOP wait for a request on IE and IF
+            env.add_cycles(1);
+            return;
+        }
+
+        if env.state.halted && env.state.z180.is_some() {
+            // The Z180 peripherals run while HALT or SLP wait for an
+            // interrupt
+            env.add_cycles(3);
+            Self::step_z180(&mut env);
+            return;
+        }
+
         let pc = env.state.reg.pc();
+        let trace_start = if self.tracer.is_some() {
+            let mut memory = [0; 4];
+            for (i, byte) in memory.iter_mut().enumerate() {
+                *byte = env.peek_pc_offset(i as u16);
+            }
+            env.start_recording();
+            Some((memory, env.state.reg.clone(), env.state.cycle))
+        } else {
+            None
+        };
+        env.state.opcode_depth = env.state.call_depth;
         let opcode = self.decoder.decode(&mut env);
-        if self.trace {
-            print!("==> {:04x}: {:20}", pc, opcode.disasm(&mut env));
+        if self.strict && opcode.alias {
+            let code = env.sys.peek(pc);
+            env.sys.undocumented_opcode(pc, code);
         }
+        let trace = trace_start.map(|(memory, before, cycle)| {
+            let disasm = Self::disasm_opcode(self.decoder.as_ref(), opcode, pc, self.syntax, &mut env);
+            (disasm, memory, before, cycle)
+        });
 
         env.clear_branch_taken();
         env.clear_int_just_enabled();
+        env.state.ld_a_ir = false;
+        env.state.reg.clear_flags_modified();
         opcode.execute(&mut env);
+        env.state.q = env.state.reg.q();
         env.advance_cycles(opcode);
         env.clear_index();
+        env.clear_suffix();
+        Self::step_z180(&mut env);
+
+        if let (Some(tracer), Some((disasm, memory, before, cycle))) = (&mut self.tracer, trace) {
+            let (bytes, accesses) = env.take_recording();
+            let record = TraceRecord {
+                pc,
+                bytes,
+                memory,
+                disasm,
+                before,
+                after: env.state.reg.clone(),
+                cycle,
+                cycles: env.state.cycle.wrapping_sub(cycle),
+                accesses,
+            };
+            tracer.trace(&record);
+        }
+    }
+
+    /// Executes instructions until a breakpoint or a watchpoint is hit,
+    /// the CPU halts or `instructions` have been executed. The
+    /// instruction on PC when called is executed even with a breakpoint,
+    /// to resume after a stop.
+    ///
+    /// The LR35902 is halted for `run()` when IME is off and no request
+    /// on IF is enabled on IE, as after `DI; HALT`. The requests raised
+    /// by the machine afterwards wake it up on the next call.
+    ///
+    /// # Arguments
+    ///
+    /// * `sys` - A representation of the emulated machine that has the Machine trait
+    /// * `instructions` - The maximum number of instructions to execute
+    ///
+    pub fn run(&mut self, sys: &mut dyn Machine, instructions: u64) -> StopReason {
+        self.run_with_stop(sys, instructions, None, true)
+    }
+
+    /// Executes instructions as `run()` with a temporary breakpoint at
+    /// `address`
+    pub fn run_to(&mut self, sys: &mut dyn Machine, address: u32, instructions: u64) -> StopReason {
+        self.run_with_stop(sys, instructions, Some(address), true)
+    }
+
+    /// Executes instructions as `run_to()` with `target`, or as `run()`
+    /// without it, but a breakpoint or the target on PC stops before
+    /// executing anything. To continue a run split in slices, as to
+    /// check for a request to stop between them.
+    pub fn run_slice(&mut self, sys: &mut dyn Machine, target: Option<u32>, instructions: u64) -> StopReason {
+        self.run_with_stop(sys, instructions, target, false)
+    }
+
+    fn run_with_stop(&mut self, sys: &mut dyn Machine, instructions: u64, stop: Option<u32>, resume: bool) -> StopReason {
+        for i in 0..instructions {
+            if self.is_halted_on(sys) {
+                return StopReason::Halted;
+            }
+            let pc = breakpoint_pc(&self.state.reg);
+            if (i > 0 || !resume) && (stop == Some(pc) || self.debugger.is_breakpoint(&self.state.reg)) {
+                return StopReason::Breakpoint(pc);
+            }
+
+            self.state.watch_hit = None;
+            self.execute_instruction(sys);
+            if let Some(access) = self.state.watch_hit.take() {
+                return StopReason::Watchpoint(access);
+            }
+        }
+        StopReason::InstructionLimit
+    }
+
+    /// Executes the instruction on PC. A `CALL` or a `RST` runs until the
+    /// subroutine returns, a `DJNZ` or a repeating block instruction as
+    /// `LDIR` runs until the loop ends. Returns `StopReason::Stepped`
+    /// when done.
+    ///
+    /// The end is detected by the stack pointer, not by the return
+    /// address: recursive calls, interrupts serviced meanwhile and
+    /// subroutines that drop their return address are handled. Stops
+    /// earlier as `run()` on breakpoints, watchpoints, halt or after
+    /// `instructions`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sys` - A representation of the emulated machine that has the Machine trait
+    /// * `instructions` - The maximum number of instructions to execute
+    ///
+    pub fn step_over(&mut self, sys: &mut dyn Machine, instructions: u64) -> StopReason {
+        let pc = self.state.reg.pc();
+        let (flow, length) = self.inspect(sys, |_, opcode, _| opcode.flow);
+        self.state.reg.set_pc(pc);
+        let next = pc.wrapping_add(length);
+        let depth = self.state.call_depth;
+        match flow {
+            Flow::Call => self.step_until(sys, instructions, StepEnd::Address(next), depth + 1),
+            Flow::Loop => self.step_until(sys, instructions, StepEnd::Address(next), depth),
+            Flow::Other => self.step_until(sys, instructions, StepEnd::Instruction, depth),
+        }
+    }
+
+    /// Executes instructions until the current subroutine returns.
+    /// Returns `StopReason::Stepped` when done.
+    ///
+    /// A return leaves the subroutine when it takes the stack pointer
+    /// above its value on the call to `step_out()` and it is not the end
+    /// of an interrupt or of a nested call. Returns done with a jump, as
+    /// `POP HL` and `JP (HL)`, are not detected. Stops earlier as `run()`
+    /// on breakpoints, watchpoints, halt or after `instructions`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sys` - A representation of the emulated machine that has the Machine trait
+    /// * `instructions` - The maximum number of instructions to execute
+    ///
+    pub fn step_out(&mut self, sys: &mut dyn Machine, instructions: u64) -> StopReason {
+        let depth = self.state.call_depth;
+        self.step_until(sys, instructions, StepEnd::Return, depth)
+    }
+
+    // Runs until the end, or until a return takes the call depth below
+    // depth and the stack above it was
+    fn step_until(&mut self, sys: &mut dyn Machine, instructions: u64, end: StepEnd, depth: i64) -> StopReason {
+        let sp = self.stack_pointer();
+        for i in 0..instructions {
+            if self.is_halted_on(sys) {
+                return StopReason::Halted;
+            }
+            if i > 0 && self.debugger.is_breakpoint(&self.state.reg) {
+                return StopReason::Breakpoint(breakpoint_pc(&self.state.reg));
+            }
+
+            let previous_depth = self.state.call_depth;
+            self.state.watch_hit = None;
+            self.execute_instruction(sys);
+            if let Some(access) = self.state.watch_hit.take() {
+                return StopReason::Watchpoint(access);
+            }
+
+            let returned = self.state.call_depth < previous_depth && self.state.call_depth < depth;
+            let popped = self.stack_popped(sp);
+            let done = match end {
+                StepEnd::Instruction => self.state.opcode_depth == depth,
+                StepEnd::Address(next) => next == self.state.reg.pc() && popped >= 0,
+                StepEnd::Return => false,
+            };
+            if done || (returned && popped > 0) {
+                return StopReason::Stepped;
+            }
+        }
+        StopReason::InstructionLimit
+    }
+
+    fn stack_pointer(&self) -> u32 {
+        if self.state.reg.is_adl() {
+            self.state.reg.get24(Reg16::SP)
+        } else {
+            self.state.reg.get16(Reg16::SP) as u32
+        }
+    }
+
+    // Bytes popped from the stack since SP was sp, negative if pushed.
+    // The stack wraps, it usually starts at 0000h.
+    fn stack_popped(&self, sp: u32) -> i32 {
+        let offset = self.stack_pointer().wrapping_sub(sp);
+        if self.state.reg.is_adl() {
+            ((offset << 8) as i32) >> 8
+        } else {
+            offset as u16 as i16 as i32
+        }
+    }
+
+    fn restart_8085(env: &mut Environment, address: u16) {
+        // TRAP and RST 5.5 to 7.5 are not acknowledged on the bus. The
+        // restart takes 12 T-states as a RST.
+        env.internal_cycles(env.state.reg.pc(), 6);
+        env.subroutine_call(address);
+        env.add_cycles(12);
+    }
+
+    fn step_z180(env: &mut Environment) {
+        let cycle = env.state.cycle;
+        if let Some(z180) = &mut env.state.z180 {
+            let cycles = z180.step(env.sys, cycle);
+            env.add_cycles(cycles);
+        }
+    }
 
-        if self.trace {
-            print!(" PC:{:04x} AF:{:04x} BC:{:04x} DE:{:04x} HL:{:04x} SP:{:04x} IX:{:04x} IY:{:04x} Flags:{:08b} Cycle:{:04}",
-                self.state.reg.pc(),
-                self.state.reg.get16(Reg16::AF),
-                self.state.reg.get16(Reg16::BC),
-                self.state.reg.get16(Reg16::DE),
-                self.state.reg.get16(Reg16::HL),
-                self.state.reg.get16(Reg16::SP),
-                self.state.reg.get16(Reg16::IX),
-                self.state.reg.get16(Reg16::IY),
-                self.state.reg.get8(Reg8::F),
-                self.state.cycle
-            );
-            println!(" [{:02x} {:02x} {:02x}]", sys.peek(pc),
-                sys.peek(pc.wrapping_add(1)), sys.peek(pc.wrapping_add(2)));
+    fn disasm_opcode(decoder: &dyn Decoder, opcode: &Opcode, pc: u16, syntax: Syntax, env: &mut Environment) -> String {
+        match (syntax, decoder.intel_mnemonics()) {
+            (Syntax::Intel, Some(mnemonics)) => {
+                let code = env.sys.peek(pc);
+                opcode.disasm_as(mnemonics[code as usize], env)
+            },
+            _ => opcode.disasm(env),
         }
     }
 
-    /// Returns the instruction in PC disassembled. PC is advanced.
+    /// Returns the instruction in PC disassembled with the syntax of the
+    /// CPU. PC is advanced.
     ///
     /// # Arguments
     ///
     /// * `sys` - A representation of the emulated machine that has the Machine trait
     ///
     pub fn disasm_instruction(&mut self, sys: &mut dyn Machine) -> String {
-        let mut env = Environment::new(&mut self.state, sys);
+        self.disasm_instruction_with(sys, self.syntax)
+    }
+
+    /// Returns the instruction in PC disassembled with the given syntax.
+    /// PC is advanced.
+    ///
+    /// # Arguments
+    ///
+    /// * `sys` - A representation of the emulated machine that has the Machine trait
+    /// * `syntax` - The assembly syntax, Zilog or Intel
+    ///
+    pub fn disasm_instruction_with(&mut self, sys: &mut dyn Machine, syntax: Syntax) -> String {
+        self.disasm_with_length(sys, syntax).0
+    }
+
+    /// Returns the instruction at `address` disassembled with the syntax
+    /// of the CPU and its length in bytes. PC is not changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `sys` - A representation of the emulated machine that has the Machine trait
+    /// * `address` - The address of the instruction
+    ///
+    pub fn disasm_at(&mut self, sys: &mut dyn Machine, address: u16) -> (String, u16) {
+        let pc = self.state.reg.pc();
+        self.state.reg.set_pc(address);
+        let disasm = self.disasm_with_length(sys, self.syntax);
+        self.state.reg.set_pc(pc);
+        disasm
+    }
+
+    fn disasm_with_length(&mut self, sys: &mut dyn Machine, syntax: Syntax) -> (String, u16) {
+        let pc = self.state.reg.pc();
+        self.inspect(sys, |decoder, opcode, env| {
+            Self::disasm_opcode(decoder, opcode, pc, syntax, env)
+        })
+    }
+
+    // Decodes the instruction on PC without executing it. Returns the
+    // result of f and the length of the instruction. PC is advanced
+    // past the opcode.
+    fn inspect<T>(&mut self, sys: &mut dyn Machine,
+            f: impl FnOnce(&dyn Decoder, &Opcode, &mut Environment) -> T) -> (T, u16) {
+        let r = self.state.reg.get8(Reg8::R);
+        let cycle = self.state.cycle;
+        let pc = self.state.reg.pc();
+        let mut env = Environment::new(&mut self.state, sys, None);
         let opcode = self.decoder.decode(&mut env);
-        opcode.disasm(&mut env)
+        let result = f(self.decoder.as_ref(), opcode, &mut env);
+        let length = env.state.reg.pc().wrapping_sub(pc) + opcode.operand_length(&env);
+        env.clear_index();
+        env.clear_suffix();
+        env.state.reg.set8(Reg8::R, r);
+        env.state.cycle = cycle;
+        (result, length)
+    }
+
+    /// Selects the Z80 variant to emulate. Defaults to `CpuModel::ZilogNmos`.
+    pub fn set_model(&mut self, model: CpuModel) {
+        self.state.model = model;
+    }
+
+    /// Returns the Z80 variant emulated
+    pub fn model(&self) -> CpuModel {
+        self.state.model
     }
 
     /// Activates or deactivates traces of the instruction executed and
-    /// the state of the registers.
+    /// the state of the registers on stdout. It replaces the tracer with
+    /// a `StdoutTracer` with the `DefaultFormat`, or removes it.
     ///
     /// # Arguments
     ///
     /// * `trace` - A bool defining the trace state to set
     pub fn set_trace(&mut self, trace: bool) {
-        self.trace = trace;
+        self.tracer = if trace {
+            Some(Box::new(StdoutTracer::new(DefaultFormat)))
+        } else {
+            None
+        };
+    }
+
+    /// Sets the tracer receiving a record of each instruction executed
+    pub fn set_tracer(&mut self, tracer: Box<dyn Tracer + Send + Sync>) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Removes the tracer and returns it
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer + Send + Sync>> {
+        self.tracer.take()
+    }
+
+    /// Selects the assembly syntax used by `disasm_instruction()` and the
+    /// traces. Defaults to `Syntax::Zilog`.
+    pub fn set_syntax(&mut self, syntax: Syntax) {
+        self.syntax = syntax;
+    }
+
+    /// Returns the assembly syntax used to disassemble
+    pub fn syntax(&self) -> Syntax {
+        self.syntax
+    }
+
+    /// Activates or deactivates the strict mode. The undocumented
+    /// duplicates of the 8080 opcodes are executed as on the silicon, in
+    /// strict mode they are also reported with
+    /// `Machine::undocumented_opcode()`.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Returns the breakpoints and watchpoints used by `run()`
+    pub fn debugger(&mut self) -> &mut Debugger {
+        &mut self.debugger
     }
 
     /// Returns a Registers struct to read and write on the Z80 registers
@@ -172,12 +760,29 @@ impl Cpu {
         &self.state.reg
     }
 
-    /// Returns if the Cpu has executed a HALT
+    /// Returns if the Cpu has executed a HALT. Always false on the
+    /// LR35902, `execute_instruction()` keeps counting cycles until IE
+    /// and IF wake it up. See `run()` for how it stops there.
     pub fn is_halted(&self) -> bool {
         self.state.halted
             && !self.state.nmi_pending
             && !self.state.reset_pending
             && !self.state.int_signaled
+            && self.state.rst_pending & !self.state.rst_mask == 0
+            && !self.state.z180.as_ref().is_some_and(Z180::is_running)
+            && !self.state.reg.is_lr35902()
+    }
+
+    // As is_halted(), the LR35902 is halted too while nothing can wake
+    // it up without the machine raising a request
+    fn is_halted_on(&self, sys: &mut dyn Machine) -> bool {
+        if self.state.reg.is_lr35902() {
+            self.state.halted
+                && !self.state.reg.get_interrupt_mode().0
+                && sys.peek(IE_ADDRESS) & sys.peek(IF_ADDRESS) & 0x1f == 0
+        } else {
+            self.is_halted()
+        }
     }
 
     /// Maskable interrupt request. It stays signaled until is is
@@ -186,11 +791,74 @@ impl Cpu {
         self.state.int_signaled = active;
     }
 
+    /// Sets the byte the interrupting device places on the data bus
+    /// when the maskable interrupt is acknowledged. On IM 2 it is the
+    /// low byte of the vector table address, on IM 0 and on the 8080 it
+    /// is the opcode to execute. Defaults to 0xff, RST 38h.
+    ///
+    /// It is used when `Machine::interrupt_ack()` returns `None`.
+    pub fn set_interrupt_data(&mut self, data: u8) {
+        self.state.int_data = [0xff; INT_DATA_SIZE];
+        self.state.int_data[0] = data;
+    }
+
+    /// Sets the instruction the interrupting device places on the data
+    /// bus when the maskable interrupt is acknowledged on IM 0 or on the
+    /// 8080. Up to 4 bytes, like the 3 bytes of a `CALL nn`. Longer
+    /// instructions are an `InvalidInput` error.
+    pub fn set_interrupt_instruction(&mut self, code: &[u8]) -> io::Result<()> {
+        if code.len() > INT_DATA_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Instruction too long for the data bus"));
+        }
+        self.state.int_data = [0xff; INT_DATA_SIZE];
+        self.state.int_data[..code.len()].copy_from_slice(code);
+        Ok(())
+    }
+
     /// Non maskable interrupt request
     pub fn signal_nmi(&mut self) {
         self.state.nmi_pending = true;
     }
 
+    /// 8085 TRAP request, non maskable. Alias of `signal_nmi()`
+    pub fn signal_trap(&mut self) {
+        self.signal_nmi();
+    }
+
+    /// 8085 RST 7.5 request. It is edge triggered, the request is latched
+    /// until accepted or reset with SIM.
+    pub fn signal_rst7_5(&mut self) {
+        self.state.rst_pending |= 0x04;
+    }
+
+    /// 8085 RST 6.5 request. It stays signaled until it is deactivated
+    /// by calling `signal_rst6_5(false)`.
+    pub fn signal_rst6_5(&mut self, active: bool) {
+        self.state.rst_pending = (self.state.rst_pending & !0x02) | ((active as u8) << 1);
+    }
+
+    /// 8085 RST 5.5 request. It stays signaled until it is deactivated
+    /// by calling `signal_rst5_5(false)`.
+    pub fn signal_rst5_5(&mut self, active: bool) {
+        self.state.rst_pending = (self.state.rst_pending & !0x01) | active as u8;
+    }
+
+    /// Z180 INT1 request. It stays signaled until it is deactivated by
+    /// calling `signal_int1(false)`. Ignored on other CPUs.
+    pub fn signal_int1(&mut self, active: bool) {
+        if let Some(z180) = &mut self.state.z180 {
+            z180.signal_int1(active);
+        }
+    }
+
+    /// Z180 INT2 request. It stays signaled until it is deactivated by
+    /// calling `signal_int2(false)`. Ignored on other CPUs.
+    pub fn signal_int2(&mut self, active: bool) {
+        if let Some(z180) = &mut self.state.z180 {
+            z180.signal_int2(active);
+        }
+    }
+
     /// Signal reset
     pub fn signal_reset(&mut self) {
         self.state.reset_pending = true;
@@ -0,0 +1,152 @@
+use super::registers::{Flag, Reg16, Reg8, Registers};
+use super::tracer::Access;
+
+/// Register value a conditional breakpoint requires
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// The 8 bits register has the value
+    Reg8(Reg8, u8),
+    /// The 16 bits register has the value
+    Reg16(Reg16, u16),
+    /// The flag is set or reset
+    Flag(Flag, bool),
+}
+
+impl Condition {
+    fn is_met(&self, reg: &Registers) -> bool {
+        match *self {
+            Condition::Reg8(r, value) => reg.get8(r) == value,
+            Condition::Reg16(rr, value) => reg.get16(rr) == value,
+            Condition::Flag(flag, value) => reg.get_flag(flag) == value,
+        }
+    }
+}
+
+/// Execution breakpoint, hit before the instruction at the address is
+/// executed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// Address of the instruction. 24 bits in eZ80 ADL mode, as
+    /// `Registers::pc24()`.
+    pub address: u32,
+    /// Hit only when the registers have the value, if present
+    pub condition: Option<Condition>,
+}
+
+/// Memory or port watchpoint, hit when an instruction does the access
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Watchpoint {
+    /// Memory read at the address. 24 bits on the eZ80 long accesses.
+    MemoryRead(u32),
+    /// Memory write at the address. 24 bits on the eZ80 long accesses.
+    MemoryWrite(u32),
+    /// Port read at the 16 bits address placed on the bus
+    PortIn(u16),
+    /// Port write at the 16 bits address placed on the bus
+    PortOut(u16),
+}
+
+impl Watchpoint {
+    fn is_hit(&self, access: Access) -> bool {
+        match (*self, access) {
+            (Watchpoint::MemoryRead(w), Access::MemoryRead { address, .. }) => w == address,
+            (Watchpoint::MemoryWrite(w), Access::MemoryWrite { address, .. }) => w == address,
+            (Watchpoint::PortIn(w), Access::PortIn { address, .. }) => w == address,
+            (Watchpoint::PortOut(w), Access::PortOut { address, .. }) => w == address,
+            _ => false,
+        }
+    }
+}
+
+/// Reason `Cpu::run()` stopped
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// PC is on a breakpoint, the instruction is not executed
+    Breakpoint(u32),
+    /// The last instruction executed did the access watched
+    Watchpoint(Access),
+    /// The CPU is halted waiting for an interrupt
+    Halted,
+    /// The instructions requested were executed
+    InstructionLimit,
+    /// The instruction stepped over or the subroutine stepped out of
+    /// completed, see `Cpu::step_over()` and `Cpu::step_out()`
+    Stepped,
+}
+
+/// Breakpoints and watchpoints of a Cpu, see `Cpu::debugger()`
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl Debugger {
+    /// Adds a breakpoint at the address
+    pub fn add_breakpoint(&mut self, address: u32) {
+        self.add(Breakpoint { address, condition: None });
+    }
+
+    /// Adds a breakpoint at the address hit only when the condition is met
+    pub fn add_conditional_breakpoint(&mut self, address: u32, condition: Condition) {
+        self.add(Breakpoint { address, condition: Some(condition) });
+    }
+
+    fn add(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Removes the breakpoints at the address, conditional or not
+    pub fn remove_breakpoint(&mut self, address: u32) {
+        self.breakpoints.retain(|b| b.address != address);
+    }
+
+    /// Returns the breakpoints
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Adds a watchpoint
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+    }
+
+    /// Removes a watchpoint
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.retain(|w| *w != watchpoint);
+    }
+
+    /// Returns the watchpoints
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Removes all the breakpoints and watchpoints
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    pub(crate) fn is_breakpoint(&self, reg: &Registers) -> bool {
+        let pc = breakpoint_pc(reg);
+        self.breakpoints.iter().any(|b| b.address == pc
+            && !matches!(&b.condition, Some(c) if !c.is_met(reg)))
+    }
+}
+
+// The address the breakpoints are compared with
+pub(crate) fn breakpoint_pc(reg: &Registers) -> u32 {
+    if reg.is_adl() {
+        reg.pc24()
+    } else {
+        reg.pc() as u32
+    }
+}
+
+pub(crate) fn is_watched(watchpoints: &[Watchpoint], access: Access) -> bool {
+    watchpoints.iter().any(|w| w.is_hit(access))
+}
@@ -9,6 +9,7 @@ use super::opcode_ld::*;
 use super::operators::*;
 use super::registers::*;
 use super::environment::*;
+use super::mnemonics_8080::*;
 
 /* See
     http://www.z80.info/decoding.htm
@@ -30,9 +31,13 @@ impl Decoder8080 {
 
 impl Decoder for Decoder8080 {
     fn decode(&self, env: &mut Environment) -> &Opcode {
-        let code = env.advance_pc();
+        let code = env.fetch_opcode();
         &self.no_prefix[code as usize]
     }
+
+    fn intel_mnemonics(&self) -> Option<&'static [&'static str; 256]> {
+        Some(&MNEMONICS_8080)
+    }
 }
 
 fn no_prefix_opcodes() -> [Opcode;256] {
@@ -41,7 +46,10 @@ fn no_prefix_opcodes() -> [Opcode;256] {
         let p = DecodingHelper::parts(c);
         let opcode = match p.x {
             0 => match p.z {
-                0 => build_nop(), // NOP
+                0 => match p.y {
+                    0 => build_nop(), // NOP
+                    _ => build_alias(build_nop(), "NOP"), // NOP, undocumented
+                },
                 1 => match p.q {
                     0 =>  build_ld_rr_nn(RP[p.p]), // LD rr, nn -- 16-bit load add
                     _ /*1*/ =>  build_add_hl_rr(RP[p.p]), // ADD HL, rr -- 16-bit add
@@ -85,14 +93,16 @@ fn no_prefix_opcodes() -> [Opcode;256] {
                 1 => match p.q {
                     0 => build_pop_rr(RP2[p.p]), // POP rr
                     _ /*1*/ => match p.p {
-                        0 | 1 => build_ret(), // RET
+                        0 => build_ret(), // RET
+                        1 => build_alias(build_ret(), "RET"), // RET, undocumented
                         2 => build_jp_hl(), // JP HL
                         _ /*3*/ => build_ld_sp_hl(), // LD SP, HL
                     },
                 },
                 2 => build_jp_eq(CC[p.y]), // JP cc, nn
                 3 => match p.y {
-                    0 | 1 => build_jp_unconditional(), // JP nn
+                    0 => build_jp_unconditional(), // JP nn
+                    1 => build_alias(build_jp_unconditional(), "JMP nn"), // JP nn, undocumented
                     2 => build_out_n_a(),  // OUT (n), A
                     3 => build_in_a_n(),   // IN A, (n)
                     4 => build_ex_psp_hl(), // EX (SP), HL
@@ -103,7 +113,10 @@ fn no_prefix_opcodes() -> [Opcode;256] {
                 4 => build_call_eq(CC[p.y]),
                 5 => match p.q {
                     0 => build_push_rr(RP2[p.p]), // PUSH rr
-                    _ /*1*/ => build_call(), // Call nn
+                    _ /*1*/ => match p.p {
+                        0 => build_call(), // Call nn
+                        _ => build_alias(build_call(), "CALL nn"), // Call nn, undocumented
+                    },
                 },
                 6 => build_operator_a_n(ALU[p.y]), // alu A, n
                 _ /*7*/ => build_rst(p.y as u8 * 8), // RST
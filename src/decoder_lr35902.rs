@@ -0,0 +1,284 @@
+use super::cpu::*;
+use super::opcode::*;
+use super::opcode_alu::*;
+use super::opcode_arith::*;
+use super::opcode_bits::*;
+use super::opcode_jumps::*;
+use super::opcode_ld::*;
+use super::opcode_lr35902::*;
+use super::operators::*;
+use super::registers::*;
+use super::environment::*;
+
+/* See
+    http://www.z80.info/decoding.htm
+    https://gbdev.io/gb-opcodes/optables/
+    https://www.pastraiser.com/cpu/gameboy/gameboy_opcodes.html
+*/
+
+pub struct DecoderLr35902 {
+    no_prefix: [Opcode; 256],
+    prefix_cb: [Opcode; 256],
+}
+
+impl DecoderLr35902 {
+    pub fn new() -> DecoderLr35902 {
+        DecoderLr35902 {
+            no_prefix: no_prefix_opcodes(),
+            prefix_cb: cb_prefix_opcodes(),
+        }
+    }
+}
+
+impl Decoder for DecoderLr35902 {
+    fn decode(&self, env: &mut Environment) -> &Opcode {
+        let code = env.fetch_opcode();
+        if code == 0xcb {
+            let code = env.fetch_opcode();
+            &self.prefix_cb[code as usize]
+        } else {
+            &self.no_prefix[code as usize]
+        }
+    }
+}
+
+fn no_prefix_opcodes() -> [Opcode;256] {
+    let mut opcodes_vector = Vec::with_capacity(256);
+    for c in 0..=255 {
+        let p = DecodingHelper::parts(c);
+        let opcode = match p.x {
+            0 => match p.z {
+                0 => match p.y {
+                    0 => build_nop(), // NOP
+                    1 => build_ld_pnn_rr(Reg16::SP, false), // LD (nn), SP
+                    2 => build_stop(), // STOP
+                    3 => build_jr_unconditional(), // JR d
+                    _ /*4..=7*/ => build_jr_eq(CC[p.y-4]), // JR cc, d
+                },
+                1 => match p.q {
+                    0 =>  build_ld_rr_nn(RP[p.p]), // LD rr, nn -- 16-bit load add
+                    _ /*1*/ =>  build_add_hl_rr(RP[p.p]), // ADD HL, rr -- 16-bit add
+                },
+                2 => match p.q {
+                    0 =>  match p.p {
+                        0 => build_ld_prr_a(Reg16::BC), // LD (BC), A
+                        1 => build_ld_prr_a(Reg16::DE), // LD (DE), A
+                        2 => build_ld_phl_a_lr35902(true), // LDI (HL), A
+                        _ /*3*/ => build_ld_phl_a_lr35902(false), // LDD (HL), A
+                    },
+                    _ /*1*/ =>  match p.p {
+                        0 => build_ld_a_prr(Reg16::BC), // LD A, (BC)
+                        1 => build_ld_a_prr(Reg16::DE), // LD A, (DE)
+                        2 => build_ld_a_phl_lr35902(true), // LDI A, (HL)
+                        _ /*3*/ => build_ld_a_phl_lr35902(false), // LDD A, (HL)
+                    }
+                },
+                3 => match p.q {
+                    0 =>  build_inc_dec_rr(RP[p.p], true), // INC rr -- 16-bit inc
+                    _ /*1*/ =>  build_inc_dec_rr(RP[p.p], false), // DEC rr -- 16-bit dec
+                },
+                4 => build_inc_r(R[p.y]), // INC r -- 8 bit inc
+                5 => build_dec_r(R[p.y]), // DEC r -- 8 bit dec
+                6 => build_ld_r_n(R[p.y]), // LD r, n -- 8 bit load imm
+                _ /*7*/ => match p.y {
+                    0..=3 => build_rot_a_lr35902(ROT[p.y]), // rotA
+                    4 => build_daa_lr35902(), // DAA, decimal adjust A
+                    5 => build_cpl(), // CPL, complement adjust A
+                    6 => build_scf(), // SCF, set carry flag
+                    _ /*7*/ => build_ccf_lr35902(), // CCF, clear carry flag
+                },
+            },
+            1 => match (p.z, p.y) {
+                (6, 6) => build_halt(), // HALT, exception instead of LD (HL), (HL)
+                _ => build_ld_r_r(R[p.y], R[p.z], false), // LD r[y], r[z] -- 8 bit load imm
+            },
+            2 => build_operator_a_r(R[p.z], ALU[p.y]), // alu A, r
+            _ /*3*/ => match p.z {
+                0 => match p.y {
+                    0..=3 => build_ret_eq(CC[p.y]), // RET cc
+                    4 => build_ldh_pn_a(), // LDH (n), A
+                    5 => build_add_sp_s(), // ADD SP, s
+                    6 => build_ldh_a_pn(), // LDH A, (n)
+                    _ /*7*/ => build_ld_hl_sp_s(), // LD HL, SP+s
+                },
+                1 => match p.q {
+                    0 => build_pop_rr(RP2[p.p]), // POP rr
+                    _ /*1*/ => match p.p {
+                        0 => build_ret(), // RET
+                        1 => build_reti_lr35902(), // RETI
+                        2 => build_jp_hl(), // JP HL
+                        _ /*3*/ => build_ld_sp_hl(), // LD SP, HL
+                    },
+                },
+                2 => match p.y {
+                    0..=3 => build_jp_eq(CC[p.y]), // JP cc, nn
+                    4 => build_ldh_pc_a(), // LDH (C), A
+                    5 => build_ld_pnn_a(), // LD (nn), A
+                    6 => build_ldh_a_pc(), // LDH A, (C)
+                    _ /*7*/ => build_ld_a_pnn(), // LD A, (nn)
+                },
+                3 => match p.y {
+                    0 => build_jp_unconditional(), // JP nn
+                    1 => build_not_an_opcode(), // CB prefix
+                    6 => build_disable_interrupts(), // DI
+                    7 => build_enable_interrupts(),  // EI
+                    _ => build_illegal_lr35902(c), // No OUT, IN, EX
+                },
+                4 => match p.y {
+                    0..=3 => build_call_eq(CC[p.y]), // CALL cc, nn
+                    _ => build_illegal_lr35902(c),
+                },
+                5 => match p.q {
+                    0 => build_push_rr(RP2[p.p]), // PUSH rr
+                    _ /*1*/ => match p.p {
+                        0 => build_call(), // Call nn
+                        _ => build_illegal_lr35902(c), // No DD, ED or FD prefixes
+                    },
+                },
+                6 => build_operator_a_n(ALU[p.y]), // alu A, n
+                _ /*7*/ => build_rst(p.y as u8 * 8), // RST
+                },
+        };
+        opcodes_vector.push(opcode);
+    }
+
+    let mut opcodes = opcodes_vector.try_into().unwrap_or_else(|_| { panic!("missing opcodes")});
+    load_cycle_information(&mut opcodes);
+    opcodes
+}
+
+fn cb_prefix_opcodes() -> [Opcode;256] {
+    let mut opcodes_vector = Vec::with_capacity(256);
+
+    for c in 0..=255 {
+        let p = DecodingHelper::parts(c);
+        let mut opcode = match p.x {
+            0 if p.y == 6 => build_swap_r(R[p.z]), // SWAP, instead of SLL
+            0 => build_rot_r(R[p.z], ROT[p.y], false, false), // Shifts
+            1 => build_bit_r(p.y as u8, R[p.z]), // BIT
+            2 => build_set_res_r(p.y as u8, R[p.z], false), // RES
+            _ /*3*/ => build_set_res_r(p.y as u8, R[p.z], true), // SET
+        };
+        opcode.cycles = PREFIX_CB_CYCLES[c as usize];
+        opcode.cycles_conditional = opcode.cycles;
+        opcodes_vector.push(opcode);
+    }
+
+    opcodes_vector.try_into().unwrap_or_else(|_| { panic!("missing opcodes")})
+}
+
+fn load_cycle_information(opcodes: &mut [Opcode; 256]) {
+
+    // Load cycle information
+    for c in 0..=255 {
+            opcodes[c].cycles = NO_PREFIX_CYCLES[c];
+            opcodes[c].cycles_conditional = opcodes[c].cycles;
+    }
+
+    //Load cycle information for conditional cases
+    for c in [0x20, 0x28, 0x30, 0x38] {
+        opcodes[c].cycles_conditional = 2; // JR cc, d
+    }
+    for c in [0xc0, 0xc8, 0xd0, 0xd8] {
+        opcodes[c].cycles_conditional = 2; // RET cc
+        opcodes[c + 2].cycles_conditional = 3; // JP cc, nn
+        opcodes[c + 4].cycles_conditional = 3; // CALL cc, nn
+    }
+}
+
+struct DecodingHelper {
+    // See notation in http://www.z80.info/decoding.htm
+    x: usize,
+    y: usize,
+    z: usize,
+    p: usize,
+    q: usize
+}
+
+impl DecodingHelper {
+    fn parts(code: u8) -> DecodingHelper {
+        DecodingHelper {
+            x: (code >> 6) as usize,
+            y: ((code >> 3) & 7) as usize,
+            z: (code & 7) as usize,
+            p: ((code >> 4) & 3) as usize,
+            q: ((code >> 3) & 1) as usize,
+        }
+    }
+}
+
+const RP:  [Reg16; 4] = [Reg16::BC, Reg16::DE, Reg16::HL, Reg16::SP];
+const RP2: [Reg16; 4] = [Reg16::BC, Reg16::DE, Reg16::HL, Reg16::AF];
+const R:  [Reg8; 8] = [Reg8::B, Reg8::C, Reg8::D, Reg8::E, Reg8::H, Reg8::L, Reg8::_HL, Reg8::A];
+
+// Only the Z and C conditions
+const CC: [(Flag, bool, &str); 4] = [
+    (Flag::Z, false, "NZ"),
+    (Flag::Z, true,  "Z"),
+    (Flag::C, false, "NC"),
+    (Flag::C, true,  "C"),
+];
+
+const ROT: [(ShiftDir, ShiftMode, &str); 8] = [
+    (ShiftDir::Left,  ShiftMode::RotateCarry, "RLC"),
+    (ShiftDir::Right, ShiftMode::RotateCarry, "RRC"),
+    (ShiftDir::Left,  ShiftMode::Rotate,      "RL" ),
+    (ShiftDir::Right, ShiftMode::Rotate,      "RR" ),
+    (ShiftDir::Left,  ShiftMode::Arithmetic,  "SLA"),
+    (ShiftDir::Right, ShiftMode::Arithmetic,  "SRA"),
+    (ShiftDir::Left,  ShiftMode::Logical,     "SLL"),
+    (ShiftDir::Right, ShiftMode::Logical,     "SRL"),
+];
+
+const ALU: [(Operator, &str); 8] = [
+    (operator_add, "ADD"),
+    (operator_adc, "ADC"),
+    (operator_sub, "SUB"),
+    (operator_sbc, "SBC"),
+    (operator_and, "AND"),
+    (operator_xor, "XOR"),
+    (operator_or,  "OR"),
+    (operator_cp,  "CP")
+];
+
+// From https://gbdev.io/gb-opcodes/optables/, in machine cycles
+const NO_PREFIX_CYCLES: [u8; 256] = [
+     1,  3,  2,  2,  1,  1,  2,  1,  5,  2,  2,  2,  1,  1,  2,  1,
+     1,  3,  2,  2,  1,  1,  2,  1,  3,  2,  2,  2,  1,  1,  2,  1,
+     3,  3,  2,  2,  1,  1,  2,  1,  3,  2,  2,  2,  1,  1,  2,  1,
+     3,  3,  2,  2,  3,  3,  3,  1,  3,  2,  2,  2,  1,  1,  2,  1,
+
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     2,  2,  2,  2,  2,  2,  1,  2,  1,  1,  1,  1,  1,  1,  2,  1,
+
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+
+     5,  3,  4,  4,  6,  4,  2,  4,  5,  4,  4,  0,  6,  6,  2,  4,
+     5,  3,  4,  1,  6,  4,  2,  4,  5,  4,  4,  1,  6,  1,  2,  4,
+     3,  3,  2,  1,  1,  4,  2,  4,  4,  1,  4,  1,  1,  1,  2,  4,
+     3,  3,  2,  1,  1,  4,  2,  4,  3,  2,  4,  1,  1,  1,  2,  4,
+];
+
+const PREFIX_CB_CYCLES: [u8; 256] = [
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+     2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+     2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+     2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+     2,  2,  2,  2,  2,  2,  4,  2,  2,  2,  2,  2,  2,  2,  4,  2,
+];
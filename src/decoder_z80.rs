@@ -6,9 +6,14 @@ use super::opcode_io::*;
 use super::opcode_bits::*;
 use super::opcode_jumps::*;
 use super::opcode_ld::*;
+use super::opcode_ez80::*;
+use super::opcode_z180::*;
+use super::opcode_z80n::*;
+use super::opcode_r800::*;
 use super::operators::*;
 use super::registers::*;
 use super::environment::*;
+use super::state::Suffix;
 
 /* See
     http://www.z80.info/decoding.htm
@@ -16,47 +21,133 @@ use super::environment::*;
     http://z80-heaven.wikidot.com/instructions-set
 */
 
+// CPUs of the Z80 family decoded as the Z80
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Variant {
+    Z80,
+    Z180,
+    Z80N,
+    R800,
+    EZ80,
+}
+
 pub struct DecoderZ80 {
     no_prefix: [Opcode; 256],
     prefix_cb: [Opcode; 256],
     prefix_cb_indexed: [Opcode; 256],
     prefix_ed: [Opcode; 256],
     has_displacement: [bool; 256],
+    has_index: [bool; 256],
+    // The opcodes without IX or IY versions trap after DD or FD on the Z180
+    index_trap: Option<Opcode>,
+    prefix_cycles: &'static PrefixCycles,
+    // The eZ80 suffixes are decoded as prefixes
+    has_suffixes: bool,
+}
+
+// Cycles counted by the decoder
+struct PrefixCycles {
+    // M1 cycle of the DD or FD prefix
+    index: u64,
+    // Read of the displacement and calculation of the address. For
+    // LD (IX+d), n part of it overlaps the read of n.
+    displacement: u64,
+    displacement_ld_n: u64,
 }
 
+const PREFIX_CYCLES_Z80: PrefixCycles = PrefixCycles {
+    index: 4,
+    displacement: 8,
+    displacement_ld_n: 5,
+};
+
+const PREFIX_CYCLES_Z180: PrefixCycles = PrefixCycles {
+    index: 3,
+    displacement: 5,
+    displacement_ld_n: 3,
+};
+
+const PREFIX_CYCLES_R800: PrefixCycles = PrefixCycles {
+    index: 1,
+    displacement: 2,
+    displacement_ld_n: 1,
+};
+
+// Counted on the bus
+const PREFIX_CYCLES_EZ80: PrefixCycles = PrefixCycles {
+    index: 0,
+    displacement: 0,
+    displacement_ld_n: 0,
+};
+
 impl Decoder for DecoderZ80 {
     fn decode(&self, env: &mut Environment) -> &Opcode {
-        let mut code = env.advance_pc();
+        let mut code = env.fetch_opcode();
+        env.state.reg.increment_r();
+
+        if self.has_suffixes {
+            if let Some(suffix) = Suffix::from_code(code) {
+                // The suffix is encoded before the instruction and its
+                // prefixes
+                env.state.suffix = Some(suffix);
+                code = env.fetch_opcode();
+                env.state.reg.increment_r();
+            }
+        }
 
         // Process prefixes even if reapeated
         while code == 0xdd || code == 0xfd {
             if code == 0xdd {
                 // DD prefix
                 env.set_index(Reg16::IX);
-                code = env.advance_pc();
             } else {
                 // FD prefix
                 env.set_index(Reg16::IY);
-                code = env.advance_pc();
+            }
+            // Each prefix is an M1 cycle, not included in the opcode cycles
+            env.add_cycles(self.prefix_cycles.index);
+            code = env.fetch_opcode();
+            env.state.reg.increment_r();
+        }
+
+        if env.is_alt_index() && !self.has_index[code as usize] {
+            if let Some(trap) = &self.index_trap {
+                return trap;
             }
         }
-        
+
         match code {
             0xcb => {
                 if env.is_alt_index() {
+                    // The displacement and the opcode are not fetched
+                    // with M1 cycles, R is not incremented
                     env.load_displacement();
-                    &self.prefix_cb_indexed[env.advance_pc() as usize]
+                    let code = env.advance_pc();
+                    env.internal_cycles(env.state.reg.pc().wrapping_sub(1), 2);
+                    &self.prefix_cb_indexed[code as usize]
                 } else {
-                    &self.prefix_cb[env.advance_pc() as usize]
+                    let code = env.fetch_opcode();
+                    env.state.reg.increment_r();
+                    &self.prefix_cb[code as usize]
                 }
             },
             0xed => {
                 env.clear_index(); // With ed, the current prefix is ignored
-                &self.prefix_ed[env.advance_pc() as usize]
+                let code = env.fetch_opcode();
+                env.state.reg.increment_r();
+                &self.prefix_ed[code as usize]
             },
             _ => {
                 if self.has_displacement[code as usize] && env.is_alt_index() {
                     env.load_displacement();
+                    // The displacement read and the calculation of the
+                    // address are not included in the opcode cycles
+                    if code == 0x36 {
+                        env.add_cycles(self.prefix_cycles.displacement_ld_n);
+                    } else {
+                        env.internal_cycles(env.state.reg.pc().wrapping_sub(1), 5);
+                        env.add_cycles(self.prefix_cycles.displacement);
+                    }
                 }
                 &self.no_prefix[code as usize]
             }
@@ -66,21 +157,63 @@ impl Decoder for DecoderZ80 {
 
 impl DecoderZ80 {
     pub fn new() -> DecoderZ80 {
+        Self::new_variant(Variant::Z80)
+    }
+
+    pub fn new_z180() -> DecoderZ80 {
+        Self::new_variant(Variant::Z180)
+    }
+
+    pub fn new_z80n() -> DecoderZ80 {
+        Self::new_variant(Variant::Z80N)
+    }
+
+    pub fn new_r800() -> DecoderZ80 {
+        Self::new_variant(Variant::R800)
+    }
+
+    pub fn new_ez80() -> DecoderZ80 {
+        Self::new_variant(Variant::EZ80)
+    }
+
+    fn new_variant(variant: Variant) -> DecoderZ80 {
         DecoderZ80 {
-            no_prefix: no_prefix_opcodes(),
-            prefix_cb: cb_prefix_opcodes(),
-            prefix_cb_indexed: cb_indexed_prefix_opcodes(),
-            prefix_ed: ed_prefix_opcodes(),
+            no_prefix: no_prefix_opcodes(variant),
+            prefix_cb: cb_prefix_opcodes(variant),
+            prefix_cb_indexed: cb_indexed_prefix_opcodes(variant),
+            prefix_ed: ed_prefix_opcodes(variant),
             has_displacement: displacements(),
+            has_index: indexed(),
+            index_trap: match variant {
+                Variant::Z180 => {
+                    // The prefix is counted by the decoder
+                    let mut trap = build_trap(false);
+                    trap.cycles = 9;
+                    trap.cycles_conditional = 9;
+                    Some(trap)
+                },
+                _ => None,
+            },
+            prefix_cycles: match variant {
+                Variant::Z80 | Variant::Z80N => &PREFIX_CYCLES_Z80,
+                Variant::Z180 => &PREFIX_CYCLES_Z180,
+                Variant::R800 => &PREFIX_CYCLES_R800,
+                Variant::EZ80 => &PREFIX_CYCLES_EZ80,
+            },
+            has_suffixes: variant == Variant::EZ80,
         }
     }
 }
 
-fn no_prefix_opcodes() -> [Opcode;256] {
+fn no_prefix_opcodes(variant: Variant) -> [Opcode;256] {
     let mut opcodes_vector = Vec::with_capacity(256);
     for c in 0..=255 {
         let p = DecodingHelper::parts(c);
-        let mut opcode = match p.x {
+        let variant_opcode = match variant {
+            Variant::EZ80 => no_prefix_opcode_ez80(&p),
+            _ => None,
+        };
+        let mut opcode = if let Some(opcode) = variant_opcode { opcode } else { match p.x {
             0 => match p.z {
                 0 => match p.y { // Relative jumps and assorted ops.
                     0 => build_nop(), // NOP
@@ -118,8 +251,14 @@ fn no_prefix_opcodes() -> [Opcode;256] {
                     0..=3 => build_rot_r(Reg8::A, ROT[p.y], true, false), // rotA
                     4 => build_daa(), // DAA, decimal adjust A
                     5 => build_cpl(), // CPL, complement adjust A
-                    6 => build_scf(), // SCF, set carry flag
-                    _ /*7*/ => build_ccf(), // CCF, clear carry flag
+                    6 => match variant {
+                        Variant::R800 => build_scf_r800(), // SCF, without Q
+                        _ => build_scf(), // SCF, set carry flag
+                    },
+                    _ /*7*/ => match variant {
+                        Variant::R800 => build_ccf_r800(), // CCF, without Q
+                        _ => build_ccf(), // CCF, clear carry flag
+                    },
                 },
             },
             1 => match (p.z, p.y) {
@@ -138,7 +277,10 @@ fn no_prefix_opcodes() -> [Opcode;256] {
                         _ /*3*/ => build_ld_sp_hl(), // LD SP, HL
                     },
                 },
-                2 => build_jp_eq(CC[p.y]), // JP cc, nn
+                2 => match variant {
+                    Variant::Z180 => build_jp_eq_early(CC[p.y]), // JP cc, nn
+                    _ => build_jp_eq(CC[p.y]), // JP cc, nn
+                },
                 3 => match p.y {
                     0 => build_jp_unconditional(), // JP nn
                     1 => build_not_an_opcode(), // CB prefix
@@ -149,7 +291,10 @@ fn no_prefix_opcodes() -> [Opcode;256] {
                     6 => build_disable_interrupts(), // DI
                     _ /*7*/ => build_enable_interrupts(),  // EI
                 }
-                4 => build_call_eq(CC[p.y]),
+                4 => match variant {
+                    Variant::Z180 => build_call_eq_early(CC[p.y]), // CALL cc, nn
+                    _ => build_call_eq(CC[p.y]), // CALL cc, nn
+                },
                 5 => match p.q {
                     0 => build_push_rr(RP2[p.p]), // PUSH rr
                     _ /*1*/ => match p.p {
@@ -162,29 +307,46 @@ fn no_prefix_opcodes() -> [Opcode;256] {
                 6 => build_operator_a_n(ALU[p.y]), // alu A, n
                 _ /*7*/ => build_rst(p.y as u8 * 8), // RST
             },
+        }};
+        opcode.cycles = match variant {
+            Variant::Z80 | Variant::Z80N => NO_PREFIX_CYCLES[c as usize],
+            Variant::Z180 => NO_PREFIX_CYCLES_Z180[c as usize],
+            Variant::R800 => NO_PREFIX_CYCLES_R800[c as usize],
+            Variant::EZ80 => 0, // Counted on the bus
         };
-        opcode.cycles = NO_PREFIX_CYCLES[c as usize];
         opcode.cycles_conditional = opcode.cycles;
         opcodes_vector.push(opcode);
     }
 
     let mut opcodes = opcodes_vector.try_into().unwrap_or_else(|_| { panic!("missing opcodes")});
-    load_cycle_information_no_prefix(&mut opcodes);
+    match variant {
+        Variant::Z80 | Variant::Z80N => load_cycle_information_no_prefix(&mut opcodes),
+        Variant::Z180 => load_cycle_information_no_prefix_z180(&mut opcodes),
+        Variant::R800 => load_cycle_information_no_prefix_r800(&mut opcodes),
+        Variant::EZ80 => load_cycle_information_no_prefix_ez80(&mut opcodes),
+    }
     opcodes
 }
 
-fn cb_prefix_opcodes() -> [Opcode;256] {
+fn cb_prefix_opcodes(variant: Variant) -> [Opcode;256] {
     let mut opcodes_vector = Vec::with_capacity(256);
 
     for c in 0..=255 {
         let p = DecodingHelper::parts(c);
         let mut opcode = match p.x {
+            0 if p.y == 6 && variant == Variant::Z180 => build_trap(false), // No SLL
+            0 if p.y == 6 && variant == Variant::R800 => build_rot_r(R[p.z], SLL_R800, false, false), // SLL
             0 => build_rot_r(R[p.z], ROT[p.y], false, false), // Shifts
             1 => build_bit_r(p.y as u8, R[p.z]), // BIT
             2 => build_set_res_r(p.y as u8, R[p.z], false), // RES
             _ /*3*/ => build_set_res_r(p.y as u8, R[p.z], true), // SET
         };
-        opcode.cycles = PREFIX_CB_CYCLES[c as usize];
+        opcode.cycles = match variant {
+            Variant::Z80 | Variant::Z80N => PREFIX_CB_CYCLES[c as usize],
+            Variant::Z180 => PREFIX_CB_CYCLES_Z180[c as usize],
+            Variant::R800 => PREFIX_CB_CYCLES_R800[c as usize],
+            Variant::EZ80 => if p.z == 6 && p.x != 1 {1} else {0}, // Read modify write on (HL)
+        };
         opcode.cycles_conditional = opcode.cycles;
         opcodes_vector.push(opcode);
     }
@@ -192,19 +354,37 @@ fn cb_prefix_opcodes() -> [Opcode;256] {
     opcodes_vector.try_into().unwrap_or_else(|_| { panic!("missing opcodes")})
 }
 
-fn cb_indexed_prefix_opcodes() -> [Opcode;256] {
+fn cb_indexed_prefix_opcodes(variant: Variant) -> [Opcode;256] {
     let mut opcodes_vector = Vec::with_capacity(256);
 
     for c in 0..=255 {
         let p = DecodingHelper::parts(c);
+        if variant == Variant::Z180 && (p.z != 6 || (p.x == 0 && p.y == 6)) {
+            // Only the (IX+d) versions, without SLL, 18 cycles with the
+            // prefix
+            let mut opcode = build_trap(true);
+            opcode.cycles = 15;
+            opcode.cycles_conditional = opcode.cycles;
+            opcodes_vector.push(opcode);
+            continue;
+        }
         let mut opcode = match p.x {
+            0 if p.y == 6 && variant == Variant::R800 => build_rot_r(R[p.z], SLL_R800, false, true), // SLL
             0 => build_rot_r(R[p.z], ROT[p.y], false, true), // Shifts
-            1 => build_bit_r(p.y as u8, R[p.z]), // BIT
+            1 => build_bit_r(p.y as u8, Reg8::_HL), // BIT, always on (IX+d)
             2 => build_indexed_set_res_r(p.y as u8, R[p.z], false), // RES
             _ /*3*/ => build_indexed_set_res_r(p.y as u8, R[p.z], true), // SET
         };
-        // 23 cycles except for BIT that is 20
-        opcode.cycles = if (c & 0xc0) == 0x40 {20} else {23};
+        // 23 cycles except for BIT that is 20, with the DD or FD prefix
+        // accounted by the decoder. 19 and 15 on the Z180, 7 and 5 on the
+        // R800. Only the read modify write cycle on the eZ80.
+        let bit = (c & 0xc0) == 0x40;
+        opcode.cycles = match variant {
+            Variant::Z80 | Variant::Z80N => if bit {16} else {19},
+            Variant::Z180 => if bit {12} else {16},
+            Variant::R800 => if bit {4} else {6},
+            Variant::EZ80 => if bit {0} else {1},
+        };
         opcode.cycles_conditional = opcode.cycles;
         opcodes_vector.push(opcode);
     }
@@ -213,12 +393,19 @@ fn cb_indexed_prefix_opcodes() -> [Opcode;256] {
 }
 
 
-fn ed_prefix_opcodes() -> [Opcode;256] {
+fn ed_prefix_opcodes(variant: Variant) -> [Opcode;256] {
     let mut opcodes_vector = Vec::with_capacity(256);
 
     for c in 0..=255 {
         let p = DecodingHelper::parts(c);
-        let mut opcode = match p.x {
+        let variant_opcode = match variant {
+            Variant::Z180 => ed_prefix_opcode_z180(&p),
+            Variant::Z80N => ed_prefix_opcode_z80n(c),
+            Variant::R800 => ed_prefix_opcode_r800(&p),
+            Variant::EZ80 => ed_prefix_opcode_ez80(c),
+            _ => None,
+        };
+        let mut opcode = if let Some(opcode) = variant_opcode { opcode } else { match p.x {
             0 | 3 => build_noni_nop(), // Invalid instruction NONI + NOP
             1 => match p.z {
                 0 => match p.y {
@@ -265,17 +452,222 @@ fn ed_prefix_opcodes() -> [Opcode;256] {
             } else {
                  build_noni_nop() // NONI + NOP
             },
+        }};
+        opcode.cycles = match variant {
+            Variant::Z80 => PREFIX_ED_CYCLES[c as usize],
+            Variant::Z180 => PREFIX_ED_CYCLES_Z180[c as usize],
+            Variant::Z80N => PREFIX_ED_CYCLES_Z80N[c as usize],
+            Variant::R800 => PREFIX_ED_CYCLES_R800[c as usize],
+            Variant::EZ80 => 0, // Counted on the bus
         };
-        opcode.cycles = PREFIX_ED_CYCLES[c as usize];
         opcode.cycles_conditional = opcode.cycles;
         opcodes_vector.push(opcode);
     }
 
     let mut opcodes = opcodes_vector.try_into().unwrap_or_else(|_| { panic!("missing opcodes")});
-    load_cycle_information_prefix_ed(&mut opcodes);
+    match variant {
+        Variant::Z80 => load_cycle_information_prefix_ed(&mut opcodes),
+        Variant::Z180 => load_cycle_information_prefix_ed_z180(&mut opcodes),
+        Variant::Z80N => load_cycle_information_prefix_ed_z80n(&mut opcodes),
+        Variant::R800 => {}, // The repeats take as long as each iteration
+        Variant::EZ80 => load_cycle_information_prefix_ed_ez80(&mut opcodes),
+    }
     opcodes
 }
 
+// Z180 opcodes on the ED prefix, the undefined ones trap. None when the
+// opcode is as on the Z80.
+fn ed_prefix_opcode_z180(p: &DecodingHelper) -> Option<Opcode> {
+    let opcode = match p.x {
+        0 => match p.z {
+            0 => match p.y {
+                6 => build_in0_n(), // IN0 (n)
+                _ => build_in0_r_n(R[p.y]), // IN0 r, (n)
+            },
+            1 if p.y != 6 => build_out0_n_r(R[p.y]), // OUT0 (n), r
+            4 => build_tst_r(R[p.y]), // TST r
+            _ => build_trap(false),
+        },
+        1 => match (p.z, p.y) {
+            (0 | 1, 6) => build_trap(false), // No IN (C) or OUT (C), 0
+            (4, 0) => return None, // NEG
+            (4, 4) => build_tst_n(), // TST n
+            (4, 6) => build_tstio_n(), // TSTIO n
+            (4, _) if p.q == 1 => build_mlt(RP[p.p]), // MLT rr
+            (5, 0 | 1) => return None, // RETN, RETI
+            (6, 0 | 2 | 3) => return None, // IM 0, IM 1, IM 2
+            (6, 6) => build_slp(), // SLP
+            (4..=6, _) => build_trap(false), // No NEG, RETN or IM duplicates
+            (7, 6 | 7) => build_trap(false),
+            _ => return None,
+        },
+        2 => match (p.z, p.y) {
+            (3, 0..=3) => build_otm_block(BLI_A[p.y]), // Block OTxM
+            (0..=3, 4..=7) => return None, // Block instructions
+            _ => build_trap(false),
+        },
+        _ /*3*/ => build_trap(false),
+    };
+    Some(opcode)
+}
+
+// Z80N opcodes on the ED prefix, on the slots that are NONI + NOP on the
+// Z80. None when the opcode is as on the Z80.
+fn ed_prefix_opcode_z80n(code: u8) -> Option<Opcode> {
+    let opcode = match code {
+        0x23 => build_swapnib(), // SWAPNIB
+        0x24 => build_mirror_a(), // MIRROR A
+        0x27 => build_test_n(), // TEST n
+        0x28 => build_barrel_shift("BSLA", shift_bsla), // BSLA DE, B
+        0x29 => build_barrel_shift("BSRA", shift_bsra), // BSRA DE, B
+        0x2a => build_barrel_shift("BSRL", shift_bsrl), // BSRL DE, B
+        0x2b => build_barrel_shift("BSRF", shift_bsrf), // BSRF DE, B
+        0x2c => build_barrel_shift("BRLC", shift_brlc), // BRLC DE, B
+        0x30 => build_mul_d_e(), // MUL D, E
+        0x31 => build_add_rr_a(Reg16::HL), // ADD HL, A
+        0x32 => build_add_rr_a(Reg16::DE), // ADD DE, A
+        0x33 => build_add_rr_a(Reg16::BC), // ADD BC, A
+        0x34 => build_add_rr_nn(Reg16::HL), // ADD HL, nn
+        0x35 => build_add_rr_nn(Reg16::DE), // ADD DE, nn
+        0x36 => build_add_rr_nn(Reg16::BC), // ADD BC, nn
+        0x8a => build_push_nn(), // PUSH nn, big endian
+        0x90 => build_outinb(), // OUTINB
+        0x91 => build_nextreg_n_n(), // NEXTREG n, n
+        0x92 => build_nextreg_n_a(), // NEXTREG n, A
+        0x93 => build_pixeldn(), // PIXELDN
+        0x94 => build_pixelad(), // PIXELAD
+        0x95 => build_setae(), // SETAE
+        0x98 => build_jp_c(), // JP (C)
+        0xa4 => build_ldx_block(BLI_A[0]), // LDIX
+        0xa5 => build_ldws(), // LDWS
+        0xac => build_ldx_block(BLI_A[1]), // LDDX
+        0xb4 => build_ldx_block(BLI_A[2]), // LDIRX
+        0xb7 => build_ldpirx(), // LDPIRX
+        0xbc => build_ldx_block(BLI_A[3]), // LDDRX
+        _ => return None,
+    };
+    Some(opcode)
+}
+
+// R800 multiplications on the ED prefix, on slots that are NONI + NOP on
+// the Z80. Only MULUB with B, C, D and E and MULUW with BC and SP are
+// documented, the others work the same. None when the opcode is as on the
+// Z80.
+fn ed_prefix_opcode_r800(p: &DecodingHelper) -> Option<Opcode> {
+    let opcode = match (p.x, p.z) {
+        (3, 1) if p.y != 6 => build_mulub_a_r(R[p.y]), // MULUB A, r
+        (3, 3) if p.q == 0 => build_muluw_hl_rr(RP[p.p]), // MULUW HL, rr
+        _ => return None,
+    };
+    Some(opcode)
+}
+
+// eZ80 opcodes that depend on the width of the registers and of the
+// addresses. None when the opcode is as on the Z80.
+fn no_prefix_opcode_ez80(p: &DecodingHelper) -> Option<Opcode> {
+    let opcode = match (p.x, p.z) {
+        (0, 1) => match p.q {
+            0 => build_ld_rr_nn_ez80(RP[p.p]), // LD rr, nn
+            _ /*1*/ => build_add_hl_rr_ez80(RP[p.p]), // ADD HL, rr
+        },
+        (0, 2) => match (p.q, p.p) {
+            (0, 0) => build_ld_prr_a_ez80(Reg16::BC), // LD (BC), A
+            (0, 1) => build_ld_prr_a_ez80(Reg16::DE), // LD (DE), A
+            (0, 2) => build_ld_pnn_rr_ez80(Reg16::HL), // LD (nn), HL
+            (0, _) => build_ld_pnn_a_ez80(), // LD (nn), A
+            (_, 0) => build_ld_a_prr_ez80(Reg16::BC), // LD A, (BC)
+            (_, 1) => build_ld_a_prr_ez80(Reg16::DE), // LD A, (DE)
+            (_, 2) => build_ld_rr_pnn_ez80(Reg16::HL), // LD HL, (nn)
+            (_, _) => build_ld_a_pnn_ez80(), // LD A, (nn)
+        },
+        (0, 3) => build_inc_dec_rr_ez80(RP[p.p], p.q == 0), // INC rr, DEC rr
+        (3, 1) => match (p.q, p.p) {
+            (0, _) => build_pop_rr_ez80(RP2[p.p]), // POP rr
+            (1, 2) => build_jp_hl_ez80(), // JP HL
+            (1, 3) => build_ld_sp_hl_ez80(), // LD SP, HL
+            _ => return None,
+        },
+        (3, 2) => build_jp_eq_ez80(CC[p.y]), // JP cc, nn
+        (3, 3) => match p.y {
+            0 => build_jp_ez80(), // JP nn
+            4 => build_ex_psp_hl_ez80(), // EX (SP), HL
+            5 => build_ex_de_hl_ez80(), // EX DE, HL
+            _ => return None,
+        },
+        (3, 4) => build_call_eq_ez80(CC[p.y]), // CALL cc, nn
+        (3, 5) => match (p.q, p.p) {
+            (0, _) => build_push_rr_ez80(RP2[p.p]), // PUSH rr
+            (1, 0) => build_call_ez80(), // CALL nn
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(opcode)
+}
+
+// eZ80 opcodes on the ED prefix, the ones of the Z180 and the new ones on
+// slots that are NONI + NOP on the Z80. None when the opcode is as on the
+// Z80.
+fn ed_prefix_opcode_ez80(code: u8) -> Option<Opcode> {
+    let opcode = match code {
+        0x02 => build_lea(Reg16::BC, Reg16::IX), // LEA BC, IX+d
+        0x03 => build_lea(Reg16::BC, Reg16::IY), // LEA BC, IY+d
+        0x12 => build_lea(Reg16::DE, Reg16::IX), // LEA DE, IX+d
+        0x13 => build_lea(Reg16::DE, Reg16::IY), // LEA DE, IY+d
+        0x22 => build_lea(Reg16::HL, Reg16::IX), // LEA HL, IX+d
+        0x23 => build_lea(Reg16::HL, Reg16::IY), // LEA HL, IY+d
+        0x32 => build_lea(Reg16::IX, Reg16::IX), // LEA IX, IX+d
+        0x33 => build_lea(Reg16::IY, Reg16::IY), // LEA IY, IY+d
+        0x54 => build_lea(Reg16::IX, Reg16::IY), // LEA IX, IY+d
+        0x55 => build_lea(Reg16::IY, Reg16::IX), // LEA IY, IX+d
+        0x65 => build_pea(Reg16::IX), // PEA IX+d
+        0x66 => build_pea(Reg16::IY), // PEA IY+d
+        0x07 => build_ld_rr_phl(Reg16::BC), // LD BC, (HL)
+        0x17 => build_ld_rr_phl(Reg16::DE), // LD DE, (HL)
+        0x27 => build_ld_rr_phl(Reg16::HL), // LD HL, (HL)
+        0x37 => build_ld_rr_phl(Reg16::IX), // LD IX, (HL)
+        0x31 => build_ld_rr_phl(Reg16::IY), // LD IY, (HL)
+        0x0f => build_ld_phl_rr(Reg16::BC), // LD (HL), BC
+        0x1f => build_ld_phl_rr(Reg16::DE), // LD (HL), DE
+        0x2f => build_ld_phl_rr(Reg16::HL), // LD (HL), HL
+        0x3f => build_ld_phl_rr(Reg16::IX), // LD (HL), IX
+        0x3e => build_ld_phl_rr(Reg16::IY), // LD (HL), IY
+        0x6d => build_ld_mb_a(), // LD MB, A
+        0x6e => build_ld_a_mb(), // LD A, MB
+        0x7d => build_stmix_rsmix(true), // STMIX
+        0x7e => build_stmix_rsmix(false), // RSMIX
+        0x76 => build_slp(), // SLP
+        _ => {
+            let p = DecodingHelper::parts(code);
+            match (p.x, p.z) {
+                (0, 0) if p.y != 6 => build_in0_r_n(R[p.y]), // IN0 r, (n)
+                (0, 1) if p.y != 6 => build_out0_n_r(R[p.y]), // OUT0 (n), r
+                (0, 4) => build_tst_r(R[p.y]), // TST r
+                (1, 2) => match p.q {
+                    0 => build_sbc_hl_rr_ez80(RP[p.p]), // SBC HL, rr
+                    _ /*1*/ => build_adc_hl_rr_ez80(RP[p.p]), // ADC HL, rr
+                },
+                (1, 3) => match p.q {
+                    0 => build_ld_pnn_rr_ez80(RP[p.p]), // LD (nn), rr
+                    _ /*1*/ => build_ld_rr_pnn_ez80(RP[p.p]), // LD rr, (nn)
+                },
+                (1, 4) => match p.y {
+                    4 => build_tst_n(), // TST n
+                    6 => build_tstio_n(), // TSTIO n
+                    _ if p.q == 1 => build_mlt(RP[p.p]), // MLT rr
+                    _ => return None,
+                },
+                (2, 0) if p.y >= 4 => build_ld_block_ez80(BLI_A[p.y-4]), // Block LDxx
+                (2, 1) if p.y >= 4 => build_cp_block_ez80(BLI_A[p.y-4]), // Block CPxx
+                (2, 2) if p.y >= 4 => build_in_block_ez80(BLI_A[p.y-4]), // Block INxx
+                (2, 3) if p.y >= 4 => build_out_block_ez80(BLI_A[p.y-4]), // Block OUTxx
+                _ => return None,
+            }
+        },
+    };
+    Some(opcode)
+}
+
 fn displacements() -> [bool; 256] {
     let mut disps = [false; 256];
     disps[0x34] = true;
@@ -306,42 +698,66 @@ fn displacements() -> [bool; 256] {
     disps
 }
 
+fn indexed() -> [bool; 256] {
+    let mut indexed = displacements();
+    for code in [0x09, 0x19, 0x21, 0x22, 0x23, 0x29, 0x2a, 0x2b, 0x39,
+            0xcb, 0xe1, 0xe3, 0xe5, 0xe9, 0xf9] {
+        indexed[code] = true;
+    }
+    indexed
+}
+
 fn load_cycle_information_no_prefix(opcodes: &mut [Opcode; 256]) {
     opcodes[0x10].cycles_conditional =  8;
-    opcodes[0x20].cycles_conditional =  8;
-    opcodes[0x28].cycles_conditional =  8;
-    opcodes[0x30].cycles_conditional =  8;
-    opcodes[0x38].cycles_conditional =  8;
+    opcodes[0x20].cycles_conditional =  7;
+    opcodes[0x28].cycles_conditional =  7;
+    opcodes[0x30].cycles_conditional =  7;
+    opcodes[0x38].cycles_conditional =  7;
 
     opcodes[0xc0].cycles_conditional =  5;
-    opcodes[0xc2].cycles_conditional =  7;
     opcodes[0xc4].cycles_conditional = 10;
     opcodes[0xc8].cycles_conditional =  5;
-    opcodes[0xca].cycles_conditional =  7;
     opcodes[0xcc].cycles_conditional = 10;
 
     opcodes[0xd0].cycles_conditional =  5;
-    opcodes[0xd2].cycles_conditional =  7;
     opcodes[0xd4].cycles_conditional = 10;
     opcodes[0xd8].cycles_conditional =  5;
-    opcodes[0xda].cycles_conditional =  7;
     opcodes[0xdc].cycles_conditional = 10;
 
     opcodes[0xe0].cycles_conditional =  5;
-    opcodes[0xe2].cycles_conditional =  7;
     opcodes[0xe4].cycles_conditional = 10;
     opcodes[0xe8].cycles_conditional =  5;
-    opcodes[0xea].cycles_conditional =  7;
     opcodes[0xec].cycles_conditional = 10;
 
     opcodes[0xf0].cycles_conditional =  5;
-    opcodes[0xf2].cycles_conditional =  7;
     opcodes[0xf4].cycles_conditional = 10;
     opcodes[0xf8].cycles_conditional =  5;
-    opcodes[0xfa].cycles_conditional =  7;
     opcodes[0xfc].cycles_conditional = 10;
 }
 
+fn load_cycle_information_no_prefix_z180(opcodes: &mut [Opcode; 256]) {
+    opcodes[0x10].cycles_conditional = 7;
+    for c in [0x20, 0x28, 0x30, 0x38] {
+        opcodes[c].cycles_conditional = 6; // JR cc
+    }
+    for c in [0xc0, 0xc8, 0xd0, 0xd8, 0xe0, 0xe8, 0xf0, 0xf8] {
+        opcodes[c].cycles_conditional = 5; // RET cc
+        opcodes[c + 2].cycles_conditional = 6; // JP cc, nn
+        opcodes[c + 4].cycles_conditional = 6; // CALL cc, nn
+    }
+}
+
+fn load_cycle_information_no_prefix_r800(opcodes: &mut [Opcode; 256]) {
+    opcodes[0x10].cycles_conditional = 2; // DJNZ
+    for c in [0x20, 0x28, 0x30, 0x38] {
+        opcodes[c].cycles_conditional = 2; // JR cc
+    }
+    for c in [0xc0, 0xc8, 0xd0, 0xd8, 0xe0, 0xe8, 0xf0, 0xf8] {
+        opcodes[c].cycles_conditional = 1; // RET cc
+        opcodes[c + 4].cycles_conditional = 3; // CALL cc, nn
+    }
+}
+
 fn load_cycle_information_prefix_ed(opcodes: &mut [Opcode; 256]) {
     opcodes[0xb0].cycles_conditional = 16;
     opcodes[0xb1].cycles_conditional = 16;
@@ -353,6 +769,44 @@ fn load_cycle_information_prefix_ed(opcodes: &mut [Opcode; 256]) {
     opcodes[0xbb].cycles_conditional = 16;
 }
 
+fn load_cycle_information_prefix_ed_z180(opcodes: &mut [Opcode; 256]) {
+    for c in [0xb0, 0xb1, 0xb2, 0xb3, 0xb8, 0xb9, 0xba, 0xbb] {
+        opcodes[c].cycles_conditional = 12;
+    }
+    opcodes[0x93].cycles_conditional = 14; // OTIMR
+    opcodes[0x9b].cycles_conditional = 14; // OTDMR
+}
+
+fn load_cycle_information_prefix_ed_z80n(opcodes: &mut [Opcode; 256]) {
+    load_cycle_information_prefix_ed(opcodes);
+    for c in [0xb4, 0xb7, 0xbc] {
+        opcodes[c].cycles_conditional = 16; // LDIRX, LDPIRX, LDDRX
+    }
+}
+
+fn load_cycle_information_no_prefix_ez80(opcodes: &mut [Opcode; 256]) {
+    // One more cycle on the jumps taken, to refill the pipeline, and on
+    // the read modify write of (HL)
+    for c in [0x10, 0x18, 0x20, 0x28, 0x30, 0x38, 0xc3, 0xe9] {
+        opcodes[c].cycles = 1; // DJNZ, JR, JP nn, JP HL
+    }
+    for c in [0xc2, 0xca, 0xd2, 0xda, 0xe2, 0xea, 0xf2, 0xfa] {
+        opcodes[c].cycles = 1; // JP cc, nn
+    }
+    opcodes[0x34].cycles = 1; // INC (HL)
+    opcodes[0x35].cycles = 1; // DEC (HL)
+    for c in [0x18, 0xc3, 0xe9, 0x34, 0x35] {
+        opcodes[c].cycles_conditional = opcodes[c].cycles; // Unconditional
+    }
+}
+
+fn load_cycle_information_prefix_ed_ez80(opcodes: &mut [Opcode; 256]) {
+    for c in [0x4c, 0x5c, 0x6c, 0x7c] {
+        opcodes[c].cycles = 4; // MLT
+        opcodes[c].cycles_conditional = 4;
+    }
+}
+
 struct DecodingHelper {
     // See notation in http://www.z80.info/decoding.htm    
     x: usize,
@@ -402,6 +856,9 @@ const ROT: [(ShiftDir, ShiftMode, &str); 8] = [
     (ShiftDir::Right, ShiftMode::Logical,     "SRL"),
 ];
 
+// SLL on the R800 keeps bit 0 instead of setting it
+const SLL_R800: (ShiftDir, ShiftMode, &str) = (ShiftDir::Left, ShiftMode::Extend, "SLL");
+
 const ALU: [(Operator, &str); 8] = [
     (operator_add, "ADD"),
     (operator_adc, "ADC"),
@@ -435,10 +892,10 @@ const NO_PREFIX_CYCLES: [u8; 256] = [
      4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4,
      4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4,
      4,  4,  4,  4,  4,  4,  7,  4,  4,  4,  4,  4,  4,  4,  7,  4,
-    11, 10, 12, 10, 17, 11,  7, 11, 11, 10, 12,  0, 17, 17,  7, 11,
-    11, 10, 12, 11, 17, 11,  7, 11, 11,  4, 12, 11, 17 , 0,  7, 11,
-    11, 10, 12, 19, 17, 11,  7, 11, 11,  4, 12,  4, 17,  0,  7, 11,
-    11, 10, 12,  4, 17, 11,  7, 11, 11,  6, 12,  4, 17,  0,  7, 11,
+    11, 10, 10, 10, 17, 11,  7, 11, 11, 10, 10,  0, 17, 17,  7, 11,
+    11, 10, 10, 11, 17, 11,  7, 11, 11,  4, 10, 11, 17 , 0,  7, 11,
+    11, 10, 10, 19, 17, 11,  7, 11, 11,  4, 10,  4, 17,  0,  7, 11,
+    11, 10, 10,  4, 17, 11,  7, 11, 11,  6, 10,  4, 17,  0,  7, 11,
 ];
 
 const PREFIX_CB_CYCLES: [u8; 256] = [
@@ -478,3 +935,143 @@ const PREFIX_ED_CYCLES: [u8; 256] = [
      0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
      0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
 ];
+
+// From the Z8018x Family MPU User Manual, Zilog UM0050
+const NO_PREFIX_CYCLES_Z180: [u8; 256] = [
+     3,  9,  7,  4,  4,  4,  6,  3,  4,  7,  6,  4,  4,  4,  6,  3,
+     9,  9,  7,  4,  4,  4,  6,  3,  8,  7,  6,  4,  4,  4,  6,  3,
+     8,  9, 16,  4,  4,  4,  6,  4,  8,  7, 15,  4,  4,  4,  6,  3,
+     8,  9, 13,  4, 10, 10,  9,  3,  8,  7, 12,  4,  4,  4,  6,  3,
+     4,  4,  4,  4,  4,  4,  6,  4,  4,  4,  4,  4,  4,  4,  6,  4,
+     4,  4,  4,  4,  4,  4,  6,  4,  4,  4,  4,  4,  4,  4,  6,  4,
+     4,  4,  4,  4,  4,  4,  6,  4,  4,  4,  4,  4,  4,  4,  6,  4,
+     7,  7,  7,  7,  7,  7,  3,  7,  4,  4,  4,  4,  4,  4,  6,  4,
+     4,  4,  4,  4,  4,  4,  6,  4,  4,  4,  4,  4,  4,  4,  6,  4,
+     4,  4,  4,  4,  4,  4,  6,  4,  4,  4,  4,  4,  4,  4,  6,  4,
+     4,  4,  4,  4,  4,  4,  6,  4,  4,  4,  4,  4,  4,  4,  6,  4,
+     4,  4,  4,  4,  4,  4,  6,  4,  4,  4,  4,  4,  4,  4,  6,  4,
+    10,  9,  9,  9, 16, 11,  6, 11, 10,  9,  9,  0, 16, 16,  6, 11,
+    10,  9,  9, 10, 16, 11,  6, 11, 10,  3,  9,  9, 16,  0,  6, 11,
+    10,  9,  9, 16, 16, 11,  6, 11, 10,  3,  9,  3, 16,  0,  6, 11,
+    10,  9,  9,  3, 16, 11,  6, 11, 10,  4,  9,  3, 16,  0,  6, 11,
+];
+
+// The TRAP on SLL takes 12 cycles
+const PREFIX_CB_CYCLES_Z180: [u8; 256] = [
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+    12, 12, 12, 12, 12, 12, 12, 12,  7,  7,  7,  7,  7,  7, 13,  7,
+     6,  6,  6,  6,  6,  6,  9,  6,  6,  6,  6,  6,  6,  6,  9,  6,
+     6,  6,  6,  6,  6,  6,  9,  6,  6,  6,  6,  6,  6,  6,  9,  6,
+     6,  6,  6,  6,  6,  6,  9,  6,  6,  6,  6,  6,  6,  6,  9,  6,
+     6,  6,  6,  6,  6,  6,  9,  6,  6,  6,  6,  6,  6,  6,  9,  6,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+     7,  7,  7,  7,  7,  7, 13,  7,  7,  7,  7,  7,  7,  7, 13,  7,
+];
+
+// The TRAP on the undefined opcodes takes 12 cycles
+const PREFIX_ED_CYCLES_Z180: [u8; 256] = [
+    12, 13, 12, 12,  7, 12, 12, 12, 12, 13, 12, 12,  7, 12, 12, 12,
+    12, 13, 12, 12,  7, 12, 12, 12, 12, 13, 12, 12,  7, 12, 12, 12,
+    12, 13, 12, 12,  7, 12, 12, 12, 12, 13, 12, 12,  7, 12, 12, 12,
+    12, 12, 12, 12, 10, 12, 12, 12, 12, 13, 12, 12,  7, 12, 12, 12,
+     9, 10, 10, 19,  6, 12,  6,  6,  9, 10, 10, 18, 17, 12, 12,  6,
+     9, 10, 10, 19, 12, 12,  6,  6,  9, 10, 10, 18, 17, 12,  6,  6,
+     9, 10, 10, 19,  9, 12, 12, 16,  9, 10, 10, 18, 17, 12, 12, 16,
+    12, 12, 10, 19, 12, 12,  8, 12,  9, 10, 10, 18, 17, 12, 12, 12,
+    12, 12, 12, 14, 12, 12, 12, 12, 12, 12, 12, 14, 12, 12, 12, 12,
+    12, 12, 12, 16, 12, 12, 12, 12, 12, 12, 12, 16, 12, 12, 12, 12,
+    12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12,
+    14, 14, 14, 14, 12, 12, 12, 12, 14, 14, 14, 14, 12, 12, 12, 12,
+    12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12,
+    12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12,
+    12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12,
+    12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12, 12,
+];
+
+// The Z80 table with the Z80N opcodes, from
+// https://wiki.specnext.dev/Extended_Z80_instruction_set
+const PREFIX_ED_CYCLES_Z80N: [u8; 256] = [
+     0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  8,  8,  0,  0, 11,  8,  8,  8,  8,  8,  0,  0,  0,
+     8,  8,  8,  8, 16, 16, 16,  0,  0,  0,  0,  0,  0,  0,  0,  0,
+    12, 12, 15, 20,  8, 14,  8,  9, 12, 12, 15, 20,  8, 14,  8,  9,
+    12, 12, 15, 20,  8, 14,  8,  9, 12, 12, 15, 20,  8, 14,  8,  9,
+    12, 12, 15, 20,  8, 14,  8, 18, 12, 12, 15, 20,  8, 14,  8, 18,
+    12, 12, 15, 20,  8, 14,  8,  0, 12, 12, 15, 20,  8, 14,  8,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,  0,  0, 23,  0,  0,  0,  0,  0,
+    16, 20, 17,  8,  8,  8,  0,  0, 13,  0,  0,  0,  0,  0,  0,  0,
+    16, 16, 16, 16, 16, 14,  0,  0, 16, 16, 16, 16, 16,  0,  0,  0,
+    21, 21, 21, 21, 21,  0,  0, 21, 21, 21, 21, 21, 21,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+// From the R800 Technical Data Book, without the page breaks. The memory
+// accesses take one clock each.
+const NO_PREFIX_CYCLES_R800: [u8; 256] = [
+     1,  3,  2,  1,  1,  1,  2,  1,  1,  1,  2,  1,  1,  1,  2,  1,
+     3,  3,  2,  1,  1,  1,  2,  1,  3,  1,  2,  1,  1,  1,  2,  1,
+     3,  3,  5,  1,  1,  1,  2,  1,  3,  1,  5,  1,  1,  1,  2,  1,
+     3,  3,  4,  1,  4,  4,  3,  1,  3,  1,  4,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     2,  2,  2,  2,  2,  2,  2,  2,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     1,  1,  1,  1,  1,  1,  2,  1,  1,  1,  1,  1,  1,  1,  2,  1,
+     3,  3,  3,  3,  5,  4,  2,  4,  3,  3,  3,  0,  5,  5,  2,  4,
+     3,  3,  3,  3,  5,  4,  2,  4,  3,  1,  3,  3,  5,  0,  2,  4,
+     3,  3,  3,  7,  5,  4,  2,  4,  3,  1,  3,  1,  5,  0,  2,  4,
+     3,  3,  3,  2,  5,  4,  2,  4,  3,  1,  3,  1,  5,  0,  2,  4,
+];
+
+const PREFIX_CB_CYCLES_R800: [u8; 256] = [
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+     2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+     2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+     2,  2,  2,  2,  2,  2,  3,  2,  2,  2,  2,  2,  2,  2,  3,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+     2,  2,  2,  2,  2,  2,  5,  2,  2,  2,  2,  2,  2,  2,  5,  2,
+];
+
+const PREFIX_ED_CYCLES_R800: [u8; 256] = [
+     2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,
+     2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,
+     2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,
+     2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,
+     3,  3,  2,  6,  2,  5,  3,  2,  3,  3,  2,  6,  2,  5,  3,  2,
+     3,  3,  2,  6,  2,  5,  3,  2,  3,  3,  2,  6,  2,  5,  3,  2,
+     3,  3,  2,  6,  2,  5,  3,  5,  3,  3,  2,  6,  2,  5,  3,  5,
+     3,  3,  2,  6,  2,  5,  3,  2,  3,  3,  2,  6,  2,  5,  3,  2,
+     2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,
+     2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,  2,
+     4,  3,  4,  4,  2,  2,  2,  2,  4,  3,  4,  4,  2,  2,  2,  2,
+     4,  3,  4,  4,  2,  2,  2,  2,  4,  3,  4,  4,  2,  2,  2,  2,
+     2, 14,  2, 36,  2,  2,  2,  2,  2, 14,  2,  2,  2,  2,  2,  2,
+     2, 14,  2, 36,  2,  2,  2,  2,  2, 14,  2,  2,  2,  2,  2,  2,
+     2, 14,  2, 36,  2,  2,  2,  2,  2, 14,  2,  2,  2,  2,  2,  2,
+     2,  2,  2, 36,  2,  2,  2,  2,  2, 14,  2,  2,  2,  2,  2,  2,
+];
@@ -2,35 +2,285 @@ use super::machine::Machine;
 use super::opcode::Opcode;
 use super::registers::{Reg16, Reg8};
 use super::state::State;
+use super::timing::{BusCycle, TimingModel};
+use super::debugger::{is_watched, Watchpoint};
+use super::tracer::Access;
 
 pub struct Environment<'a> {
     pub state: &'a mut State,
-    pub sys: &'a mut dyn Machine
+    pub sys: &'a mut dyn Machine,
+    // None when disassembling, the machine is not notified
+    timing: Option<&'static TimingModel>,
+    // T-states elapsed since state.cycle
+    t: u64,
+    // Instruction bytes and accesses, kept only when tracing
+    recording: bool,
+    code: Vec<u8>,
+    accesses: Vec<Access>,
+    watchpoints: &'a [Watchpoint],
+    // Instruction placed on the data bus by the device on the interrupt
+    // acknowledge
+    int_bus: Option<Vec<u8>>,
+}
+
+impl <'a> Environment<'a> {
+    pub fn set_watchpoints(&mut self, watchpoints: &'a [Watchpoint]) {
+        self.watchpoints = watchpoints;
+    }
 }
 
 impl <'a> Environment<'_> {
-    pub fn new(state: &'a mut State, sys: &'a mut dyn Machine) -> Environment<'a> {
+    pub fn new(state: &'a mut State, sys: &'a mut dyn Machine, timing: Option<&'static TimingModel>) -> Environment<'a> {
         Environment {
             state,
-            sys
+            sys,
+            timing,
+            t: 0,
+            recording: false,
+            code: Vec::new(),
+            accesses: Vec::new(),
+            watchpoints: &[],
+            int_bus: None,
         }
     }
 
-    pub fn peek_pc(&mut self) -> u8 {
+    pub fn start_recording(&mut self) {
+        self.recording = true;
+    }
+
+    // Returns the instruction bytes and the accesses recorded
+    pub fn take_recording(&mut self) -> (Vec<u8>, Vec<Access>) {
+        (std::mem::take(&mut self.code), std::mem::take(&mut self.accesses))
+    }
+
+    fn record_code(&mut self, value: u8) {
+        if self.recording {
+            self.code.push(value);
+        }
+    }
+
+    fn record_access(&mut self, access: Access) {
+        if self.recording {
+            self.accesses.push(access);
+        }
+        if self.state.watch_hit.is_none() && is_watched(self.watchpoints, access) {
+            self.state.watch_hit = Some(access);
+        }
+    }
+
+    fn bus_cycle(&mut self, cycle: BusCycle, address: u16) {
+        if let Some(timing) = self.timing {
+            let length = match cycle {
+                BusCycle::OpcodeFetch => timing.opcode_fetch,
+                BusCycle::InterruptAck => timing.interrupt_ack,
+                BusCycle::MemoryRead => timing.memory_read,
+                BusCycle::MemoryWrite => timing.memory_write,
+                BusCycle::IoRead | BusCycle::IoWrite => timing.io,
+                BusCycle::Internal => 1,
+                BusCycle::Refresh => 0,
+            };
+            if timing.page_break != 0 && matches!(cycle,
+                    BusCycle::OpcodeFetch | BusCycle::MemoryRead | BusCycle::MemoryWrite) {
+                // A new row address is needed when the DRAM page changes.
+                // Not on the cycle tables, counted as the wait states.
+                let page = (address >> 8) as u8;
+                if self.state.memory_page != Some(page) {
+                    self.state.memory_page = Some(page);
+                    self.state.cycle = self.state.cycle.wrapping_add(timing.page_break as u64);
+                }
+            }
+            let t_state = self.state.cycle.wrapping_add(self.t);
+            self.sys.bus_cycle(cycle, address, t_state);
+            // The wait states delay everything after them, they are
+            // added to the counter right away
+            let wait = self.sys.wait_states(cycle, address, t_state);
+            self.state.cycle = self.state.cycle.wrapping_add(wait as u64);
+            self.t += length as u64;
+
+            if timing.refresh && (cycle == BusCycle::OpcodeFetch || cycle == BusCycle::InterruptAck) {
+                // The refresh uses the last two T-states of the M1 cycle
+                let ir = self.ir();
+                self.sys.bus_cycle(BusCycle::Refresh, ir, self.state.cycle.wrapping_add(self.t - 2));
+            }
+        }
+    }
+
+    pub fn ir(&self) -> u16 {
+        ((self.state.reg.get8(Reg8::I) as u16) << 8)
+            | self.state.reg.get8(Reg8::R) as u16
+    }
+
+    pub fn internal_cycles(&mut self, address: u16, cycles: u8) {
+        if self.timing.is_some_and(|timing| !timing.internal) {
+            return;
+        }
+        // Notified one by one, the machine can delay each of them
+        for _ in 0..cycles {
+            self.bus_cycle(BusCycle::Internal, address);
+        }
+    }
+
+    pub fn model_cycles(&mut self, address: u16, cycles: fn(&TimingModel) -> u8) {
+        if let Some(timing) = self.timing {
+            self.internal_cycles(address, cycles(timing));
+        }
+    }
+
+    pub fn add_cycles(&mut self, cycles: u64) {
+        // The cycles already elapsed on the bus are now on the counter
+        self.state.cycle = self.state.cycle.wrapping_add(cycles);
+        self.t = self.t.saturating_sub(cycles);
+    }
+
+    pub fn read_modify_write_cycles(&mut self, address: u16) {
+        self.model_cycles(address, |timing| timing.read_modify_write);
+    }
+
+    // Memory access through the Z180 MMU or with the eZ80 MBASE, if
+    // present
+    fn read_memory(&mut self, address: u16) -> u8 {
+        match &self.state.z180 {
+            Some(z180) => self.sys.peek_physical(z180.translate(address)),
+            None if self.state.reg.is_ez80() => self.sys.peek_physical(self.mbase_address(address)),
+            None => self.sys.peek(address),
+        }
+    }
+
+    fn write_memory(&mut self, address: u16, value: u8) {
+        match &self.state.z180 {
+            Some(z180) => self.sys.poke_physical(z180.translate(address), value),
+            None if self.state.reg.is_ez80() => self.sys.poke_physical(self.mbase_address(address), value),
+            None => self.sys.poke(address, value),
+        }
+    }
+
+    fn is_translated(&self) -> bool {
+        self.state.z180.is_some() || self.state.reg.is_ez80()
+    }
+
+    fn read_memory16(&mut self, address: u16) -> u16 {
+        if self.is_translated() {
+            self.read_memory(address) as u16
+                + ((self.read_memory(address.wrapping_add(1)) as u16) << 8)
+        } else {
+            self.sys.peek16(address)
+        }
+    }
+
+    pub fn peek(&mut self, address: u16) -> u8 {
+        self.bus_cycle(BusCycle::MemoryRead, address);
+        let value = self.read_memory(address);
+        self.record_access(Access::MemoryRead { address: address as u32, value });
+        value
+    }
+
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.bus_cycle(BusCycle::MemoryWrite, address);
+        self.record_access(Access::MemoryWrite { address: address as u32, value });
+        self.write_memory(address, value);
+    }
+
+    pub fn peek16(&mut self, address: u16) -> u16 {
+        self.bus_cycle(BusCycle::MemoryRead, address);
+        self.bus_cycle(BusCycle::MemoryRead, address.wrapping_add(1));
+        let value = self.read_memory16(address);
+        self.record_access(Access::MemoryRead { address: address as u32, value: value as u8 });
+        self.record_access(Access::MemoryRead { address: address.wrapping_add(1) as u32, value: (value >> 8) as u8 });
+        value
+    }
+
+    pub fn poke16(&mut self, address: u16, value: u16) {
+        self.bus_cycle(BusCycle::MemoryWrite, address);
+        self.bus_cycle(BusCycle::MemoryWrite, address.wrapping_add(1));
+        self.record_access(Access::MemoryWrite { address: address as u32, value: value as u8 });
+        self.record_access(Access::MemoryWrite { address: address.wrapping_add(1) as u32, value: (value >> 8) as u8 });
+        if self.is_translated() {
+            self.write_memory(address, value as u8);
+            self.write_memory(address.wrapping_add(1), (value >> 8) as u8);
+        } else {
+            self.sys.poke16(address, value);
+        }
+    }
+
+    pub fn fetch_opcode(&mut self) -> u8 {
+        if self.state.int_data_index.is_some() {
+            return self.interrupt_ack();
+        }
+
+        let pc = self.state.reg.pc();
+        self.bus_cycle(BusCycle::OpcodeFetch, pc);
+        let value = self.read_code(0);
+        self.record_code(value);
+        self.state.reg.inc_pc();
+        value
+    }
+
+    pub fn discard_opcode_fetch(&mut self) {
+        // M1 cycle with the data bus ignored, as on the NMI response
         let pc = self.state.reg.pc();
-        self.sys.peek(pc)
+        self.bus_cycle(BusCycle::OpcodeFetch, pc);
+    }
+
+    pub fn peek_pc(&mut self) -> u8 {
+        self.peek_pc_offset(0)
+    }
+
+    pub fn peek_pc_offset(&mut self, offset: u16) -> u8 {
+        self.read_code(offset)
+    }
+
+    fn read_code(&mut self, offset: u16) -> u8 {
+        if self.state.reg.is_ez80() {
+            // The code is on PCU in ADL mode, on MBASE in Z80 mode
+            let address = if self.state.reg.is_adl() {
+                self.state.reg.pc24().wrapping_add(offset as u32) & 0xffffff
+            } else {
+                self.mbase_address(self.state.reg.pc().wrapping_add(offset))
+            };
+            self.sys.peek_physical(address)
+        } else {
+            let address = self.state.reg.pc().wrapping_add(offset);
+            self.read_memory(address)
+        }
     }
 
     pub fn advance_pc(&mut self) -> u8 {
+        if self.state.int_data_index.is_some() {
+            // Fetching from the data bus on an interrupt acknowledge, the
+            // PC is not advanced.
+            return self.interrupt_ack();
+        }
+
         let pc = self.state.reg.pc();
-        let value = self.sys.peek(pc);
-        self.state.reg.set_pc(pc.wrapping_add(1));
+        self.bus_cycle(BusCycle::MemoryRead, pc);
+        let value = self.read_code(0);
+        self.record_code(value);
+        self.state.reg.inc_pc();
         value
     }
 
-    pub fn peek16_pc(&mut self) -> u16 {
+    pub fn interrupt_ack(&mut self) -> u8 {
+        let index = self.state.int_data_index.unwrap_or(0);
+        self.state.int_data_index = Some(index + 1);
+        // Only the first byte is read on the acknowledge cycle, the rest
+        // of the instruction is read with regular memory read cycles.
         let pc = self.state.reg.pc();
-        self.sys.peek16(pc)
+        if index == 0 {
+            self.bus_cycle(BusCycle::InterruptAck, pc);
+        } else {
+            self.bus_cycle(BusCycle::MemoryRead, pc);
+        }
+        if index == 0 {
+            // The device is acknowledged once, it places the whole
+            // instruction
+            self.int_bus = self.sys.interrupt_ack();
+        }
+        let data = self.int_bus.as_deref().unwrap_or(&self.state.int_data);
+        data.get(index).copied().unwrap_or(0xff)
+    }
+
+    pub fn peek16_pc(&mut self) -> u16 {
+        self.read_code(0) as u16 + ((self.read_code(1) as u16) << 8)
     }
 
     pub fn advance_immediate16(&mut self) -> u16 {
@@ -46,10 +296,10 @@ impl <'a> Environment<'_> {
         let l = value as u8;
 
         sp = sp.wrapping_sub(1);
-        self.sys.poke(sp, h);
+        self.poke(sp, h);
 
         sp = sp.wrapping_sub(1);
-        self.sys.poke(sp, l);
+        self.poke(sp, l);
 
         self.state.reg.set16(Reg16::SP, sp);
     }
@@ -57,10 +307,10 @@ impl <'a> Environment<'_> {
     pub fn pop(&mut self) -> u16 {
         let mut sp = self.state.reg.get16(Reg16::SP);
 
-        let l = self.sys.peek(sp);
+        let l = self.peek(sp);
         sp = sp.wrapping_add(1);
 
-        let h = self.sys.peek(sp);
+        let h = self.peek(sp);
         sp = sp.wrapping_add(1);
 
         self.state.reg.set16(Reg16::SP, sp);
@@ -68,13 +318,48 @@ impl <'a> Environment<'_> {
     }
 
     pub fn subroutine_call(&mut self, address: u16) {
+        if self.state.reg.is_ez80() {
+            self.subroutine_call_word(address as u32);
+            return;
+        }
         self.push(self.state.reg.pc());
         self.state.reg.set_pc(address);
+        self.state.wz = address;
+        self.state.call_depth += 1;
+    }
+
+    // Interrupt call through the table entry at vector, as on IM 2
+    pub fn vectored_call(&mut self, vector: u16) {
+        if self.state.reg.is_ez80() {
+            // The table entries and the return address have the width of
+            // the mode
+            let pc = self.pc_word();
+            self.push_word(pc);
+            let address = self.peek_word(vector as u32);
+            self.set_pc_word(address);
+            self.state.wz = address as u16;
+            self.state.call_depth += 1;
+            return;
+        }
+        self.push(self.state.reg.pc());
+        let address = self.peek16(vector);
+        self.state.reg.set_pc(address);
+        self.state.wz = address;
+        self.state.call_depth += 1;
     }
 
     pub fn subroutine_return(&mut self) {
+        if self.state.reg.is_ez80() {
+            let pc = self.pop_word();
+            self.set_pc_word(pc);
+            self.state.wz = pc as u16;
+            self.state.call_depth -= 1;
+            return;
+        }
         let pc = self.pop();
         self.state.reg.set_pc(pc);
+        self.state.wz = pc;
+        self.state.call_depth -= 1;
     }
 
     pub fn set_index(&mut self, index: Reg16) {
@@ -85,6 +370,10 @@ impl <'a> Environment<'_> {
         self.state.index = Reg16::HL;
     }
 
+    pub fn clear_suffix(&mut self) {
+        self.state.suffix = None;
+    }
+
     pub fn clear_branch_taken(&mut self) {
         self.state.branch_taken = false;
     }
@@ -104,7 +393,13 @@ impl <'a> Environment<'_> {
         } else {
             opcode.cycles_conditional
         };
-        self.state.cycle = self.state.cycle.wrapping_add(cycles as u64);
+        if self.timing.is_some_and(|timing| timing.bus_timed) {
+            // The bus cycles are the length of the instruction
+            self.add_cycles(self.t + cycles as u64);
+            return;
+        }
+        debug_assert!(self.t <= cycles as u64, "Bus cycles beyond the end of {}", opcode.name);
+        self.add_cycles(cycles as u64);
     }
 
 
@@ -130,6 +425,8 @@ impl <'a> Environment<'_> {
         byte or not.
         */
         self.state.displacement = self.advance_pc() as i8;
+        // Every access to (IX+d) or (IY+d) sets WZ to the address
+        self.state.wz = self.index_address();
     }
 
     pub fn index_value(& self) -> u16 {
@@ -163,8 +460,10 @@ impl <'a> Environment<'_> {
     }
 
     pub fn reg8_ext(&mut self, reg: Reg8) -> u8 {
-        if reg == Reg8::_HL {
-            self.sys.peek(self.index_address())
+        if reg == Reg8::_HL && self.state.reg.is_ez80() {
+            self.peek24(self.index_address24())
+        } else if reg == Reg8::_HL {
+            self.peek(self.index_address())
         } else {
             self.state.reg.get8(self.translate_reg(reg))
         }
@@ -179,8 +478,10 @@ impl <'a> Environment<'_> {
     }
 
     pub fn set_reg(&mut self, reg: Reg8, value: u8) {
-        if reg == Reg8::_HL {
-            self.sys.poke(self.index_address(), value);
+        if reg == Reg8::_HL && self.state.reg.is_ez80() {
+            self.poke24(self.index_address24(), value);
+        } else if reg == Reg8::_HL {
+            self.poke(self.index_address(), value);
         } else {
             self.state.reg.set8(self.translate_reg(reg), value);
         }
@@ -195,10 +496,196 @@ impl <'a> Environment<'_> {
     }
 
     pub fn port_in(&mut self, address: u16) -> u8 {
-        self.sys.port_in(address)
+        self.bus_cycle(BusCycle::IoRead, address);
+        let internal = match &mut self.state.z180 {
+            Some(z180) => z180.internal_register(address).map(|reg| z180.read(reg)),
+            None => None,
+        };
+        let value = match internal {
+            Some(value) => value,
+            None => self.sys.port_in(address),
+        };
+        self.record_access(Access::PortIn { address, value });
+        value
     }
 
     pub fn port_out(&mut self, address: u16, value: u8) {
+        self.bus_cycle(BusCycle::IoWrite, address);
+        self.record_access(Access::PortOut { address, value });
+        if let Some(z180) = &mut self.state.z180 {
+            if let Some(reg) = z180.internal_register(address) {
+                z180.write(reg, value, self.sys);
+                return;
+            }
+        }
         self.sys.port_out(address, value);
     }
+
+    /*
+        eZ80 registers and memory. The width of the registers, of the
+        addresses and of the stack is 24 bits in ADL mode and 16 bits in
+        Z80 mode, where MBASE is the upper byte of the addresses. The
+        suffixes change the width for one instruction.
+    */
+
+    fn mbase_address(&self, address: u16) -> u32 {
+        ((self.state.reg.mbase() as u32) << 16) | address as u32
+    }
+
+    /// The data, the registers and the stack are on 24 bits
+    pub fn is_long(&self) -> bool {
+        match self.state.suffix {
+            Some(suffix) => suffix.long(),
+            None => self.state.reg.is_adl(),
+        }
+    }
+
+    /// The immediate values are on 24 bits
+    pub fn is_long_immediate(&self) -> bool {
+        match self.state.suffix {
+            Some(suffix) => suffix.long_immediate(),
+            None => self.state.reg.is_adl(),
+        }
+    }
+
+    pub fn address24(&self, address: u32) -> u32 {
+        if self.is_long() {
+            address & 0xffffff
+        } else {
+            self.mbase_address(address as u16)
+        }
+    }
+
+    pub fn peek24(&mut self, address: u32) -> u8 {
+        self.bus_cycle(BusCycle::MemoryRead, address as u16);
+        let value = self.sys.peek_physical(address);
+        self.record_access(Access::MemoryRead { address, value });
+        value
+    }
+
+    pub fn poke24(&mut self, address: u32, value: u8) {
+        self.bus_cycle(BusCycle::MemoryWrite, address as u16);
+        self.record_access(Access::MemoryWrite { address, value });
+        self.sys.poke_physical(address, value);
+    }
+
+    fn word_size(&self) -> u32 {
+        if self.is_long() { 3 } else { 2 }
+    }
+
+    pub fn peek_word(&mut self, address: u32) -> u32 {
+        let mut value = 0;
+        for i in 0..self.word_size() {
+            let address = self.address24(address.wrapping_add(i));
+            value |= (self.peek24(address) as u32) << (8 * i);
+        }
+        value
+    }
+
+    pub fn poke_word(&mut self, address: u32, value: u32) {
+        for i in 0..self.word_size() {
+            let address = self.address24(address.wrapping_add(i));
+            self.poke24(address, (value >> (8 * i)) as u8);
+        }
+    }
+
+    pub fn advance_immediate_word(&mut self) -> u32 {
+        let mut value = self.advance_immediate16() as u32;
+        if self.is_long_immediate() {
+            value |= (self.advance_pc() as u32) << 16;
+        }
+        value
+    }
+
+    pub fn peek_immediate_word(&mut self) -> u32 {
+        let mut value = self.peek16_pc() as u32;
+        if self.is_long_immediate() {
+            value |= (self.read_code(2) as u32) << 16;
+        }
+        value
+    }
+
+    pub fn reg_word(&self, rr: Reg16) -> u32 {
+        let rr = if rr == Reg16::HL { self.state.index } else { rr };
+        if self.is_long() {
+            self.state.reg.get24(rr)
+        } else {
+            self.state.reg.get16(rr) as u32
+        }
+    }
+
+    pub fn set_reg_word(&mut self, rr: Reg16, value: u32) {
+        // The upper byte is kept on 16 bit operations
+        let rr = if rr == Reg16::HL { self.state.index } else { rr };
+        if self.is_long() {
+            self.state.reg.set24(rr, value);
+        } else {
+            self.state.reg.set16(rr, value as u16);
+        }
+    }
+
+    pub fn inc_dec_word(&mut self, rr: Reg16, inc: bool) -> u32 {
+        let v = self.reg_word(rr);
+        let v = if inc { v.wrapping_add(1) } else { v.wrapping_sub(1) };
+        self.set_reg_word(rr, v);
+        v & self.word_mask()
+    }
+
+    pub fn word_mask(&self) -> u32 {
+        if self.is_long() { 0xffffff } else { 0xffff }
+    }
+
+    pub fn push_word(&mut self, value: u32) {
+        let size = self.word_size();
+        let sp = self.reg_word(Reg16::SP).wrapping_sub(size) & self.word_mask();
+        // The high byte is written first
+        for i in (0..size).rev() {
+            let address = self.address24(sp.wrapping_add(i));
+            self.poke24(address, (value >> (8 * i)) as u8);
+        }
+        self.set_reg_word(Reg16::SP, sp);
+    }
+
+    pub fn pop_word(&mut self) -> u32 {
+        let sp = self.reg_word(Reg16::SP);
+        let value = self.peek_word(sp);
+        self.set_reg_word(Reg16::SP, sp.wrapping_add(self.word_size()));
+        value
+    }
+
+    pub fn pc_word(&self) -> u32 {
+        if self.state.reg.is_adl() {
+            self.state.reg.pc24()
+        } else {
+            self.state.reg.pc() as u32
+        }
+    }
+
+    pub fn set_pc_word(&mut self, value: u32) {
+        if self.state.reg.is_adl() {
+            self.state.reg.set_pc24(value);
+        } else {
+            self.state.reg.set_pc(value as u16);
+        }
+    }
+
+    pub fn subroutine_call_word(&mut self, address: u32) {
+        // The mixed mode stack frames of the suffixes and MADL are not
+        // emulated, the return address has the width of the stack
+        let pc = self.pc_word();
+        self.push_word(pc);
+        self.set_pc_word(address);
+        self.state.wz = address as u16;
+        self.state.call_depth += 1;
+    }
+
+    pub fn index_address24(&self) -> u32 {
+        // Pseudo register (HL), (IX+d), (IY+d) on 24 bits
+        let address = self.reg_word(Reg16::HL);
+        if self.is_alt_index() {
+            self.address24(address.wrapping_add(self.state.displacement as u32))
+        } else {
+            self.address24(address)
+        }
+    }
 }
@@ -0,0 +1,435 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use super::cpu::Cpu;
+use super::debugger::{StopReason, Watchpoint};
+use super::hex::parse_hex_bytes;
+use super::machine::Machine;
+use super::registers::{Reg16, Reg8, Registers};
+use super::tracer::Access;
+
+/*
+    GDB remote serial protocol stub. See
+    https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html
+
+    The registers are in the order of the z80 target of gdb: AF, BC, DE,
+    HL, SP, PC, IX, IY, AF', BC', DE', HL' and IR, 16 bits little endian.
+*/
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+const INTERRUPT: u8 = 0x03;
+const REGISTER_COUNT: usize = 13;
+// Max size of a packet, announced to gdb
+const PACKET_SIZE: usize = 0x1000;
+// Instructions executed between the checks for an interrupt on continue
+const SLICE: u64 = 10_000;
+
+/// Connection to the debugger, a TCP stream or a scripted client
+pub trait Connection: Read + Write {
+    /// Returns the next byte received without consuming it, None if
+    /// nothing has been received. It must not block.
+    fn peek_byte(&mut self) -> io::Result<Option<u8>>;
+}
+
+impl Connection for TcpStream {
+    fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+        self.set_nonblocking(true)?;
+        let mut buf = [0; 1];
+        let result = self.peek(&mut buf);
+        self.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Err(io::Error::from(ErrorKind::UnexpectedEof)),
+            Ok(_) => Ok(Some(buf[0])),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Reason `GdbStub::serve()` returned
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GdbExit {
+    /// The debugger detached, the program can continue
+    Detached,
+    /// The debugger killed the program
+    Killed,
+    /// The connection was closed
+    Disconnected,
+}
+
+/// GDB remote serial protocol stub
+///
+/// Serves the requests of a debugger on the Cpu and the Machine: the
+/// registers, the memory through `Machine::peek()` and `Machine::poke()`,
+/// the breakpoints and watchpoints of `Cpu::debugger()`, single step,
+/// continue and interrupt.
+pub struct GdbStub<C: Connection> {
+    connection: C,
+    no_ack: bool,
+    // Watchpoints set by the debugger with the count of the watches
+    // covering them, the watches may overlap
+    watches: HashMap<Watchpoint, usize>,
+}
+
+impl GdbStub<TcpStream> {
+    /// Waits for a debugger to connect on the address, as
+    /// "127.0.0.1:1234"
+    pub fn listen<A: ToSocketAddrs>(address: A) -> io::Result<GdbStub<TcpStream>> {
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(GdbStub::new(stream))
+    }
+}
+
+impl<C: Connection> GdbStub<C> {
+    /// Returns a stub serving the debugger on the connection
+    pub fn new(connection: C) -> GdbStub<C> {
+        GdbStub {
+            connection,
+            no_ack: false,
+            watches: HashMap::new(),
+        }
+    }
+
+    /// Returns the connection
+    pub fn into_inner(self) -> C {
+        self.connection
+    }
+
+    /// Serves the debugger requests until it detaches, kills the program
+    /// or closes the connection. The CPU only runs on the continue and
+    /// step requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - The CPU debugged
+    /// * `sys` - A representation of the emulated machine that has the Machine trait
+    ///
+    pub fn serve(&mut self, cpu: &mut Cpu, sys: &mut dyn Machine) -> io::Result<GdbExit> {
+        loop {
+            let packet = match self.read_packet()? {
+                Some(packet) => packet,
+                None => return Ok(GdbExit::Disconnected),
+            };
+            if let Some(exit) = self.handle(&packet, cpu, sys)? {
+                return Ok(exit);
+            }
+        }
+    }
+
+    fn handle(&mut self, packet: &str, cpu: &mut Cpu, sys: &mut dyn Machine) -> io::Result<Option<GdbExit>> {
+        let args = packet.get(1..).unwrap_or("");
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => stop_reply(SIGTRAP),
+            Some(b'g') => read_registers(cpu.registers()),
+            Some(b'G') => ok_or_error(write_registers(cpu.registers(), args)),
+            Some(b'p') => read_register(cpu.registers(), args).unwrap_or_else(error),
+            Some(b'P') => ok_or_error(write_register(cpu.registers(), args)),
+            Some(b'm') => read_memory(sys, args).unwrap_or_else(error),
+            Some(b'M') => ok_or_error(write_memory(sys, args)),
+            Some(b'Z') => ok_or_error(change_breakpoint(cpu, &mut self.watches, args, true)),
+            Some(b'z') => ok_or_error(change_breakpoint(cpu, &mut self.watches, args, false)),
+            Some(b's') => {
+                if resume_address(cpu, args).is_none() {
+                    error()
+                } else {
+                    cpu.execute_instruction(sys);
+                    stop_reply(SIGTRAP)
+                }
+            },
+            Some(b'c') => {
+                if resume_address(cpu, args).is_none() {
+                    error()
+                } else {
+                    self.continue_run(cpu, sys)?
+                }
+            },
+            Some(b'k') => return Ok(Some(GdbExit::Killed)),
+            Some(b'D') => {
+                self.send("OK")?;
+                return Ok(Some(GdbExit::Detached));
+            },
+            Some(b'H') => "OK".to_string(),
+            _ if packet.starts_with("qSupported") => format!("PacketSize={PACKET_SIZE:x};QStartNoAckMode+"),
+            _ if packet == "qAttached" => "1".to_string(),
+            _ if packet == "QStartNoAckMode" => {
+                self.send("OK")?;
+                self.no_ack = true;
+                return Ok(None);
+            },
+            _ => String::new(), // Not supported
+        };
+        self.send(&reply)?;
+        Ok(None)
+    }
+
+    fn continue_run(&mut self, cpu: &mut Cpu, sys: &mut dyn Machine) -> io::Result<String> {
+        let mut reason = cpu.run(sys, SLICE);
+        loop {
+            match reason {
+                StopReason::InstructionLimit => {
+                    if self.connection.peek_byte()? == Some(INTERRUPT) {
+                        self.read_byte()?;
+                        return Ok(stop_reply(SIGINT));
+                    }
+                    reason = cpu.run_slice(sys, None, SLICE);
+                },
+                StopReason::Watchpoint(access) => return Ok(watch_reply(access)),
+                StopReason::Breakpoint(_) | StopReason::Halted | StopReason::Stepped =>
+                    return Ok(stop_reply(SIGTRAP)),
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut buf = [0; 1];
+        loop {
+            match self.connection.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buf[0])),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Skip the acknowledges, and the interrupts while stopped
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {},
+                Some(_) => continue,
+            }
+
+            let mut data = Vec::new();
+            let mut sum = 0_u8;
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(b) => {
+                        data.push(b);
+                        sum = sum.wrapping_add(b);
+                    },
+                }
+            }
+            let mut checksum = [0; 2];
+            for c in checksum.iter_mut() {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b) => *c = b,
+                }
+            }
+
+            let valid = std::str::from_utf8(&checksum).ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok()) == Some(sum);
+            if !self.no_ack {
+                self.connection.write_all(if valid { b"+" } else { b"-" })?;
+                self.connection.flush()?;
+            }
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let sum = data.bytes().fold(0_u8, |sum, b| sum.wrapping_add(b));
+        let packet = format!("${data}#{sum:02x}");
+        loop {
+            self.connection.write_all(packet.as_bytes())?;
+            self.connection.flush()?;
+            if self.no_ack {
+                return Ok(());
+            }
+            // Sent again when not acknowledged
+            match self.read_byte()? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{signal:02x}")
+}
+
+fn watch_reply(access: Access) -> String {
+    let (kind, address) = match access {
+        Access::MemoryRead { address, .. } => ("rwatch", address),
+        Access::MemoryWrite { address, .. } => ("watch", address),
+        // gdb has no port watchpoints, a plain stop is reported
+        Access::PortIn { .. } | Access::PortOut { .. } => return stop_reply(SIGTRAP),
+    };
+    format!("T{SIGTRAP:02x}{kind}:{address:x};")
+}
+
+fn ok_or_error(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_string(),
+        None => error(),
+    }
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    u32::from_str_radix(s, 16).ok()
+}
+
+fn parse_address(s: &str) -> Option<u16> {
+    parse_hex(s).and_then(|a| u16::try_from(a).ok())
+}
+
+fn resume_address(cpu: &mut Cpu, args: &str) -> Option<()> {
+    // "c addr" and "s addr" resume at the address
+    if !args.is_empty() {
+        cpu.registers().set_pc(parse_address(args)?);
+    }
+    Some(())
+}
+
+fn get_register(reg: &Registers, n: usize) -> Option<u16> {
+    Some(match n {
+        0 => reg.get16(Reg16::AF),
+        1 => reg.get16(Reg16::BC),
+        2 => reg.get16(Reg16::DE),
+        3 => reg.get16(Reg16::HL),
+        4 => reg.get16(Reg16::SP),
+        5 => reg.pc(),
+        6 => reg.get16(Reg16::IX),
+        7 => reg.get16(Reg16::IY),
+        8 => reg.get16_shadow(Reg16::AF),
+        9 => reg.get16_shadow(Reg16::BC),
+        10 => reg.get16_shadow(Reg16::DE),
+        11 => reg.get16_shadow(Reg16::HL),
+        12 => ((reg.get8(Reg8::I) as u16) << 8) | reg.get8(Reg8::R) as u16,
+        _ => return None,
+    })
+}
+
+fn set_register(reg: &mut Registers, n: usize, value: u16) -> Option<()> {
+    match n {
+        0 => reg.set16(Reg16::AF, value),
+        1 => reg.set16(Reg16::BC, value),
+        2 => reg.set16(Reg16::DE, value),
+        3 => reg.set16(Reg16::HL, value),
+        4 => reg.set16(Reg16::SP, value),
+        5 => reg.set_pc(value),
+        6 => reg.set16(Reg16::IX, value),
+        7 => reg.set16(Reg16::IY, value),
+        8 => reg.set16_shadow(Reg16::AF, value),
+        9 => reg.set16_shadow(Reg16::BC, value),
+        10 => reg.set16_shadow(Reg16::DE, value),
+        11 => reg.set16_shadow(Reg16::HL, value),
+        12 => {
+            reg.set8(Reg8::I, (value >> 8) as u8);
+            reg.set8(Reg8::R, value as u8);
+        },
+        _ => return None,
+    }
+    Some(())
+}
+
+fn read_registers(reg: &Registers) -> String {
+    (0..REGISTER_COUNT).filter_map(|n| get_register(reg, n))
+        .map(|v| format!("{:02x}{:02x}", v as u8, v >> 8))
+        .collect()
+}
+
+fn write_registers(reg: &mut Registers, args: &str) -> Option<()> {
+    let bytes = parse_hex_bytes(args)?;
+    if bytes.len() != REGISTER_COUNT * 2 {
+        return None;
+    }
+    for (n, pair) in bytes.chunks(2).enumerate() {
+        set_register(reg, n, u16::from_le_bytes([pair[0], pair[1]]))?;
+    }
+    Some(())
+}
+
+fn read_register(reg: &Registers, args: &str) -> Option<String> {
+    let v = get_register(reg, parse_hex(args)? as usize)?;
+    Some(format!("{:02x}{:02x}", v as u8, v >> 8))
+}
+
+fn write_register(reg: &mut Registers, args: &str) -> Option<()> {
+    // "n=value"
+    let (n, value) = args.split_once('=')?;
+    let bytes = parse_hex_bytes(value)?;
+    if bytes.len() != 2 {
+        return None;
+    }
+    set_register(reg, parse_hex(n)? as usize, u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_memory(sys: &mut dyn Machine, args: &str) -> Option<String> {
+    // "addr,length"
+    let (address, length) = args.split_once(',')?;
+    let address = parse_address(address)?;
+    // A shorter reply is allowed, up to the bytes fitting in a packet
+    let length = (parse_hex(length)? as usize).min(PACKET_SIZE / 2);
+    Some((0..length).map(|i| format!("{:02x}", sys.peek(address.wrapping_add(i as u16)))).collect())
+}
+
+fn write_memory(sys: &mut dyn Machine, args: &str) -> Option<()> {
+    // "addr,length:data"
+    let (range, data) = args.split_once(':')?;
+    let (address, length) = range.split_once(',')?;
+    let address = parse_address(address)?;
+    let bytes = parse_hex_bytes(data)?;
+    if bytes.len() != parse_hex(length)? as usize {
+        return None;
+    }
+    for (i, b) in bytes.iter().enumerate() {
+        sys.poke(address.wrapping_add(i as u16), *b);
+    }
+    Some(())
+}
+
+fn change_breakpoint(cpu: &mut Cpu, watches: &mut HashMap<Watchpoint, usize>, args: &str, insert: bool) -> Option<()> {
+    // "type,addr,kind"
+    let mut parts = args.split(',');
+    let kind = parts.next()?;
+    let address = parse_address(parts.next()?)?;
+    // Bytes watched, one watchpoint each
+    let length = parse_hex(parts.next()?)?.clamp(1, 0x10000);
+    let addresses = (0..length).map(|i| address.wrapping_add(i as u16) as u32);
+    let debugger = cpu.debugger();
+    let watchpoints: Vec<Watchpoint> = match kind {
+        // Software and hardware breakpoints are the same
+        "0" | "1" => {
+            if insert {
+                debugger.add_breakpoint(address.into());
+            } else {
+                debugger.remove_breakpoint(address.into());
+            }
+            return Some(());
+        },
+        "2" => addresses.map(Watchpoint::MemoryWrite).collect(),
+        "3" => addresses.map(Watchpoint::MemoryRead).collect(),
+        "4" => addresses.flat_map(|a| [Watchpoint::MemoryRead(a), Watchpoint::MemoryWrite(a)]).collect(),
+        _ => return None,
+    };
+    for watchpoint in watchpoints {
+        if insert {
+            *watches.entry(watchpoint).or_insert(0) += 1;
+            debugger.add_watchpoint(watchpoint);
+        } else if let Some(count) = watches.get_mut(&watchpoint) {
+            // Removed when no other watch covers it
+            *count -= 1;
+            if *count == 0 {
+                watches.remove(&watchpoint);
+                debugger.remove_watchpoint(watchpoint);
+            }
+        }
+    }
+    Some(())
+}
@@ -0,0 +1,12 @@
+//! Hex helpers shared by the gdb stub and the iz80 monitor
+
+/// Parses a string of hex digit pairs, as in the gdb packets and the
+/// Intel HEX records
+pub fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if s.len() & 1 != 0 {
+        return None;
+    }
+    (0..s.len()).step_by(2)
+        .map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
@@ -12,7 +12,9 @@
 //!fn main() {
 //!    // Prepare the device
 //!    let mut machine = PlainMachine::new();
-//!    let mut cpu = Cpu::new(); // Or Cpu::new_8080()
+//!    let mut cpu = Cpu::new(); // Or Cpu::new_8080(), Cpu::new_8085(), Cpu::new_z180(),
+//!                              // Cpu::new_z80n(), Cpu::new_r800(), Cpu::new_ez80()
+//!                              // or Cpu::new_lr35902()
 //!    cpu.set_trace(true);
 //!
 //!    // Load program inline or from a file with:
@@ -38,26 +40,70 @@
 //! ```
 
 
+mod contention;
 mod cpu;
+mod debugger;
+#[cfg(feature = "gdb")]
+mod gdb;
+#[cfg(feature = "gdb")]
+mod hex;
 mod machine;
 mod registers;
 mod state;
 mod timed_runner;
+mod timing;
+mod tracer;
+mod z180;
 
 mod decoder_z80;
 mod decoder_8080;
+mod decoder_8085;
+mod decoder_lr35902;
 mod environment;
+mod mnemonics_8080;
 mod opcode;
+mod opcode_8085;
 mod opcode_alu;
 mod opcode_arith;
 mod opcode_bits;
+mod opcode_ez80;
 mod opcode_io;
 mod opcode_jumps;
 mod opcode_ld;
+mod opcode_lr35902;
+mod opcode_r800;
+mod opcode_z180;
+mod opcode_z80n;
 mod operators;
 
+pub use contention::ContentionModel;
+pub use contention::SpectrumContention;
 pub use cpu::Cpu;
+pub use cpu::CpuModel;
+pub use cpu::Syntax;
+pub use debugger::Breakpoint;
+pub use debugger::Condition;
+pub use debugger::Debugger;
+pub use debugger::StopReason;
+pub use debugger::Watchpoint;
+#[cfg(feature = "gdb")]
+pub use gdb::Connection;
+#[cfg(feature = "gdb")]
+pub use gdb::GdbExit;
+#[cfg(feature = "gdb")]
+pub use gdb::GdbStub;
 pub use machine::Machine;
 pub use machine::PlainMachine;
 pub use registers::*;
-pub use timed_runner::TimedRunner;
\ No newline at end of file
+pub use timed_runner::TimedRunner;
+pub use timing::BusCycle;
+pub use tracer::Access;
+pub use tracer::DefaultFormat;
+pub use tracer::GameboyDoctorFormat;
+pub use tracer::MameFormat;
+pub use tracer::RingBufferTracer;
+pub use tracer::StdoutTracer;
+pub use tracer::TraceFormat;
+pub use tracer::TraceRecord;
+pub use tracer::Tracer;
+pub use tracer::WriterTracer;
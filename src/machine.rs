@@ -1,3 +1,5 @@
+use super::timing::BusCycle;
+
 /// Abstraction of the device hosting the Z80 CPU
 ///
 /// The device hosting the CPU has to provide implementations
@@ -28,6 +30,93 @@ pub trait Machine {
     /// Port out, from the CPU to the device. Sets a port value on
     /// the hosting device.
     fn port_out(&mut self, address: u16, value: u8);
+
+    /// Interrupt acknowledge, the CPU accepts the maskable interrupt.
+    /// The device can deactivate its request and return the bytes to
+    /// place on the data bus. When `None` is returned, the data set with
+    /// `Cpu::set_interrupt_instruction()` is used.
+    ///
+    /// It is called once per acknowledge. On IM 2 the first byte is the
+    /// low byte of the vector. For IM 0 and on the 8080 the bytes are
+    /// the instruction to execute, as the sequence of an 8259 answering
+    /// a CALL. The bytes missing read as 0xff.
+    fn interrupt_ack(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Return from interrupt, the CPU has executed a RETI. Z80 family
+    /// peripherals decode it to end the interrupt service and to let
+    /// lower priority devices on the daisy chain interrupt again.
+    fn reti(&mut self) {}
+
+    /// Return from non maskable interrupt, the CPU has executed a RETN.
+    fn retn(&mut self) {}
+
+    /// Undocumented opcode, the 8080 in strict mode is executing a
+    /// duplicate of NOP, JMP, RET or CALL at [address].
+    fn undocumented_opcode(&mut self, _address: u16, _opcode: u8) {}
+
+    /// Serial output, the 8085 has executed a SIM with SDE set. The
+    /// value is the new level of the SOD line.
+    fn serial_out(&mut self, _value: bool) {}
+
+    /// Serial input, the 8085 is executing a RIM. Returns the level of
+    /// the SID line.
+    fn serial_in(&mut self) -> bool {
+        false
+    }
+
+    /// Returns the memory contents in the physical [address]. The Z180
+    /// MMU translates the 16 bit addresses to 20 bits, the eZ80 uses 24
+    /// bit addresses. Defaults to `peek()` with the low 16 bits.
+    fn peek_physical(&mut self, address: u32) -> u8 {
+        self.peek(address as u16)
+    }
+
+    /// Sets the memory content to [value] in the physical [address].
+    /// Defaults to `poke()` with the low 16 bits.
+    fn poke_physical(&mut self, address: u32, value: u8) {
+        self.poke(address as u16, value);
+    }
+
+    /// Z180 ASCI transmit, a byte was written to the transmit data
+    /// register of the channel 0 or 1 with the transmitter enabled.
+    fn asci_transmit(&mut self, _channel: usize, _value: u8) {}
+
+    /// Z180 ASCI receive. Called after each instruction while the
+    /// receiver of the channel 0 or 1 is enabled and its receive data
+    /// register is empty. Returns the byte received, if any.
+    fn asci_receive(&mut self, _channel: usize) -> Option<u8> {
+        None
+    }
+
+    /// Z80N NEXTREG, writes [value] on the Spectrum Next register
+    /// [register]. Defaults to selecting the register on port 243Bh and
+    /// writing the value on port 253Bh.
+    fn nextreg(&mut self, register: u8, value: u8) {
+        self.port_out(0x243b, register);
+        self.port_out(0x253b, value);
+    }
+
+    /// Machine cycle start. Called before each access to memory or to
+    /// the ports with the type of cycle, the address on the bus and the
+    /// T-state where the cycle starts. The T-state is on the same scale
+    /// as `Cpu::cycle_count()`, that is updated only at the end of each
+    /// instruction.
+    ///
+    /// On the Z80 the M1 cycles are followed by a `BusCycle::Refresh`.
+    /// The ports are accessed with `port_in()` and `port_out()` after a
+    /// `BusCycle::IoRead` or a `BusCycle::IoWrite`.
+    fn bus_cycle(&mut self, _cycle: BusCycle, _address: u16, _t_state: u64) {}
+
+    /// Wait states. Called after `bus_cycle()` for each machine cycle
+    /// except the refresh. Returns the number of T-states the /WAIT line
+    /// is held active, they extend the cycle and are added to
+    /// `Cpu::cycle_count()`. The automatic wait states of the Z80 on the
+    /// I/O and interrupt acknowledge cycles are already counted.
+    fn wait_states(&mut self, _cycle: BusCycle, _address: u16, _t_state: u64) -> u8 {
+        0
+    }
 }
 
 /// A simple Machine implementation
@@ -0,0 +1,79 @@
+/*
+    Intel mnemonics of the 8080 and the 8085, used when disassembling
+    with `Syntax::Intel`. The opcodes are built with the Zilog names, these
+    tables are indexed by the opcode byte. See
+    https://pastraiser.com/cpu/i8080/i8080_opcodes.html
+    https://pastraiser.com/cpu/i8085/i8085_opcodes.html
+*/
+
+// The undocumented duplicates are marked with an asterisk
+pub(crate) const MNEMONICS_8080: [&str; 256] = [
+    /* 00 */ "NOP", "LXI B,nn", "STAX B", "INX B", "INR B", "DCR B", "MVI B,n", "RLC",
+    /* 08 */ "*NOP", "DAD B", "LDAX B", "DCX B", "INR C", "DCR C", "MVI C,n", "RRC",
+    /* 10 */ "*NOP", "LXI D,nn", "STAX D", "INX D", "INR D", "DCR D", "MVI D,n", "RAL",
+    /* 18 */ "*NOP", "DAD D", "LDAX D", "DCX D", "INR E", "DCR E", "MVI E,n", "RAR",
+    /* 20 */ "*NOP", "LXI H,nn", "SHLD nn", "INX H", "INR H", "DCR H", "MVI H,n", "DAA",
+    /* 28 */ "*NOP", "DAD H", "LHLD nn", "DCX H", "INR L", "DCR L", "MVI L,n", "CMA",
+    /* 30 */ "*NOP", "LXI SP,nn", "STA nn", "INX SP", "INR M", "DCR M", "MVI M,n", "STC",
+    /* 38 */ "*NOP", "DAD SP", "LDA nn", "DCX SP", "INR A", "DCR A", "MVI A,n", "CMC",
+    /* 40 */ "MOV B,B", "MOV B,C", "MOV B,D", "MOV B,E", "MOV B,H", "MOV B,L", "MOV B,M", "MOV B,A",
+    /* 48 */ "MOV C,B", "MOV C,C", "MOV C,D", "MOV C,E", "MOV C,H", "MOV C,L", "MOV C,M", "MOV C,A",
+    /* 50 */ "MOV D,B", "MOV D,C", "MOV D,D", "MOV D,E", "MOV D,H", "MOV D,L", "MOV D,M", "MOV D,A",
+    /* 58 */ "MOV E,B", "MOV E,C", "MOV E,D", "MOV E,E", "MOV E,H", "MOV E,L", "MOV E,M", "MOV E,A",
+    /* 60 */ "MOV H,B", "MOV H,C", "MOV H,D", "MOV H,E", "MOV H,H", "MOV H,L", "MOV H,M", "MOV H,A",
+    /* 68 */ "MOV L,B", "MOV L,C", "MOV L,D", "MOV L,E", "MOV L,H", "MOV L,L", "MOV L,M", "MOV L,A",
+    /* 70 */ "MOV M,B", "MOV M,C", "MOV M,D", "MOV M,E", "MOV M,H", "MOV M,L", "HLT", "MOV M,A",
+    /* 78 */ "MOV A,B", "MOV A,C", "MOV A,D", "MOV A,E", "MOV A,H", "MOV A,L", "MOV A,M", "MOV A,A",
+    /* 80 */ "ADD B", "ADD C", "ADD D", "ADD E", "ADD H", "ADD L", "ADD M", "ADD A",
+    /* 88 */ "ADC B", "ADC C", "ADC D", "ADC E", "ADC H", "ADC L", "ADC M", "ADC A",
+    /* 90 */ "SUB B", "SUB C", "SUB D", "SUB E", "SUB H", "SUB L", "SUB M", "SUB A",
+    /* 98 */ "SBB B", "SBB C", "SBB D", "SBB E", "SBB H", "SBB L", "SBB M", "SBB A",
+    /* a0 */ "ANA B", "ANA C", "ANA D", "ANA E", "ANA H", "ANA L", "ANA M", "ANA A",
+    /* a8 */ "XRA B", "XRA C", "XRA D", "XRA E", "XRA H", "XRA L", "XRA M", "XRA A",
+    /* b0 */ "ORA B", "ORA C", "ORA D", "ORA E", "ORA H", "ORA L", "ORA M", "ORA A",
+    /* b8 */ "CMP B", "CMP C", "CMP D", "CMP E", "CMP H", "CMP L", "CMP M", "CMP A",
+    /* c0 */ "RNZ", "POP B", "JNZ nn", "JMP nn", "CNZ nn", "PUSH B", "ADI n", "RST 0",
+    /* c8 */ "RZ", "RET", "JZ nn", "*JMP nn", "CZ nn", "CALL nn", "ACI n", "RST 1",
+    /* d0 */ "RNC", "POP D", "JNC nn", "OUT n", "CNC nn", "PUSH D", "SUI n", "RST 2",
+    /* d8 */ "RC", "*RET", "JC nn", "IN n", "CC nn", "*CALL nn", "SBI n", "RST 3",
+    /* e0 */ "RPO", "POP H", "JPO nn", "XTHL", "CPO nn", "PUSH H", "ANI n", "RST 4",
+    /* e8 */ "RPE", "PCHL", "JPE nn", "XCHG", "CPE nn", "*CALL nn", "XRI n", "RST 5",
+    /* f0 */ "RP", "POP PSW", "JP nn", "DI", "CP nn", "PUSH PSW", "ORI n", "RST 6",
+    /* f8 */ "RM", "SPHL", "JM nn", "EI", "CM nn", "*CALL nn", "CPI n", "RST 7",
+];
+
+// The undocumented opcodes as named by Dehnhardt and Sorensen
+pub(crate) const MNEMONICS_8085: [&str; 256] = [
+    /* 00 */ "NOP", "LXI B,nn", "STAX B", "INX B", "INR B", "DCR B", "MVI B,n", "RLC",
+    /* 08 */ "DSUB", "DAD B", "LDAX B", "DCX B", "INR C", "DCR C", "MVI C,n", "RRC",
+    /* 10 */ "ARHL", "LXI D,nn", "STAX D", "INX D", "INR D", "DCR D", "MVI D,n", "RAL",
+    /* 18 */ "RDEL", "DAD D", "LDAX D", "DCX D", "INR E", "DCR E", "MVI E,n", "RAR",
+    /* 20 */ "RIM", "LXI H,nn", "SHLD nn", "INX H", "INR H", "DCR H", "MVI H,n", "DAA",
+    /* 28 */ "LDHI n", "DAD H", "LHLD nn", "DCX H", "INR L", "DCR L", "MVI L,n", "CMA",
+    /* 30 */ "SIM", "LXI SP,nn", "STA nn", "INX SP", "INR M", "DCR M", "MVI M,n", "STC",
+    /* 38 */ "LDSI n", "DAD SP", "LDA nn", "DCX SP", "INR A", "DCR A", "MVI A,n", "CMC",
+    /* 40 */ "MOV B,B", "MOV B,C", "MOV B,D", "MOV B,E", "MOV B,H", "MOV B,L", "MOV B,M", "MOV B,A",
+    /* 48 */ "MOV C,B", "MOV C,C", "MOV C,D", "MOV C,E", "MOV C,H", "MOV C,L", "MOV C,M", "MOV C,A",
+    /* 50 */ "MOV D,B", "MOV D,C", "MOV D,D", "MOV D,E", "MOV D,H", "MOV D,L", "MOV D,M", "MOV D,A",
+    /* 58 */ "MOV E,B", "MOV E,C", "MOV E,D", "MOV E,E", "MOV E,H", "MOV E,L", "MOV E,M", "MOV E,A",
+    /* 60 */ "MOV H,B", "MOV H,C", "MOV H,D", "MOV H,E", "MOV H,H", "MOV H,L", "MOV H,M", "MOV H,A",
+    /* 68 */ "MOV L,B", "MOV L,C", "MOV L,D", "MOV L,E", "MOV L,H", "MOV L,L", "MOV L,M", "MOV L,A",
+    /* 70 */ "MOV M,B", "MOV M,C", "MOV M,D", "MOV M,E", "MOV M,H", "MOV M,L", "HLT", "MOV M,A",
+    /* 78 */ "MOV A,B", "MOV A,C", "MOV A,D", "MOV A,E", "MOV A,H", "MOV A,L", "MOV A,M", "MOV A,A",
+    /* 80 */ "ADD B", "ADD C", "ADD D", "ADD E", "ADD H", "ADD L", "ADD M", "ADD A",
+    /* 88 */ "ADC B", "ADC C", "ADC D", "ADC E", "ADC H", "ADC L", "ADC M", "ADC A",
+    /* 90 */ "SUB B", "SUB C", "SUB D", "SUB E", "SUB H", "SUB L", "SUB M", "SUB A",
+    /* 98 */ "SBB B", "SBB C", "SBB D", "SBB E", "SBB H", "SBB L", "SBB M", "SBB A",
+    /* a0 */ "ANA B", "ANA C", "ANA D", "ANA E", "ANA H", "ANA L", "ANA M", "ANA A",
+    /* a8 */ "XRA B", "XRA C", "XRA D", "XRA E", "XRA H", "XRA L", "XRA M", "XRA A",
+    /* b0 */ "ORA B", "ORA C", "ORA D", "ORA E", "ORA H", "ORA L", "ORA M", "ORA A",
+    /* b8 */ "CMP B", "CMP C", "CMP D", "CMP E", "CMP H", "CMP L", "CMP M", "CMP A",
+    /* c0 */ "RNZ", "POP B", "JNZ nn", "JMP nn", "CNZ nn", "PUSH B", "ADI n", "RST 0",
+    /* c8 */ "RZ", "RET", "JZ nn", "RSTV", "CZ nn", "CALL nn", "ACI n", "RST 1",
+    /* d0 */ "RNC", "POP D", "JNC nn", "OUT n", "CNC nn", "PUSH D", "SUI n", "RST 2",
+    /* d8 */ "RC", "SHLX", "JC nn", "IN n", "CC nn", "JNK nn", "SBI n", "RST 3",
+    /* e0 */ "RPO", "POP H", "JPO nn", "XTHL", "CPO nn", "PUSH H", "ANI n", "RST 4",
+    /* e8 */ "RPE", "PCHL", "JPE nn", "XCHG", "CPE nn", "LHLX", "XRI n", "RST 5",
+    /* f0 */ "RP", "POP PSW", "JP nn", "DI", "CP nn", "PUSH PSW", "ORI n", "RST 6",
+    /* f8 */ "RM", "SPHL", "JM nn", "EI", "CM nn", "JK nn", "CPI n", "RST 7",
+];
@@ -3,10 +3,25 @@ use super::registers::Reg16;
 
 type OpcodeFn = dyn Fn(&mut Environment) + Send + Sync;
 
+// Control flow of an instruction, to step over and out of it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Flow {
+    // CALL, RST and RSTV, may enter a subroutine
+    Call,
+    // DJNZ and the repeating block instructions, may loop before the
+    // next instruction
+    Loop,
+    Other,
+}
+
 pub struct Opcode {
     pub name: String,
     pub cycles: u8,
     pub cycles_conditional: u8,
+    // Undocumented duplicate of another opcode, as on the 8080
+    pub alias: bool,
+    // Set by the builders of the calls and the loops
+    pub flow: Flow,
     pub action: Box<OpcodeFn>,
 }
 
@@ -17,6 +32,8 @@ impl Opcode {
             name,
             cycles: 0,
             cycles_conditional: 0,
+            alias: false,
+            flow: Flow::Other,
             action: Box::new(action),
         }
     }
@@ -26,32 +43,102 @@ impl Opcode {
     }
 
     pub fn disasm(&self, env: &mut Environment) -> String {
-        let name = if self.name.contains("__index") {
-            self.name.replace("__index", &env.index_description())
+        self.disasm_as(&self.name, env)
+    }
+
+    // Disassembles with another name, as the Intel mnemonics of the 8080
+    pub fn disasm_as(&self, template: &str, env: &mut Environment) -> String {
+        let mut name = if template.contains("__index") {
+            template.replace("__index", &env.index_description())
         } else {
-            self.name.clone()
+            template.to_string()
         };
 
-        if self.name.contains("nn") {
+        if let Some(suffix) = env.state.suffix {
+            // The eZ80 suffix goes after the mnemonic
+            let position = name.find(' ').unwrap_or(name.len());
+            name.insert_str(position, suffix.name());
+        }
+
+        if template.contains("nn") && env.is_long_immediate() {
+            // Immediate argument 24 bits, on the eZ80
+            let nn = env.peek_immediate_word();
+            let nn_str = format!("{nn:06x}h");
+            name.replace("nn", &nn_str)
+        } else if template.contains("nn") {
             // Immediate argument 16 bits
             let nn = env.peek16_pc();
             let nn_str = format!("{nn:04x}h");
             name.replace("nn", &nn_str)
-        } else if self.name.contains('n') {
-            // Immediate argument 8 bits
-            let n = env.peek_pc();
-            let n_str = format!("{n:02x}h");
-            name.replace('n', &n_str)
-        } else if self.name.contains('d') {
+        } else if template.contains("mm") {
+            // Immediate argument 16 bits big endian, as on PUSH nn of the
+            // Z80N
+            let mm = env.peek16_pc().swap_bytes();
+            let mm_str = format!("{mm:04x}h");
+            name.replace("mm", &mm_str)
+        } else if template.contains('n') {
+            // Immediate arguments 8 bits, NEXTREG n, n has two of them
+            let mut offset = 0;
+            let mut disasm = String::new();
+            for c in name.chars() {
+                if c == 'n' {
+                    let n = env.peek_pc_offset(offset);
+                    disasm.push_str(&format!("{n:02x}h"));
+                    offset += 1;
+                } else {
+                    disasm.push(c);
+                }
+            }
+            disasm
+        } else if template.contains('d') {
             // Immediate argument 8 bits signed
             // In assembly it's shown with 2 added as if it were from the opcode pc.
             let d = env.peek_pc() as i8 as i16 + 2;
             let d_str = format!("{d:+x}");
             name.replace('d', &d_str)
+        } else if template.contains('s') {
+            // Immediate argument 8 bits signed, added to SP
+            let s = env.peek_pc() as i8;
+            let s_str = if s < 0 {
+                format!("-{:02x}h", -(s as i16))
+            } else {
+                format!("+{s:02x}h")
+            };
+            name.replace('s', &s_str)
         } else {
             name
         }
     }
+
+    // Bytes of the arguments after the opcode, as read by disasm()
+    pub fn operand_length(&self, env: &Environment) -> u16 {
+        let name = if self.name.contains("__index") {
+            self.name.replace("__index", &env.index_description())
+        } else {
+            self.name.clone()
+        };
+
+        if name.contains("nn") {
+            if env.is_long_immediate() { 3 } else { 2 }
+        } else if name.contains("mm") {
+            2
+        } else if name.contains('n') {
+            name.matches('n').count() as u16
+        } else if name.contains('d') || name.contains('s') {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+pub fn build_alias(opcode: Opcode, name: &str) -> Opcode {
+    // Disassembled with an asterisk
+    Opcode {
+        name: format!("*{name}"),
+        alias: true,
+        ..opcode
+    }
 }
 
 pub fn build_not_an_opcode() -> Opcode {
@@ -105,6 +192,7 @@ pub fn build_push_rr(rr: Reg16) -> Opcode {
         format!("PUSH {rr:?}"),
         move |env: &mut Environment| {
             let value = env.reg16_ext(rr);
+            env.internal_cycles(env.ir(), 1);
             env.push(value);
         }
     )
@@ -0,0 +1,169 @@
+use super::opcode::{Flow, Opcode};
+use super::environment::Environment;
+use super::opcode_jumps::conditional_jump;
+use super::registers::{Flag, Reg16};
+
+/*
+    Intel 8085 specific opcodes. The undocumented ones are described in
+    "Unspecified 8085 op codes enhance programming", W. Dehnhardt and
+    V. M. Sorensen, Electronics, January 1979.
+
+    The opcodes only on the 8085 use the Intel mnemonics as there is no
+    Z80 equivalent.
+*/
+
+// Interrupt masks and serial I/O
+pub fn build_rim() -> Opcode {
+    Opcode::new(
+        "RIM".to_string(),
+        |env: &mut Environment| {
+            // SID, pending I7.5 I6.5 I5.5, IE, masks M7.5 M6.5 M5.5
+            let sid = env.sys.serial_in();
+            let a = ((sid as u8) << 7)
+                | (env.state.rst_pending << 4)
+                | ((env.state.reg.iff2() as u8) << 3) // IE before a TRAP
+                | env.state.rst_mask;
+            env.state.reg.set_a(a);
+        }
+    )
+}
+
+pub fn build_sim() -> Opcode {
+    Opcode::new(
+        "SIM".to_string(),
+        |env: &mut Environment| {
+            // SOD, SDE, unused, R7.5, MSE, masks M7.5 M6.5 M5.5
+            let a = env.state.reg.a();
+            if a & 0x08 != 0 {
+                env.state.rst_mask = a & 0x07;
+            }
+            if a & 0x10 != 0 {
+                // Reset the RST 7.5 latch
+                env.state.rst_pending &= !0x04;
+            }
+            if a & 0x40 != 0 {
+                env.sys.serial_out(a & 0x80 != 0);
+            }
+        }
+    )
+}
+
+// Jumps
+pub fn build_jk(value: bool) -> Opcode {
+    let name = if value {"JK"} else {"JNK"};
+    Opcode::new(
+        format!("{name} nn"),
+        move |env: &mut Environment| {
+            let condition = env.state.reg.get_flag(Flag::K) == value;
+            if let Some(address) = conditional_jump(env, condition) {
+                env.state.reg.set_pc(address);
+            }
+        }
+    )
+}
+
+pub fn build_rstv() -> Opcode {
+    let mut opcode = Opcode::new(
+        "RSTV".to_string(),
+        |env: &mut Environment| {
+            if env.state.reg.get_flag(Flag::V) {
+                env.set_branch_taken();
+                env.internal_cycles(env.ir(), 1);
+                env.subroutine_call(0x0040);
+            }
+        }
+    );
+    opcode.flow = Flow::Call;
+    opcode
+}
+
+// 16 bit arithmetic
+pub fn build_inc_dec_rr_8085(rr: Reg16, inc: bool) -> Opcode {
+    let mnemonic = if inc {"INC"} else {"DEC"};
+    Opcode::new(
+        format!("{mnemonic} {rr:?}"),
+        move |env: &mut Environment| {
+            let v = env.state.reg.inc_dec16(rr, inc);
+            env.model_cycles(env.ir(), |timing| timing.inc_dec_16);
+            // K is set when the register wraps around
+            env.state.reg.put_flag(Flag::K, v == if inc {0x0000} else {0xffff});
+        }
+    )
+}
+
+pub fn build_dsub() -> Opcode {
+    Opcode::new(
+        "DSUB".to_string(),
+        |env: &mut Environment| {
+            // HL = HL - BC, without borrow
+            let hl = env.state.reg.get16(Reg16::HL) as u32;
+            let bc = env.state.reg.get16(Reg16::BC) as u32;
+            let v = hl.wrapping_sub(bc);
+            env.state.reg.update_arithmetic_flags_16(hl, bc, v, true);
+            env.state.reg.put_flag(Flag::Z, v as u16 == 0);
+            env.state.reg.set16(Reg16::HL, v as u16);
+        }
+    )
+}
+
+pub fn build_arhl() -> Opcode {
+    Opcode::new(
+        "ARHL".to_string(),
+        |env: &mut Environment| {
+            // Arithmetic shift right of HL, bit 0 to the carry
+            let hl = env.state.reg.get16(Reg16::HL);
+            env.state.reg.set16(Reg16::HL, ((hl as i16) >> 1) as u16);
+            env.state.reg.put_flag(Flag::C, hl & 1 != 0);
+        }
+    )
+}
+
+pub fn build_rdel() -> Opcode {
+    Opcode::new(
+        "RDEL".to_string(),
+        |env: &mut Environment| {
+            // Rotate DE left through the carry
+            let de = env.state.reg.get16(Reg16::DE);
+            let v = (de << 1) | env.state.reg.get_flag(Flag::C) as u16;
+            env.state.reg.set16(Reg16::DE, v);
+            env.state.reg.put_flag(Flag::C, de & 0x8000 != 0);
+            env.state.reg.put_flag(Flag::V, (de ^ v) & 0x8000 != 0);
+        }
+    )
+}
+
+// 16 bit loads
+pub fn build_ld_de_rr_n(rr: Reg16) -> Opcode {
+    let name = if rr == Reg16::SP {"LDSI"} else {"LDHI"};
+    Opcode::new(
+        format!("{name} n"),
+        move |env: &mut Environment| {
+            // DE = rr + n
+            let n = env.advance_pc();
+            let v = env.state.reg.get16(rr).wrapping_add(n as u16);
+            env.state.reg.set16(Reg16::DE, v);
+        }
+    )
+}
+
+pub fn build_shlx() -> Opcode {
+    Opcode::new(
+        "SHLX".to_string(),
+        |env: &mut Environment| {
+            let address = env.state.reg.get16(Reg16::DE);
+            let value = env.state.reg.get16(Reg16::HL);
+            env.poke16(address, value);
+        }
+    )
+}
+
+pub fn build_lhlx() -> Opcode {
+    Opcode::new(
+        "LHLX".to_string(),
+        |env: &mut Environment| {
+            let address = env.state.reg.get16(Reg16::DE);
+            let value = env.peek16(address);
+            env.state.reg.set16(Reg16::HL, value);
+        }
+    )
+}
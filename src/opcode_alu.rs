@@ -1,4 +1,4 @@
-use super::opcode::Opcode;
+use super::opcode::{Flow, Opcode};
 use super::environment::Environment;
 use super::registers::{Flag, Reg16, Reg8};
 use super::operators::{Operator, operator_cp};
@@ -43,15 +43,18 @@ pub fn build_operator_a_n((op, name): (Operator, &str)) -> Opcode {
 }
 
 pub fn build_cp_block((inc, repeat, postfix) : (bool, bool, &'static str)) -> Opcode {
-    Opcode::new(
+    let mut opcode = Opcode::new(
         format!("CP{postfix}"),
         move |env: &mut Environment| {
             let a = env.state.reg.a();
+            let address = env.state.reg.get16(Reg16::HL);
             let b = env.reg8_ext(Reg8::_HL);
+            env.internal_cycles(address, 5);
             let c_bak = env.state.reg.get_flag(Flag::C);
             operator_cp(env, a, b);
             let bc = env.state.reg.inc_dec16(Reg16::BC, false /*decrement*/);
             env.state.reg.inc_dec16(Reg16::HL, inc);
+            env.state.wz = if inc {env.state.wz.wrapping_add(1)} else {env.state.wz.wrapping_sub(1)};
 
             // TUZD-4.2
             let mut n = a.wrapping_sub(b);
@@ -67,9 +70,13 @@ pub fn build_cp_block((inc, repeat, postfix) : (bool, bool, &'static str)) -> Op
             if repeat && bc != 0 &&  a != b {
                 // Back to redo the instruction
                 env.set_branch_taken();
+                env.internal_cycles(address, 5);
                 let pc = env.state.reg.pc().wrapping_sub(2);
                 env.state.reg.set_pc(pc);
+                env.state.wz = pc.wrapping_add(1);
             }
         }
-    )
+    );
+    opcode.flow = if repeat { Flow::Loop } else { Flow::Other };
+    opcode
 }
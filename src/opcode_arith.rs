@@ -10,6 +10,8 @@ pub fn build_add_hl_rr(rr: Reg16) -> Opcode {
         move |env: &mut Environment| {
             let aa = env.index_value();
             let bb = env.reg16_ext(rr);
+            env.state.wz = aa.wrapping_add(1);
+            env.model_cycles(env.ir(), |timing| timing.add_16);
             let vv = operator_add16(env, aa, bb);
             env.set_reg16(Reg16::HL, vv);
         }
@@ -22,6 +24,8 @@ pub fn build_adc_hl_rr(rr: Reg16) -> Opcode {
         move |env: &mut Environment| {
             let aa = env.index_value(); // This will always be HL.
             let bb = env.reg16_ext(rr);
+            env.state.wz = aa.wrapping_add(1);
+            env.model_cycles(env.ir(), |timing| timing.add_16);
             let vv = operator_adc16(env, aa, bb);
             env.state.reg.set16(Reg16::HL, vv);
         }
@@ -34,6 +38,8 @@ pub fn build_sbc_hl_rr(rr: Reg16) -> Opcode {
         move |env: &mut Environment| {
             let aa = env.index_value(); // This will always be HL.
             let bb = env.reg16_ext(rr);
+            env.state.wz = aa.wrapping_add(1);
+            env.model_cycles(env.ir(), |timing| timing.add_16);
             let vv = operator_sbc16(env, aa, bb);
             env.state.reg.set16(Reg16::HL, vv);
         }
@@ -48,6 +54,9 @@ pub fn build_inc_r(r: Reg8) -> Opcode {
         move |env: &mut Environment| {
             let a = env.reg8_ext(r);
             let v = operator_inc(env, a);
+            if r == Reg8::_HL {
+                env.read_modify_write_cycles(env.index_address());
+            }
             env.set_reg(r, v);
         }
     )
@@ -59,6 +68,9 @@ pub fn build_dec_r(r: Reg8) -> Opcode {
         move |env: &mut Environment| {
             let a = env.reg8_ext(r);
             let v = operator_dec(env, a);
+            if r == Reg8::_HL {
+                env.read_modify_write_cycles(env.index_address());
+            }
             env.set_reg(r, v);
         }
     )
@@ -72,6 +84,7 @@ pub fn build_inc_dec_rr(rr: Reg16, inc: bool) -> Opcode {
         move |env: &mut Environment| {
             let mut v = env.reg16_ext(rr);
             v = v.wrapping_add(delta);
+            env.model_cycles(env.ir(), |timing| timing.inc_dec_16);
             env.set_reg16(rr, v);
             // Note: flags not affected on the 16 bit INC and DEC
         }
@@ -1,13 +1,15 @@
+use super::cpu::CpuModel;
 use super::opcode::Opcode;
 use super::environment::Environment;
-use super::registers::{Flag, Reg8};
+use super::registers::{Flag, Reg16, Reg8};
 
 #[derive(Copy, Clone)]
 pub enum ShiftMode {
     Arithmetic,
     Logical,
     Rotate,
-    RotateCarry
+    RotateCarry,
+    Extend, // The bit shifted out is kept, SLL on the R800
 }
 
 #[derive(Copy, Clone)]
@@ -36,12 +38,14 @@ pub fn build_rot_r(r: Reg8, (dir, mode, name): (ShiftDir, ShiftMode, &str), fast
             let carry = match dir {
                 ShiftDir::Left => {
                     let upper_bit = v >= 0x80;
+                    let lower_bit = (v & 1) == 1;
                     v <<= 1;
                     let set_lower_bit = match mode {
                         ShiftMode::Arithmetic => false, // always 0 in bit 0
                         ShiftMode::Logical => true, // always 1 in bit 0
                         ShiftMode::Rotate => env.state.reg.get_flag(Flag::C), // carry in bit 0
                         ShiftMode::RotateCarry => upper_bit, // bit 7 moves to bit 0
+                        ShiftMode::Extend => lower_bit, // bit 0 is kept
                     };
                     if set_lower_bit { // bit 0 is 0 already
                         v |= 1;
@@ -53,7 +57,7 @@ pub fn build_rot_r(r: Reg8, (dir, mode, name): (ShiftDir, ShiftMode, &str), fast
                     let lower_bit = (v & 1) == 1;
                     v >>= 1;
                     let set_upper_bit = match mode {
-                        ShiftMode::Arithmetic => upper_bit, // extend bit 7
+                        ShiftMode::Arithmetic | ShiftMode::Extend => upper_bit, // extend bit 7
                         ShiftMode::Logical => false, // always 0 in bit 7
                         ShiftMode::Rotate => env.state.reg.get_flag(Flag::C), // carry in bit 0
                         ShiftMode::RotateCarry => lower_bit, // bit 0 goes to bit 7
@@ -64,10 +68,17 @@ pub fn build_rot_r(r: Reg8, (dir, mode, name): (ShiftDir, ShiftMode, &str), fast
                     lower_bit
                 }
             };
+            if indexed || r == Reg8::_HL {
+                env.read_modify_write_cycles(env.index_address());
+            }
             if indexed && r != Reg8::_HL {
+                // The result is also copied to the register. H and L are
+                // not replaced by IXH or IXL.
                 env.set_reg(Reg8::_HL, v);
+                env.state.reg.set8(r, v);
+            } else {
+                env.set_reg(r, v);
             }
-            env.set_reg(r, v);
 
             env.state.reg.put_flag(Flag::C, carry);
             env.state.reg.update_hn_flags(false, false);
@@ -85,6 +96,9 @@ pub fn build_bit_r(n: u8, r: Reg8) -> Opcode {
         format!("BIT {n}, {r}"),
         move |env: &mut Environment| {
             let v = env.reg8_ext(r);
+            if r == Reg8::_HL {
+                env.internal_cycles(env.index_address(), 1);
+            }
             let z = v & (1<<n);
             env.state.reg.put_flag(Flag::S, (z & 0x80) != 0);
             env.state.reg.put_flag(Flag::Z, z == 0);
@@ -101,15 +115,14 @@ pub fn build_bit_r(n: u8, r: Reg8) -> Opcode {
                 different, namely bit 5 and 3 of the high byte of IX+d (so IX
                 plus the displacement).
                 */
-                let address = env.index_address();
-                env.state.reg.update_undocumented_flags((address >> 8) as u8);
-
                 // Exceptions for (HL) TUZD-4-1
                 /* Things get more bizarre with the BIT n,(HL)
                 instruction. Again, except for YF and XF the flags
                 are the same. YF and XF are copied from some sort
                 of internal register */
-                // Not implemented. Just done the same than for (IX+d)
+                // Both cases are the high byte of WZ, that is set to IX+d
+                // when the displacement is loaded.
+                env.state.reg.update_undocumented_flags((env.state.wz >> 8) as u8);
             } else {
                 env.state.reg.update_undocumented_flags(v); // TUZD-4.1, copy bits from reg
             }
@@ -129,6 +142,9 @@ pub fn build_set_res_r(bit: u8, r: Reg8, value: bool) -> Opcode {
                 v &= !(1<<bit);
             }
 
+            if r == Reg8::_HL {
+                env.read_modify_write_cycles(env.index_address());
+            }
             env.set_reg(r, v);
         }
     )
@@ -151,9 +167,11 @@ pub fn build_indexed_set_res_r(bit: u8, r: Reg8, value: bool) -> Opcode {
             } else {
                 v &= !(1<<bit);
             }
+            env.read_modify_write_cycles(env.index_address());
             env.set_reg(Reg8::_HL, v);
             if r != Reg8::_HL {
-                env.set_reg(r, v);
+                // H and L are not replaced by IXH or IXL
+                env.state.reg.set8(r, v);
             }
         }
     )
@@ -179,11 +197,11 @@ pub fn build_scf() -> Opcode {
     Opcode::new(
         "SCF".to_string(),
         |env: &mut Environment| {
-            let a = env.state.reg.a();
+            let reference = scf_ccf_undocumented_reference(env);
 
             env.state.reg.set_flag(Flag::C);
             env.state.reg.update_hn_flags(false, false);
-            env.state.reg.update_undocumented_flags(a);
+            env.state.reg.update_undocumented_flags(reference);
         }
     )
 }
@@ -192,16 +210,32 @@ pub fn build_ccf() -> Opcode {
     Opcode::new(
         "CCF".to_string(),
         |env: &mut Environment| {
-            let a = env.state.reg.a();
+            let reference = scf_ccf_undocumented_reference(env);
             let c = env.state.reg.get_flag(Flag::C);
 
             env.state.reg.put_flag(Flag::C, !c);
             env.state.reg.update_hn_flags(c, false);
-            env.state.reg.update_undocumented_flags(a);
+            env.state.reg.update_undocumented_flags(reference);
         }
     )
 }
 
+fn scf_ccf_undocumented_reference(env: &Environment) -> u8 {
+    /*
+    Flags 3 and 5 depend on the Q register, the flags set by the previous
+    instruction. On Zilog they are copied from (Q xor F) or A. That is, from
+    A if the previous instruction modified the flags, from A or F otherwise.
+    See https://github.com/redcode/Z80/wiki/Z80-XCF-Flavor
+    */
+    let a = env.state.reg.a();
+    let qf = env.state.q ^ env.state.reg.get8(Reg8::F);
+    match env.state.model {
+        CpuModel::ZilogNmos | CpuModel::ZilogCmos => qf | a,
+        CpuModel::NecNmos => a,
+        CpuModel::StCmos => ((qf | a) & (1<<5)) | (a & (1<<3)),
+    }
+}
+
 pub fn build_rxd(dir: ShiftDir, name: &str) -> Opcode {
     Opcode::new(
         name.to_string(),
@@ -224,7 +258,9 @@ pub fn build_rxd(dir: ShiftDir, name: &str) -> Opcode {
                 }
             }
             env.state.reg.set_a(a);
+            env.internal_cycles(env.index_address(), 4); // The nibbles are rotated
             env.set_reg(Reg8::_HL, phl);
+            env.state.wz = env.state.reg.get16(Reg16::HL).wrapping_add(1);
 
             env.state.reg.update_bits_in_flags(a);
         }
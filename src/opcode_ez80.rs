@@ -0,0 +1,524 @@
+use super::opcode::{Flow, Opcode};
+use super::environment::Environment;
+use super::operators::*;
+use super::registers::{Flag, Reg16, Reg8};
+
+/*
+    eZ80 specific opcodes and behaviour. See the eZ80 CPU User Manual,
+    Zilog UM0077.
+
+    The registers, the addresses and the stack are 24 bits wide in ADL
+    mode and 16 bits wide in Z80 mode, where MBASE is the upper byte of
+    the addresses. The suffixes .SIS, .LIS, .SIL and .LIL select the width
+    of the data and of the immediate values for one instruction. The
+    opcodes here replace the ones of the Z80 that depend on the width.
+
+    A jump with a suffix changes the ADL mode. The calls and returns with
+    a suffix, that mix the widths of the stack frames with MADL, are not
+    emulated, the stack frame has the width of the instruction.
+*/
+
+// Registers without IX or IY replacing HL
+fn get_word(env: &Environment, rr: Reg16) -> u32 {
+    if rr == Reg16::AF {
+        env.state.reg.get16(rr) as u32
+    } else if env.is_long() {
+        env.state.reg.get24(rr)
+    } else {
+        env.state.reg.get16(rr) as u32
+    }
+}
+
+fn set_word(env: &mut Environment, rr: Reg16, value: u32) {
+    if rr == Reg16::AF {
+        env.state.reg.set16(rr, value as u16);
+    } else if env.is_long() {
+        env.state.reg.set24(rr, value);
+    } else {
+        env.state.reg.set16(rr, value as u16);
+    }
+}
+
+fn jump(env: &mut Environment, address: u32) {
+    // JP.SIS and JP.LIL switch to Z80 or to ADL mode
+    if let Some(suffix) = env.state.suffix {
+        env.state.reg.set_adl(suffix.long());
+    }
+    env.set_pc_word(address);
+    env.state.wz = address as u16;
+}
+
+fn repeat(env: &mut Environment) {
+    // Back to redo the instruction, with the suffix if there is one
+    let length = if env.state.suffix.is_some() {3} else {2};
+    let pc = env.pc_word().wrapping_sub(length);
+    env.set_pc_word(pc & 0xffffff);
+}
+
+// 8 bit load
+pub fn build_ld_a_prr_ez80(rr: Reg16) -> Opcode {
+    // rr can be only BC or DE
+    Opcode::new(
+        format!("LD A, ({rr:?})"),
+        move |env: &mut Environment| {
+            let address = env.address24(env.reg_word(rr));
+            let value = env.peek24(address);
+            env.state.reg.set_a(value);
+        }
+    )
+}
+
+pub fn build_ld_prr_a_ez80(rr: Reg16) -> Opcode {
+    // rr can be only BC or DE
+    Opcode::new(
+        format!("LD ({rr:?}), A"),
+        move |env: &mut Environment| {
+            let address = env.address24(env.reg_word(rr));
+            env.poke24(address, env.state.reg.a());
+        }
+    )
+}
+
+pub fn build_ld_a_pnn_ez80() -> Opcode {
+    Opcode::new(
+        "LD A, (nn)".to_string(),
+        |env: &mut Environment| {
+            let address = env.advance_immediate_word();
+            let address = env.address24(address);
+            let value = env.peek24(address);
+            env.state.reg.set_a(value);
+        }
+    )
+}
+
+pub fn build_ld_pnn_a_ez80() -> Opcode {
+    Opcode::new(
+        "LD (nn), A".to_string(),
+        |env: &mut Environment| {
+            let address = env.advance_immediate_word();
+            let address = env.address24(address);
+            env.poke24(address, env.state.reg.a());
+        }
+    )
+}
+
+pub fn build_ld_mb_a() -> Opcode {
+    Opcode::new(
+        "LD MB, A".to_string(),
+        |env: &mut Environment| {
+            env.state.reg.set_mbase(env.state.reg.a());
+        }
+    )
+}
+
+pub fn build_ld_a_mb() -> Opcode {
+    Opcode::new(
+        "LD A, MB".to_string(),
+        |env: &mut Environment| {
+            env.state.reg.set_a(env.state.reg.mbase());
+        }
+    )
+}
+
+// 16 or 24 bit load
+pub fn build_ld_rr_nn_ez80(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("LD {rr:?}, nn"),
+        move |env: &mut Environment| {
+            let value = env.advance_immediate_word();
+            env.set_reg_word(rr, value);
+        }
+    )
+}
+
+pub fn build_ld_pnn_rr_ez80(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("LD (nn), {rr:?}"),
+        move |env: &mut Environment| {
+            let address = env.advance_immediate_word();
+            let value = env.reg_word(rr);
+            env.poke_word(address, value);
+        }
+    )
+}
+
+pub fn build_ld_rr_pnn_ez80(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("LD {rr:?}, (nn)"),
+        move |env: &mut Environment| {
+            let address = env.advance_immediate_word();
+            let value = env.peek_word(address);
+            env.set_reg_word(rr, value);
+        }
+    )
+}
+
+pub fn build_ld_rr_phl(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("LD {rr:?}, (HL)"),
+        move |env: &mut Environment| {
+            let address = env.index_address24();
+            let value = env.peek_word(address);
+            env.set_reg_word(rr, value);
+        }
+    )
+}
+
+pub fn build_ld_phl_rr(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("LD (HL), {rr:?}"),
+        move |env: &mut Environment| {
+            let address = env.index_address24();
+            let value = env.reg_word(rr);
+            env.poke_word(address, value);
+        }
+    )
+}
+
+pub fn build_ld_sp_hl_ez80() -> Opcode {
+    Opcode::new(
+        "LD SP, HL".to_string(),
+        |env: &mut Environment| {
+            let value = env.reg_word(Reg16::HL);
+            env.set_reg_word(Reg16::SP, value);
+        }
+    )
+}
+
+pub fn build_lea(rr: Reg16, index: Reg16) -> Opcode {
+    Opcode::new(
+        format!("LEA {rr:?}, {index:?}s"),
+        move |env: &mut Environment| {
+            // rr = index + d, no memory access
+            let d = env.advance_pc() as i8;
+            let value = env.reg_word(index).wrapping_add(d as u32);
+            env.set_reg_word(rr, value);
+        }
+    )
+}
+
+pub fn build_pea(index: Reg16) -> Opcode {
+    Opcode::new(
+        format!("PEA {index:?}s"),
+        move |env: &mut Environment| {
+            let d = env.advance_pc() as i8;
+            let value = env.reg_word(index).wrapping_add(d as u32);
+            env.push_word(value);
+        }
+    )
+}
+
+// Exchanges
+pub fn build_ex_de_hl_ez80() -> Opcode {
+    Opcode::new(
+        "EX DE, HL".to_string(),
+        |env: &mut Environment| {
+            let temp = get_word(env, Reg16::HL); // No IX/IY variant
+            let de = get_word(env, Reg16::DE);
+            set_word(env, Reg16::HL, de);
+            set_word(env, Reg16::DE, temp);
+        }
+    )
+}
+
+pub fn build_ex_psp_hl_ez80() -> Opcode {
+    Opcode::new(
+        "EX (SP), HL".to_string(),
+        |env: &mut Environment| {
+            let address = env.reg_word(Reg16::SP);
+            let temp = env.reg_word(Reg16::HL);
+            let value = env.peek_word(address);
+            env.set_reg_word(Reg16::HL, value);
+            env.poke_word(address, temp);
+        }
+    )
+}
+
+// Stack
+pub fn build_push_rr_ez80(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("PUSH {rr:?}"),
+        move |env: &mut Environment| {
+            let value = if rr == Reg16::AF {
+                get_word(env, rr)
+            } else {
+                env.reg_word(rr)
+            };
+            env.push_word(value);
+        }
+    )
+}
+
+pub fn build_pop_rr_ez80(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("POP {rr:?}"),
+        move |env: &mut Environment| {
+            let value = env.pop_word();
+            if rr == Reg16::AF {
+                set_word(env, rr, value);
+            } else {
+                env.set_reg_word(rr, value);
+            }
+        }
+    )
+}
+
+// Arithmetic
+fn update_add_word_flags(env: &mut Environment, a: u32, b: u32, v: u32) {
+    // As on the 16 bit ADD, with the carry from bit 23. S, Z and P/V are
+    // not updated.
+    let xor = a ^ b ^ v;
+    env.state.reg.update_undocumented_flags((v >> 16) as u8);
+    env.state.reg.put_flag(Flag::C, (v >> 24) & 1 != 0);
+    env.state.reg.put_flag(Flag::H, (xor >> 12) & 1 != 0);
+    env.state.reg.clear_flag(Flag::N);
+}
+
+fn update_adc_sbc_word_flags(env: &mut Environment, a: u32, b: u32, v: u32, neg: bool) {
+    let xor = a ^ b ^ v;
+    let carry = (v >> 24) & 1 != 0;
+    env.state.reg.update_undocumented_flags((v >> 16) as u8);
+    env.state.reg.put_flag(Flag::S, (v >> 23) & 1 != 0);
+    env.state.reg.put_flag(Flag::Z, v & 0xffffff == 0);
+    env.state.reg.put_flag(Flag::H, (xor >> 12) & 1 != 0);
+    env.state.reg.put_flag(Flag::P, carry != ((xor >> 23) & 1 != 0)); // As overflow flag
+    env.state.reg.put_flag(Flag::N, neg);
+    env.state.reg.put_flag(Flag::C, carry);
+}
+
+pub fn build_add_hl_rr_ez80(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("ADD HL, {rr:?}"),
+        move |env: &mut Environment| {
+            let a = env.reg_word(Reg16::HL);
+            let b = env.reg_word(rr);
+            let v = if env.is_long() {
+                let v = a + b;
+                update_add_word_flags(env, a, b, v);
+                v
+            } else {
+                operator_add16(env, a as u16, b as u16) as u32
+            };
+            env.set_reg_word(Reg16::HL, v);
+        }
+    )
+}
+
+pub fn build_adc_hl_rr_ez80(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("ADC HL, {rr:?}"),
+        move |env: &mut Environment| {
+            let a = env.reg_word(Reg16::HL);
+            let b = env.reg_word(rr);
+            let v = if env.is_long() {
+                let c = env.state.reg.get_flag(Flag::C) as u32;
+                let v = a + b + c;
+                update_adc_sbc_word_flags(env, a, b, v, false);
+                v
+            } else {
+                operator_adc16(env, a as u16, b as u16) as u32
+            };
+            env.set_reg_word(Reg16::HL, v);
+        }
+    )
+}
+
+pub fn build_sbc_hl_rr_ez80(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("SBC HL, {rr:?}"),
+        move |env: &mut Environment| {
+            let a = env.reg_word(Reg16::HL);
+            let b = env.reg_word(rr);
+            let v = if env.is_long() {
+                let c = env.state.reg.get_flag(Flag::C) as u32;
+                let v = a.wrapping_sub(b).wrapping_sub(c);
+                update_adc_sbc_word_flags(env, a, b, v, true);
+                v
+            } else {
+                operator_sbc16(env, a as u16, b as u16) as u32
+            };
+            env.set_reg_word(Reg16::HL, v);
+        }
+    )
+}
+
+pub fn build_inc_dec_rr_ez80(rr: Reg16, inc: bool) -> Opcode {
+    let mnemonic = if inc {"INC"} else {"DEC"};
+    Opcode::new(
+        format!("{mnemonic} {rr:?}"),
+        move |env: &mut Environment| {
+            env.inc_dec_word(rr, inc);
+            // Note: flags not affected on the 16 bit INC and DEC
+        }
+    )
+}
+
+// Jumps and calls
+pub fn build_jp_ez80() -> Opcode {
+    Opcode::new(
+        "JP nn".to_string(),
+        |env: &mut Environment| {
+            let address = env.advance_immediate_word();
+            jump(env, address);
+        }
+    )
+}
+
+pub fn build_jp_eq_ez80((flag, value, name): (Flag, bool, &str)) -> Opcode {
+    Opcode::new(
+        format!("JP {name}, nn"),
+        move |env: &mut Environment| {
+            let address = env.advance_immediate_word();
+            if env.state.reg.get_flag(flag) == value {
+                env.set_branch_taken();
+                jump(env, address);
+            }
+        }
+    )
+}
+
+pub fn build_jp_hl_ez80() -> Opcode {
+    Opcode::new(
+        "JP HL".to_string(),
+        |env: &mut Environment| {
+            let address = env.reg_word(Reg16::HL);
+            jump(env, address);
+        }
+    )
+}
+
+pub fn build_call_ez80() -> Opcode {
+    let mut opcode = Opcode::new(
+        "CALL nn".to_string(),
+        |env: &mut Environment| {
+            let address = env.advance_immediate_word();
+            env.subroutine_call_word(address);
+        }
+    );
+    opcode.flow = Flow::Call;
+    opcode
+}
+
+pub fn build_call_eq_ez80((flag, value, name): (Flag, bool, &str)) -> Opcode {
+    let mut opcode = Opcode::new(
+        format!("CALL {name}, nn"),
+        move |env: &mut Environment| {
+            let address = env.advance_immediate_word();
+            if env.state.reg.get_flag(flag) == value {
+                env.set_branch_taken();
+                env.subroutine_call_word(address);
+            }
+        }
+    );
+    opcode.flow = Flow::Call;
+    opcode
+}
+
+// Block instructions, as on the Z80 with the width of the mode
+pub fn build_ld_block_ez80((inc, repeats, postfix) : (bool, bool, &'static str)) -> Opcode {
+    let mut opcode = Opcode::new(
+        format!("LD{postfix}"),
+        move |env: &mut Environment| {
+            let value = env.reg8_ext(Reg8::_HL);
+            let address = env.address24(env.reg_word(Reg16::DE));
+            env.poke24(address, value);
+
+            env.inc_dec_word(Reg16::DE, inc);
+            env.inc_dec_word(Reg16::HL, inc);
+            let bc = env.inc_dec_word(Reg16::BC, false /*decrement*/);
+
+            env.state.reg.clear_flag(Flag::N);
+            env.state.reg.clear_flag(Flag::H);
+            env.state.reg.put_flag(Flag::P, bc != 0);
+
+            if repeats && bc != 0 {
+                env.set_branch_taken();
+                repeat(env);
+            }
+        }
+    );
+    opcode.flow = if repeats { Flow::Loop } else { Flow::Other };
+    opcode
+}
+
+pub fn build_cp_block_ez80((inc, repeats, postfix) : (bool, bool, &'static str)) -> Opcode {
+    let mut opcode = Opcode::new(
+        format!("CP{postfix}"),
+        move |env: &mut Environment| {
+            let a = env.state.reg.a();
+            let b = env.reg8_ext(Reg8::_HL);
+            let c_bak = env.state.reg.get_flag(Flag::C);
+            operator_cp(env, a, b);
+            let bc = env.inc_dec_word(Reg16::BC, false /*decrement*/);
+            env.inc_dec_word(Reg16::HL, inc);
+
+            env.state.reg.set_flag(Flag::N);
+            env.state.reg.put_flag(Flag::P, bc != 0);
+            env.state.reg.put_flag(Flag::C, c_bak); // C unchanged
+
+            if repeats && bc != 0 && a != b {
+                env.set_branch_taken();
+                repeat(env);
+            }
+        }
+    );
+    opcode.flow = if repeats { Flow::Loop } else { Flow::Other };
+    opcode
+}
+
+pub fn build_in_block_ez80((inc, repeats, postfix) : (bool, bool, &'static str)) -> Opcode {
+    let mut opcode = Opcode::new(
+        format!("IN{postfix}"),
+        move |env: &mut Environment| {
+            let address = env.state.reg.get16(Reg16::BC);
+            let value = env.port_in(address);
+            env.set_reg(Reg8::_HL, value);
+            env.inc_dec_word(Reg16::HL, inc);
+            let b = env.state.reg.inc_dec8(Reg8::B, false /* decrement */);
+
+            env.state.reg.put_flag(Flag::Z, b == 0);
+            env.state.reg.put_flag(Flag::N, value & 0x80 != 0);
+
+            if repeats && b != 0 {
+                env.set_branch_taken();
+                repeat(env);
+            }
+        }
+    );
+    opcode.flow = if repeats { Flow::Loop } else { Flow::Other };
+    opcode
+}
+
+pub fn build_out_block_ez80((inc, repeats, postfix) : (bool, bool, &'static str)) -> Opcode {
+    let n0 = if repeats {"OT"} else {"OUT"};
+    let mut opcode = Opcode::new(
+        format!("{n0}{postfix}"),
+        move |env: &mut Environment| {
+            let value = env.reg8_ext(Reg8::_HL);
+            let address = env.state.reg.get16(Reg16::BC);
+            env.port_out(address, value);
+            env.inc_dec_word(Reg16::HL, inc);
+            let b = env.state.reg.inc_dec8(Reg8::B, false /* decrement */);
+
+            env.state.reg.put_flag(Flag::Z, b == 0);
+            env.state.reg.put_flag(Flag::N, value & 0x80 != 0);
+
+            if repeats && b != 0 {
+                env.set_branch_taken();
+                repeat(env);
+            }
+        }
+    );
+    opcode.flow = if repeats { Flow::Loop } else { Flow::Other };
+    opcode
+}
+
+// Mixed memory mode for the interrupts
+pub fn build_stmix_rsmix(madl: bool) -> Opcode {
+    Opcode::new(
+        if madl {"STMIX"} else {"RSMIX"}.to_string(),
+        move |env: &mut Environment| {
+            env.state.reg.set_madl(madl);
+        }
+    )
+}
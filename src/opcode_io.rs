@@ -1,4 +1,4 @@
-use super::opcode::Opcode;
+use super::opcode::{Flow, Opcode};
 use super::environment::Environment;
 use super::registers::{Reg16, Reg8};
 
@@ -22,6 +22,7 @@ pub fn build_out_c_r(r: Reg8) -> Opcode {
             let address = env.state.reg.get16(Reg16::BC);
             let value = env.state.reg.get8(r);
             env.port_out(address, value);
+            env.state.wz = address.wrapping_add(1);
         }
     )
 }
@@ -30,8 +31,11 @@ pub fn build_out_c_0() -> Opcode {
     Opcode::new(
         "OUT (C), 0".to_string(),
         |env: &mut Environment| {
+            // The CMOS parts write FFh
+            let value = if env.state.model.is_cmos() {0xff} else {0};
             let address = env.state.reg.get16(Reg16::BC);
-            env.port_out(address, 0);
+            env.port_out(address, value);
+            env.state.wz = address.wrapping_add(1);
         }
     )
 }
@@ -43,6 +47,7 @@ pub fn build_out_n_a() -> Opcode {
             let a = env.state.reg.a();
             let address = ((a as u16) << 8) + env.advance_pc() as u16;
             env.port_out(address, a);
+            env.state.wz = ((a as u16) << 8) | (address.wrapping_add(1) & 0xff);
         }
     )
 }
@@ -53,6 +58,7 @@ pub fn build_in_r_c(r: Reg8) -> Opcode {
         move |env: &mut Environment| {
             let address = env.state.reg.get16(Reg16::BC);
             let value = env.port_in(address);
+            env.state.wz = address.wrapping_add(1);
             env.state.reg.set8(r, value);
 
             env.state.reg.update_bits_in_flags(value);
@@ -66,6 +72,7 @@ pub fn build_in_0_c() -> Opcode {
         |env: &mut Environment| {
             let address = env.state.reg.get16(Reg16::BC);
             let value = env.port_in(address);
+            env.state.wz = address.wrapping_add(1);
 
             env.state.reg.update_bits_in_flags(value);
         }
@@ -79,6 +86,7 @@ pub fn build_in_a_n() -> Opcode {
             let a = env.state.reg.a();
             let address = ((a as u16) << 8) + env.advance_pc() as u16;
             let value = env.port_in(address);
+            env.state.wz = address.wrapping_add(1);
             env.state.reg.set_a(value);
         }
     )
@@ -90,15 +98,22 @@ instructions before.
 */
 
 pub fn build_in_block((inc, repeat, postfix) : (bool, bool, &'static str)) -> Opcode {
-    Opcode::new(
+    let mut opcode = Opcode::new(
         format!("IN{postfix}"),
         move |env: &mut Environment| {
+            env.internal_cycles(env.ir(), 1);
+
+            // WZ is set from BC before decrementing B
+            let bc = env.state.reg.get16(Reg16::BC);
+            env.state.wz = if inc {bc.wrapping_add(1)} else {bc.wrapping_sub(1)};
+
             // The INI/INIR/IND/INDR instructions use BC after decrementing B
             let b = env.state.reg.inc_dec8(Reg8::B, false /* decrement */);
             let address = env.state.reg.get16(Reg16::BC);
 
             let value = env.port_in(address);
             // We won't have IX and IY cases to consider
+            let hl = env.state.reg.get16(Reg16::HL);
             env.set_reg(Reg8::_HL, value);
             env.state.reg.inc_dec16(Reg16::HL, inc);
 
@@ -110,21 +125,28 @@ pub fn build_in_block((inc, repeat, postfix) : (bool, bool, &'static str)) -> Op
             if repeat && b != 0 {
                 // Back to redo the instruction
                 env.set_branch_taken();
+                env.internal_cycles(hl, 5);
                 let pc = env.state.reg.pc().wrapping_sub(2);
                 env.state.reg.set_pc(pc);
             }
         }
-    )
+    );
+    opcode.flow = if repeat { Flow::Loop } else { Flow::Other };
+    opcode
 }
 
 pub fn build_out_block((inc, repeat, postfix) : (bool, bool, &'static str)) -> Opcode {
     let n0 = if repeat {"OT"} else {"OUT"};
-    Opcode::new(
+    let mut opcode = Opcode::new(
         format!("{n0}{postfix}"),
         move |env: &mut Environment| {
+            env.internal_cycles(env.ir(), 1);
+
             // the OUTI/OTIR/OUTD/OTDR instructions use BC before decrementing B
             let address = env.state.reg.get16(Reg16::BC);
             let b = env.state.reg.inc_dec8(Reg8::B, false /* decrement */);
+            let bc = env.state.reg.get16(Reg16::BC);
+            env.state.wz = if inc {bc.wrapping_add(1)} else {bc.wrapping_sub(1)};
 
             // We won't have IX and IY cases to consider
             let value = env.reg8_ext(Reg8::_HL);
@@ -138,9 +160,12 @@ pub fn build_out_block((inc, repeat, postfix) : (bool, bool, &'static str)) -> O
             if repeat && b != 0 {
                 // Back to redo the instruction
                 env.set_branch_taken();
+                env.internal_cycles(bc, 5);
                 let pc = env.state.reg.pc().wrapping_sub(2);
                 env.state.reg.set_pc(pc);
             }
         }
-    )
+    );
+    opcode.flow = if repeat { Flow::Loop } else { Flow::Other };
+    opcode
 }
\ No newline at end of file
@@ -1,12 +1,13 @@
-use super::opcode::Opcode;
+use super::opcode::{Flow, Opcode};
 use super::environment::Environment;
 use super::registers::{Flag, Reg8};
 
 // Relative jumps
 pub fn build_djnz() -> Opcode {
-    Opcode::new(
+    let mut opcode = Opcode::new(
         "DJNZ d".to_string(),
         |env: &mut Environment| {
+            env.internal_cycles(env.ir(), 1); // B is decremented
             let offset = env.advance_pc();
             let b = env.state.reg.get8(Reg8::B).wrapping_add(0xff /* -1 */);
             env.state.reg.set8(Reg8::B, b);
@@ -16,7 +17,9 @@ pub fn build_djnz() -> Opcode {
                 relative_jump(env, offset);
             }
         }
-    )
+    );
+    opcode.flow = Flow::Loop;
+    opcode
 }
 
 pub fn build_jr_unconditional() -> Opcode {
@@ -44,9 +47,19 @@ pub fn build_jr_eq((flag, value, name): (Flag, bool, &str)) -> Opcode {
 
 
 fn relative_jump(env: &mut Environment, offset: u8) {
+    if env.state.reg.is_adl() {
+        // 24 bit PC of the eZ80
+        let pc = env.state.reg.pc24().wrapping_add(offset as i8 as u32) & 0xffffff;
+        env.state.reg.set_pc24(pc);
+        env.state.wz = pc as u16;
+        return;
+    }
     let mut pc = env.state.reg.pc();
+    // The address is calculated with the offset still on the bus
+    env.internal_cycles(pc.wrapping_sub(1), 5);
     pc = pc.wrapping_add(offset as i8 as i16 as u16);
     env.state.reg.set_pc(pc);
+    env.state.wz = pc;
 }
 
 // Absolute jumps
@@ -56,6 +69,7 @@ pub fn build_jp_unconditional() -> Opcode {
         |env: &mut Environment| {
             let address = env.advance_immediate16();
             env.state.reg.set_pc(address);
+            env.state.wz = address;
         }
     )
 }
@@ -65,6 +79,7 @@ pub fn build_jp_eq((flag, value, name): (Flag, bool, &str)) -> Opcode {
         format!("JP {name}, nn"),
         move |env: &mut Environment| {
             let address = env.advance_immediate16();
+            env.state.wz = address; // Even if the jump is not taken
             if env.state.reg.get_flag(flag) == value {
                 env.set_branch_taken();
                 env.state.reg.set_pc(address);
@@ -86,36 +101,89 @@ pub fn build_jp_hl() -> Opcode {
 
 // Calls to subroutine
 pub fn build_call() -> Opcode {
-    Opcode::new(
+    let mut opcode = Opcode::new(
         "CALL nn".to_string(),
         |env: &mut Environment| {
             let address = env.advance_immediate16();
+            env.internal_cycles(env.state.reg.pc().wrapping_sub(1), 1);
             env.subroutine_call(address);
         }
-    )
+    );
+    opcode.flow = Flow::Call;
+    opcode
 }
 
 pub fn build_call_eq((flag, value, name): (Flag, bool, &str)) -> Opcode {
-    Opcode::new(
+    let mut opcode = Opcode::new(
         format!("CALL {name}, nn"),
         move |env: &mut Environment| {
             let address = env.advance_immediate16();
+            env.state.wz = address; // Even if the call is not done
             if env.state.reg.get_flag(flag) == value {
                 env.set_branch_taken();
+                env.internal_cycles(env.state.reg.pc().wrapping_sub(1), 1);
                 env.subroutine_call(address);
             }
         }
+    );
+    opcode.flow = Flow::Call;
+    opcode
+}
+
+// Jumps and calls with the condition checked early, as on the 8085 and on
+// the Z180. The high byte of the address is not read when the condition is
+// false.
+pub fn conditional_jump(env: &mut Environment, condition: bool) -> Option<u16> {
+    let l = env.advance_pc();
+    if condition {
+        env.set_branch_taken();
+        let h = env.advance_pc();
+        Some(((h as u16) << 8) | l as u16)
+    } else {
+        let pc = env.state.reg.pc().wrapping_add(1);
+        env.state.reg.set_pc(pc);
+        None
+    }
+}
+
+pub fn build_jp_eq_early((flag, value, name): (Flag, bool, &str)) -> Opcode {
+    Opcode::new(
+        format!("JP {name}, nn"),
+        move |env: &mut Environment| {
+            let condition = env.state.reg.get_flag(flag) == value;
+            if let Some(address) = conditional_jump(env, condition) {
+                env.state.reg.set_pc(address);
+            }
+        }
     )
 }
 
+pub fn build_call_eq_early((flag, value, name): (Flag, bool, &str)) -> Opcode {
+    let mut opcode = Opcode::new(
+        format!("CALL {name}, nn"),
+        move |env: &mut Environment| {
+            let condition = env.state.reg.get_flag(flag) == value;
+            if let Some(address) = conditional_jump(env, condition) {
+                env.internal_cycles(env.state.reg.pc().wrapping_sub(1), 1);
+                env.subroutine_call(address);
+            }
+        }
+    );
+    opcode.flow = Flow::Call;
+    opcode
+}
+
 pub fn build_rst(d: u8) -> Opcode {
-    Opcode::new(
+    let mut opcode = Opcode::new(
         format!("RST {d:02x}h"),
         move |env: &mut Environment| {
             let address = d as u16;
+            env.internal_cycles(env.ir(), 1);
             env.subroutine_call(address);
         }
-    )
+    );
+    opcode.flow = Flow::Call;
+    opcode
 }
 
 // Returns
@@ -134,6 +202,7 @@ pub fn build_reti() -> Opcode {
         "RETI".to_string(),
         |env: &mut Environment| {
             env.subroutine_return();
+            env.sys.reti();
         }
     )
 }
@@ -144,6 +213,7 @@ pub fn build_retn() -> Opcode {
         |env: &mut Environment| {
             env.subroutine_return();
             env.state.reg.end_nmi();
+            env.sys.retn();
         }
     )
 }
@@ -152,6 +222,7 @@ pub fn build_ret_eq((flag, value, name): (Flag, bool, &str)) -> Opcode {
     Opcode::new(
         format!("RET {name}"),
         move |env: &mut Environment| {
+            env.internal_cycles(env.ir(), 1); // The condition is evaluated
             if env.state.reg.get_flag(flag) == value {
                 env.set_branch_taken();
                 env.subroutine_return();
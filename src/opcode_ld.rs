@@ -1,4 +1,4 @@
-use super::opcode::Opcode;
+use super::opcode::{Flow, Opcode};
 use super::environment::Environment;
 use super::registers::{Flag, Reg16, Reg8};
 
@@ -58,11 +58,17 @@ pub fn build_ld_r_r(dst: Reg8, src: Reg8, _special: bool) -> Opcode {
         Opcode::new(
             format!("LD {dst}, {src}"),
             move |env: &mut Environment| {
+                if src == Reg8::I || src == Reg8::R || dst == Reg8::I || dst == Reg8::R {
+                    env.internal_cycles(env.ir(), 1);
+                }
                 let value = env.state.reg.get8(src);
                 env.state.reg.set8(dst, value);
                 if dst == Reg8::A && (src == Reg8::I || src == Reg8::R) {
                     // LDA A,I and LDA A,R copy the IFF2 flag into the P flag
+                    env.state.reg.update_sz53_flags(value);
+                    env.state.reg.update_hn_flags(false, false);
                     env.state.reg.update_p_flag_with_iff2();
+                    env.state.ld_a_ir = true;
                 }
             }
         )
@@ -96,6 +102,10 @@ pub fn build_ld_r_n(r: Reg8) -> Opcode {
         format!("LD {r}, n"),
         move |env: &mut Environment| {
             let value = env.advance_pc();
+            if r == Reg8::_HL && env.is_alt_index() {
+                // The calculation of the address ends after reading n
+                env.internal_cycles(env.state.reg.pc().wrapping_sub(1), 2);
+            }
             env.set_reg(r, value);
         }
     )
@@ -107,8 +117,9 @@ pub fn build_ld_a_prr(rr: Reg16) -> Opcode {
         format!("LD A, ({rr:?})"),
         move |env: &mut Environment| {
             let address = env.state.reg.get16(rr);
-            let value = env.sys.peek(address);
+            let value = env.peek(address);
             env.state.reg.set_a(value);
+            env.state.wz = address.wrapping_add(1);
         }
     )
 }
@@ -118,8 +129,9 @@ pub fn build_ld_a_pnn() -> Opcode {
         "LD A, (nn)".to_string(),
         |env: &mut Environment| {
             let address = env.advance_immediate16();
-            let value = env.sys.peek(address);
+            let value = env.peek(address);
             env.state.reg.set_a(value);
+            env.state.wz = address.wrapping_add(1);
         }
     )
 }
@@ -131,7 +143,8 @@ pub fn build_ld_prr_a(rr: Reg16) -> Opcode {
         move |env: &mut Environment| {
             let value = env.state.reg.a();
             let address = env.state.reg.get16(rr);
-            env.sys.poke(address, value);
+            env.poke(address, value);
+            env.state.wz = ((value as u16) << 8) | (address.wrapping_add(1) & 0xff);
         }
     )
 }
@@ -142,7 +155,8 @@ pub fn build_ld_pnn_a() -> Opcode {
         |env: &mut Environment| {
             let value = env.state.reg.a();
             let address = env.advance_immediate16();
-            env.sys.poke(address, value);
+            env.poke(address, value);
+            env.state.wz = ((value as u16) << 8) | (address.wrapping_add(1) & 0xff);
         }
     )
 }
@@ -164,6 +178,7 @@ pub fn build_ld_sp_hl() -> Opcode {
         "LD SP, HL".to_string(),
         |env: &mut Environment| {
             let value = env.reg16_ext(Reg16::HL);
+            env.model_cycles(env.ir(), |timing| timing.inc_dec_16);
             env.set_reg16(Reg16::SP, value);
         }
     )
@@ -175,7 +190,8 @@ pub fn build_ld_pnn_rr(rr: Reg16, _fast: bool) -> Opcode {
         move |env: &mut Environment| {
             let address = env.advance_immediate16();
             let value = env.reg16_ext(rr);
-            env.sys.poke16(address, value);
+            env.poke16(address, value);
+            env.state.wz = address.wrapping_add(1);
         }
     )
 }
@@ -185,8 +201,9 @@ pub fn build_ld_rr_pnn(rr: Reg16, _fast: bool) -> Opcode {
         format!("LD {rr:?}, (nn)"),
         move |env: &mut Environment| {
             let address = env.advance_immediate16();
-            let value = env.sys.peek16(address);
+            let value = env.peek16(address);
             env.set_reg16(rr, value);
+            env.state.wz = address.wrapping_add(1);
         }
     )
 }
@@ -229,20 +246,28 @@ pub fn build_ex_psp_hl() -> Opcode {
             let address = env.state.reg.get16(Reg16::SP);
 
             let temp = env.reg16_ext(Reg16::HL);
-            let val = env.sys.peek16(address);
+            let l = env.peek(address);
+            let h = env.peek(address.wrapping_add(1));
+            let val = ((h as u16) << 8) | l as u16;
+            env.model_cycles(address.wrapping_add(1), |timing| timing.ex_sp_hl_read);
             env.set_reg16(Reg16::HL, val);
-            env.sys.poke16(address, temp);
+            // The high byte is written first
+            env.poke(address.wrapping_add(1), (temp >> 8) as u8);
+            env.poke(address, temp as u8);
+            env.model_cycles(address, |timing| timing.ex_sp_hl);
+            env.state.wz = val;
         }
     )
 }
 
 pub fn build_ld_block((inc, repeat, postfix) : (bool, bool, &'static str)) -> Opcode {
-    Opcode::new(
+    let mut opcode = Opcode::new(
         format!("LD{postfix}"),
         move |env: &mut Environment| {
             let value = env.reg8_ext(Reg8::_HL);
             let address = env.state.reg.get16(Reg16::DE);
-            env.sys.poke(address, value);
+            env.poke(address, value);
+            env.internal_cycles(address, 2);
 
             env.state.reg.inc_dec16(Reg16::DE, inc);
             env.state.reg.inc_dec16(Reg16::HL, inc);
@@ -259,9 +284,13 @@ pub fn build_ld_block((inc, repeat, postfix) : (bool, bool, &'static str)) -> Op
             if repeat && bc != 0 {
                 // Back to redo the instruction
                 env.set_branch_taken();
+                env.internal_cycles(address, 5);
                 let pc = env.state.reg.pc().wrapping_sub(2);
                 env.state.reg.set_pc(pc);
+                env.state.wz = pc.wrapping_add(1);
             }
         }
-    )
+    );
+    opcode.flow = if repeat { Flow::Loop } else { Flow::Other };
+    opcode
 }
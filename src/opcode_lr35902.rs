@@ -0,0 +1,227 @@
+use super::opcode::Opcode;
+use super::environment::Environment;
+use super::opcode_bits::{build_rot_r, ShiftDir, ShiftMode};
+use super::registers::{Flag, Reg16, Reg8};
+
+/*
+    Sharp LR35902 specific opcodes, the CPU of the Game Boy. See the Pan
+    Docs, https://gbdev.io/pandocs/CPU_Instruction_Set.html
+
+    The I/O registers are on the memory map from FF00h, the LDH opcodes
+    access them with a one byte address.
+*/
+
+// 8 bit loads
+pub fn build_ld_phl_a_lr35902(inc: bool) -> Opcode {
+    let name = if inc {"LDI"} else {"LDD"};
+    Opcode::new(
+        format!("{name} (HL), A"),
+        move |env: &mut Environment| {
+            let address = env.state.reg.get16(Reg16::HL);
+            env.poke(address, env.state.reg.a());
+            env.state.reg.inc_dec16(Reg16::HL, inc);
+        }
+    )
+}
+
+pub fn build_ld_a_phl_lr35902(inc: bool) -> Opcode {
+    let name = if inc {"LDI"} else {"LDD"};
+    Opcode::new(
+        format!("{name} A, (HL)"),
+        move |env: &mut Environment| {
+            let address = env.state.reg.get16(Reg16::HL);
+            let value = env.peek(address);
+            env.state.reg.set_a(value);
+            env.state.reg.inc_dec16(Reg16::HL, inc);
+        }
+    )
+}
+
+pub fn build_ldh_pn_a() -> Opcode {
+    Opcode::new(
+        "LDH (n), A".to_string(),
+        |env: &mut Environment| {
+            let address = 0xff00 | env.advance_pc() as u16;
+            env.poke(address, env.state.reg.a());
+        }
+    )
+}
+
+pub fn build_ldh_a_pn() -> Opcode {
+    Opcode::new(
+        "LDH A, (n)".to_string(),
+        |env: &mut Environment| {
+            let address = 0xff00 | env.advance_pc() as u16;
+            let value = env.peek(address);
+            env.state.reg.set_a(value);
+        }
+    )
+}
+
+pub fn build_ldh_pc_a() -> Opcode {
+    Opcode::new(
+        "LDH (C), A".to_string(),
+        |env: &mut Environment| {
+            let address = 0xff00 | env.state.reg.get8(Reg8::C) as u16;
+            env.poke(address, env.state.reg.a());
+        }
+    )
+}
+
+pub fn build_ldh_a_pc() -> Opcode {
+    Opcode::new(
+        "LDH A, (C)".to_string(),
+        |env: &mut Environment| {
+            let address = 0xff00 | env.state.reg.get8(Reg8::C) as u16;
+            let value = env.peek(address);
+            env.state.reg.set_a(value);
+        }
+    )
+}
+
+// 16 bit arithmetic with the stack pointer
+fn sp_plus_offset(env: &mut Environment) -> u16 {
+    let sp = env.state.reg.get16(Reg16::SP);
+    let offset = env.advance_pc() as i8 as u16;
+    let v = sp.wrapping_add(offset);
+
+    // H and C from the unsigned addition of the low byte
+    let xor = sp ^ offset ^ v;
+    env.state.reg.clear_flag(Flag::Z);
+    env.state.reg.clear_flag(Flag::N);
+    env.state.reg.put_flag(Flag::H, xor & 0x10 != 0);
+    env.state.reg.put_flag(Flag::C, xor & 0x100 != 0);
+    v
+}
+
+pub fn build_add_sp_s() -> Opcode {
+    Opcode::new(
+        "ADD SP, s".to_string(),
+        |env: &mut Environment| {
+            let v = sp_plus_offset(env);
+            env.state.reg.set16(Reg16::SP, v);
+        }
+    )
+}
+
+pub fn build_ld_hl_sp_s() -> Opcode {
+    Opcode::new(
+        "LD HL, SPs".to_string(),
+        |env: &mut Environment| {
+            let v = sp_plus_offset(env);
+            env.state.reg.set16(Reg16::HL, v);
+        }
+    )
+}
+
+// Bits and flags
+pub fn build_rot_a_lr35902(rot: (ShiftDir, ShiftMode, &str)) -> Opcode {
+    let rot = build_rot_r(Reg8::A, rot, true, false);
+    Opcode::new(
+        rot.name.clone(),
+        move |env: &mut Environment| {
+            rot.execute(env);
+            // Z is cleared, it is not changed on the Z80
+            env.state.reg.clear_flag(Flag::Z);
+        }
+    )
+}
+
+pub fn build_swap_r(r: Reg8) -> Opcode {
+    Opcode::new(
+        format!("SWAP {r}"),
+        move |env: &mut Environment| {
+            let v = env.reg8_ext(r).rotate_left(4);
+            env.set_reg(r, v);
+            env.state.reg.update_bits_in_flags(v);
+            env.state.reg.clear_flag(Flag::C);
+        }
+    )
+}
+
+pub fn build_daa_lr35902() -> Opcode {
+    Opcode::new(
+        "DAA".to_string(),
+        |env: &mut Environment| {
+            // Only valid after an addition or a substraction, N tells
+            // which one it was
+            let mut a = env.state.reg.a();
+            let nf = env.state.reg.get_flag(Flag::N);
+            let hf = env.state.reg.get_flag(Flag::H);
+            let mut cf = env.state.reg.get_flag(Flag::C);
+
+            if nf {
+                if cf {
+                    a = a.wrapping_sub(0x60);
+                }
+                if hf {
+                    a = a.wrapping_sub(0x06);
+                }
+            } else {
+                if cf || a > 0x99 {
+                    a = a.wrapping_add(0x60);
+                    cf = true;
+                }
+                if hf || (a & 0x0f) > 0x09 {
+                    a = a.wrapping_add(0x06);
+                }
+            }
+
+            env.state.reg.set_a(a);
+            env.state.reg.put_flag(Flag::Z, a == 0);
+            env.state.reg.clear_flag(Flag::H);
+            env.state.reg.put_flag(Flag::C, cf);
+        }
+    )
+}
+
+pub fn build_ccf_lr35902() -> Opcode {
+    Opcode::new(
+        "CCF".to_string(),
+        |env: &mut Environment| {
+            // H is cleared, it gets the previous carry on the Z80
+            let c = env.state.reg.get_flag(Flag::C);
+            env.state.reg.put_flag(Flag::C, !c);
+            env.state.reg.clear_flag(Flag::N);
+            env.state.reg.clear_flag(Flag::H);
+        }
+    )
+}
+
+// CPU control
+pub fn build_stop() -> Opcode {
+    Opcode::new(
+        "STOP".to_string(),
+        |env: &mut Environment| {
+            // Two bytes, the second one is skipped. It waits as HALT until
+            // an interrupt is requested on IE and IF.
+            let pc = env.state.reg.pc();
+            env.state.reg.set_pc(pc.wrapping_add(1));
+            env.state.halted = true;
+        }
+    )
+}
+
+pub fn build_reti_lr35902() -> Opcode {
+    Opcode::new(
+        "RETI".to_string(),
+        |env: &mut Environment| {
+            // IME is enabled without the delay of EI
+            env.subroutine_return();
+            env.state.reg.set_interrupts(true);
+            env.sys.reti();
+        }
+    )
+}
+
+pub fn build_illegal_lr35902(code: u8) -> Opcode {
+    Opcode::new(
+        format!("ILLEGAL {code:02X}h"),
+        |env: &mut Environment| {
+            // The CPU hangs, not even the interrupts are serviced
+            let pc = env.state.reg.pc();
+            env.state.reg.set_pc(pc.wrapping_sub(1));
+            env.state.reg.set_interrupts(false);
+        }
+    )
+}
@@ -0,0 +1,68 @@
+use super::opcode::Opcode;
+use super::environment::Environment;
+use super::registers::{Flag, Reg16, Reg8};
+
+/*
+    R800 specific opcodes and behaviour, the CPU of the MSX turboR. See
+    the R800 Technical Data Book by ASCII and the openMSX R800 core.
+
+    The R800 has no Q register, SCF and CCF do not change the
+    undocumented flags 3 and 5.
+*/
+
+// Multiply
+fn update_multiply_flags(env: &mut Environment, zero: bool, carry: bool) {
+    // S and P/V are reset, N, H and the undocumented flags are kept
+    env.state.reg.clear_flag(Flag::S);
+    env.state.reg.put_flag(Flag::Z, zero);
+    env.state.reg.clear_flag(Flag::P);
+    env.state.reg.put_flag(Flag::C, carry);
+}
+
+pub fn build_mulub_a_r(r: Reg8) -> Opcode {
+    Opcode::new(
+        format!("MULUB A, {r}"),
+        move |env: &mut Environment| {
+            // HL = A * r, C is set when the result does not fit on 8 bits
+            let v = env.state.reg.a() as u16 * env.state.reg.get8(r) as u16;
+            env.state.reg.set16(Reg16::HL, v);
+            update_multiply_flags(env, v == 0, v > 0xff);
+        }
+    )
+}
+
+pub fn build_muluw_hl_rr(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("MULUW HL, {rr:?}"),
+        move |env: &mut Environment| {
+            // DE:HL = HL * rr, C is set when the result does not fit on
+            // 16 bits
+            let v = env.state.reg.get16(Reg16::HL) as u32 * env.state.reg.get16(rr) as u32;
+            env.state.reg.set16(Reg16::DE, (v >> 16) as u16);
+            env.state.reg.set16(Reg16::HL, v as u16);
+            update_multiply_flags(env, v == 0, v > 0xffff);
+        }
+    )
+}
+
+// Flags
+pub fn build_scf_r800() -> Opcode {
+    Opcode::new(
+        "SCF".to_string(),
+        |env: &mut Environment| {
+            env.state.reg.set_flag(Flag::C);
+            env.state.reg.update_hn_flags(false, false);
+        }
+    )
+}
+
+pub fn build_ccf_r800() -> Opcode {
+    Opcode::new(
+        "CCF".to_string(),
+        |env: &mut Environment| {
+            let c = env.state.reg.get_flag(Flag::C);
+            env.state.reg.put_flag(Flag::C, !c);
+            env.state.reg.update_hn_flags(c, false);
+        }
+    )
+}
@@ -0,0 +1,165 @@
+use super::opcode::{Flow, Opcode};
+use super::environment::Environment;
+use super::registers::{Flag, Reg16, Reg8};
+
+/*
+    Z180 specific opcodes, all of them on the ED prefix. See the Z8018x
+    Family MPU User Manual, UM0050.
+
+    The I/O opcodes use ports with the high byte as 0, where the internal
+    I/O registers are.
+*/
+
+// Multiply and test
+pub fn build_mlt(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("MLT {rr:?}"),
+        move |env: &mut Environment| {
+            // rr = high * low, no flags affected
+            let [l, h] = env.state.reg.get16(rr).to_le_bytes();
+            env.state.reg.set16(rr, h as u16 * l as u16);
+        }
+    )
+}
+
+fn test(env: &mut Environment, b: u8) {
+    // AND without storing the result
+    let a = env.state.reg.a();
+    env.state.reg.update_logic_flags(a, b, a & b, true);
+}
+
+pub fn build_tst_r(r: Reg8) -> Opcode {
+    Opcode::new(
+        format!("TST {r}"),
+        move |env: &mut Environment| {
+            let b = env.reg8_ext(r);
+            test(env, b);
+        }
+    )
+}
+
+pub fn build_tst_n() -> Opcode {
+    Opcode::new(
+        "TST n".to_string(),
+        |env: &mut Environment| {
+            let b = env.advance_pc();
+            test(env, b);
+        }
+    )
+}
+
+pub fn build_tstio_n() -> Opcode {
+    Opcode::new(
+        "TSTIO n".to_string(),
+        |env: &mut Environment| {
+            // (C) AND n
+            let n = env.advance_pc();
+            let address = env.state.reg.get8(Reg8::C) as u16;
+            let value = env.port_in(address);
+            env.state.reg.update_logic_flags(value, n, value & n, true);
+        }
+    )
+}
+
+// Input and output on the page 0
+pub fn build_in0_r_n(r: Reg8) -> Opcode {
+    Opcode::new(
+        format!("IN0 {r}, (n)"),
+        move |env: &mut Environment| {
+            let address = env.advance_pc() as u16;
+            let value = env.port_in(address);
+            env.state.reg.set8(r, value);
+            env.state.reg.update_bits_in_flags(value);
+        }
+    )
+}
+
+pub fn build_in0_n() -> Opcode {
+    Opcode::new(
+        "IN0 (n)".to_string(),
+        |env: &mut Environment| {
+            // Only the flags are updated
+            let address = env.advance_pc() as u16;
+            let value = env.port_in(address);
+            env.state.reg.update_bits_in_flags(value);
+        }
+    )
+}
+
+pub fn build_out0_n_r(r: Reg8) -> Opcode {
+    Opcode::new(
+        format!("OUT0 (n), {r}"),
+        move |env: &mut Environment| {
+            let address = env.advance_pc() as u16;
+            let value = env.state.reg.get8(r);
+            env.port_out(address, value);
+        }
+    )
+}
+
+pub fn build_otm_block((inc, repeat, _) : (bool, bool, &'static str)) -> Opcode {
+    let direction = if inc {"I"} else {"D"};
+    let postfix = if repeat {"R"} else {""};
+    let mut opcode = Opcode::new(
+        format!("OT{direction}M{postfix}"),
+        move |env: &mut Environment| {
+            // (C) = (HL), HL and C are incremented or decremented, B is
+            // decremented
+            let value = env.reg8_ext(Reg8::_HL);
+            let address = env.state.reg.get8(Reg8::C) as u16;
+            env.port_out(address, value);
+            env.state.reg.inc_dec16(Reg16::HL, inc);
+            env.state.reg.inc_dec8(Reg8::C, inc);
+
+            // The flags are set as on DEC B, but N is bit 7 of the data
+            let b = env.state.reg.get8(Reg8::B);
+            let v = b.wrapping_sub(1);
+            env.state.reg.set8(Reg8::B, v);
+            env.state.reg.update_sz53_flags(v);
+            env.state.reg.update_p_flag(v);
+            env.state.reg.put_flag(Flag::H, b & 0x0f == 0);
+            env.state.reg.put_flag(Flag::N, value & 0x80 != 0);
+            env.state.reg.put_flag(Flag::C, b == 0);
+
+            if repeat && v != 0 {
+                // Back to redo the instruction
+                env.set_branch_taken();
+                let pc = env.state.reg.pc().wrapping_sub(2);
+                env.state.reg.set_pc(pc);
+            }
+        }
+    );
+    opcode.flow = if repeat { Flow::Loop } else { Flow::Other };
+    opcode
+}
+
+pub fn build_slp() -> Opcode {
+    Opcode::new(
+        "SLP".to_string(),
+        |env: &mut Environment| {
+            // Sleep until an interrupt, as HALT
+            env.state.halted = true;
+        }
+    )
+}
+
+// Undefined opcodes
+pub fn build_trap(third_byte: bool) -> Opcode {
+    Opcode::new(
+        "TRAP".to_string(),
+        move |env: &mut Environment| {
+            /*
+            The PC pushed points after the first byte of the instruction,
+            or after the second one when the undefined opcode is the third
+            byte, as on DD CB d xx. The handler checks UFO to find the
+            start of the instruction.
+            */
+            if let Some(z180) = &mut env.state.z180 {
+                z180.trap(third_byte);
+            }
+            let pc = env.state.reg.pc().wrapping_sub(if third_byte {2} else {1});
+            env.state.reg.set_pc(pc);
+            env.subroutine_call(0x0000);
+        }
+    )
+}
@@ -0,0 +1,296 @@
+use super::opcode::{Flow, Opcode};
+use super::environment::Environment;
+use super::registers::{Reg16, Reg8};
+
+/*
+    Z80N specific opcodes of the ZX Spectrum Next, all of them on the ED
+    prefix. See https://wiki.specnext.dev/Extended_Z80_instruction_set
+
+    Unless noted, the flags are not affected.
+*/
+
+// Block copies
+fn copy_unless_a(env: &mut Environment, value: u8) {
+    // The byte is not written when it is equal to A, as a transparent
+    // color
+    let address = env.state.reg.get16(Reg16::DE);
+    if value != env.state.reg.a() {
+        env.poke(address, value);
+    }
+    env.internal_cycles(address, 2);
+}
+
+fn repeat_while_bc(env: &mut Environment, bc: u16) {
+    if bc != 0 {
+        // Back to redo the instruction
+        env.set_branch_taken();
+        env.internal_cycles(env.state.reg.get16(Reg16::DE), 5);
+        let pc = env.state.reg.pc().wrapping_sub(2);
+        env.state.reg.set_pc(pc);
+    }
+}
+
+pub fn build_ldx_block((inc, repeat, postfix) : (bool, bool, &'static str)) -> Opcode {
+    let mut opcode = Opcode::new(
+        format!("LD{postfix}X"),
+        move |env: &mut Environment| {
+            // As LDI and LDD, DE is always incremented
+            let value = env.reg8_ext(Reg8::_HL);
+            copy_unless_a(env, value);
+            env.state.reg.inc_dec16(Reg16::DE, true);
+            env.state.reg.inc_dec16(Reg16::HL, inc);
+            let bc = env.state.reg.inc_dec16(Reg16::BC, false /*decrement*/);
+
+            if repeat {
+                repeat_while_bc(env, bc);
+            }
+        }
+    );
+    opcode.flow = if repeat { Flow::Loop } else { Flow::Other };
+    opcode
+}
+
+pub fn build_ldpirx() -> Opcode {
+    let mut opcode = Opcode::new(
+        "LDPIRX".to_string(),
+        |env: &mut Environment| {
+            // The source is an 8 byte pattern aligned on HL, indexed by
+            // the low bits of E. HL is not changed.
+            let hl = env.state.reg.get16(Reg16::HL);
+            let e = env.state.reg.get8(Reg8::E);
+            let value = env.peek((hl & 0xfff8) | (e & 0x07) as u16);
+            copy_unless_a(env, value);
+            env.state.reg.inc_dec16(Reg16::DE, true);
+            let bc = env.state.reg.inc_dec16(Reg16::BC, false /*decrement*/);
+            repeat_while_bc(env, bc);
+        }
+    );
+    opcode.flow = Flow::Loop;
+    opcode
+}
+
+pub fn build_ldws() -> Opcode {
+    Opcode::new(
+        "LDWS".to_string(),
+        |env: &mut Environment| {
+            // (DE) = (HL), L and D are incremented, the flags are set as
+            // on INC D
+            let value = env.reg8_ext(Reg8::_HL);
+            let address = env.state.reg.get16(Reg16::DE);
+            env.poke(address, value);
+            env.state.reg.inc_dec8(Reg8::L, true);
+            let d = env.state.reg.get8(Reg8::D);
+            let v = (d as u16) + 1;
+            env.state.reg.update_arithmetic_flags(d as u16, 0, v, false, false);
+            env.state.reg.set8(Reg8::D, v as u8);
+        }
+    )
+}
+
+pub fn build_outinb() -> Opcode {
+    Opcode::new(
+        "OUTINB".to_string(),
+        |env: &mut Environment| {
+            // As OUTI without the decrement of B
+            env.internal_cycles(env.ir(), 1);
+            let address = env.state.reg.get16(Reg16::BC);
+            let value = env.reg8_ext(Reg8::_HL);
+            env.port_out(address, value);
+            env.state.reg.inc_dec16(Reg16::HL, true);
+        }
+    )
+}
+
+// Arithmetic
+pub fn build_mul_d_e() -> Opcode {
+    Opcode::new(
+        "MUL D, E".to_string(),
+        |env: &mut Environment| {
+            let d = env.state.reg.get8(Reg8::D) as u16;
+            let e = env.state.reg.get8(Reg8::E) as u16;
+            env.state.reg.set16(Reg16::DE, d * e);
+        }
+    )
+}
+
+pub fn build_add_rr_a(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("ADD {rr:?}, A"),
+        move |env: &mut Environment| {
+            // A is unsigned
+            let v = env.state.reg.get16(rr).wrapping_add(env.state.reg.a() as u16);
+            env.state.reg.set16(rr, v);
+        }
+    )
+}
+
+pub fn build_add_rr_nn(rr: Reg16) -> Opcode {
+    Opcode::new(
+        format!("ADD {rr:?}, nn"),
+        move |env: &mut Environment| {
+            let nn = env.advance_immediate16();
+            let v = env.state.reg.get16(rr).wrapping_add(nn);
+            env.state.reg.set16(rr, v);
+        }
+    )
+}
+
+pub fn build_test_n() -> Opcode {
+    Opcode::new(
+        "TEST n".to_string(),
+        |env: &mut Environment| {
+            // AND without storing the result, the flags are updated
+            let a = env.state.reg.a();
+            let b = env.advance_pc();
+            env.state.reg.update_logic_flags(a, b, a & b, true);
+        }
+    )
+}
+
+// Bit manipulation
+pub fn build_swapnib() -> Opcode {
+    Opcode::new(
+        "SWAPNIB".to_string(),
+        |env: &mut Environment| {
+            let a = env.state.reg.a();
+            env.state.reg.set_a(a.rotate_left(4));
+        }
+    )
+}
+
+pub fn build_mirror_a() -> Opcode {
+    Opcode::new(
+        "MIRROR A".to_string(),
+        |env: &mut Environment| {
+            let a = env.state.reg.a();
+            env.state.reg.set_a(a.reverse_bits());
+        }
+    )
+}
+
+pub fn build_barrel_shift(name: &'static str, shift: fn(u16, u32) -> u16) -> Opcode {
+    Opcode::new(
+        format!("{name} DE, B"),
+        move |env: &mut Environment| {
+            // Only the low 5 bits of B are used, 4 for the rotation
+            let de = env.state.reg.get16(Reg16::DE);
+            let b = (env.state.reg.get8(Reg8::B) & 0x1f) as u32;
+            env.state.reg.set16(Reg16::DE, shift(de, b));
+        }
+    )
+}
+
+// Barrel shifters, the shifts of 16 or more bits empty DE
+pub fn shift_bsla(de: u16, b: u32) -> u16 {
+    de.checked_shl(b).unwrap_or(0)
+}
+
+pub fn shift_bsra(de: u16, b: u32) -> u16 {
+    ((de as i16) >> b.min(15)) as u16
+}
+
+pub fn shift_bsrl(de: u16, b: u32) -> u16 {
+    de.checked_shr(b).unwrap_or(0)
+}
+
+pub fn shift_bsrf(de: u16, b: u32) -> u16 {
+    // Filled with ones
+    !shift_bsrl(!de, b)
+}
+
+pub fn shift_brlc(de: u16, b: u32) -> u16 {
+    de.rotate_left(b & 0x0f)
+}
+
+// Graphics
+pub fn build_pixeldn() -> Opcode {
+    Opcode::new(
+        "PIXELDN".to_string(),
+        |env: &mut Environment| {
+            // HL moves one pixel row down on the ULA screen
+            let hl = env.state.reg.get16(Reg16::HL);
+            let v = if hl & 0x0700 != 0x0700 {
+                hl.wrapping_add(0x0100)
+            } else if hl & 0x00e0 != 0x00e0 {
+                (hl & 0xf8ff).wrapping_add(0x0020)
+            } else {
+                (hl & 0xf81f).wrapping_add(0x0800)
+            };
+            env.state.reg.set16(Reg16::HL, v);
+        }
+    )
+}
+
+pub fn build_pixelad() -> Opcode {
+    Opcode::new(
+        "PIXELAD".to_string(),
+        |env: &mut Environment| {
+            // HL is the ULA screen address of the pixel at x=E, y=D
+            let d = env.state.reg.get8(Reg8::D) as u16;
+            let e = env.state.reg.get8(Reg8::E) as u16;
+            let v = 0x4000 + ((d & 0xc0) << 5) + ((d & 0x07) << 8)
+                + ((d & 0x38) << 2) + (e >> 3);
+            env.state.reg.set16(Reg16::HL, v);
+        }
+    )
+}
+
+pub fn build_setae() -> Opcode {
+    Opcode::new(
+        "SETAE".to_string(),
+        |env: &mut Environment| {
+            // The mask of the pixel x=E in the screen byte
+            let e = env.state.reg.get8(Reg8::E);
+            env.state.reg.set_a(0x80 >> (e & 0x07));
+        }
+    )
+}
+
+// Stack, registers and jumps
+pub fn build_push_nn() -> Opcode {
+    Opcode::new(
+        "PUSH mm".to_string(),
+        |env: &mut Environment| {
+            // The immediate value is big endian
+            let h = env.advance_pc();
+            let l = env.advance_pc();
+            env.push(((h as u16) << 8) | l as u16);
+        }
+    )
+}
+
+pub fn build_nextreg_n_n() -> Opcode {
+    Opcode::new(
+        "NEXTREG n, n".to_string(),
+        |env: &mut Environment| {
+            let register = env.advance_pc();
+            let value = env.advance_pc();
+            env.sys.nextreg(register, value);
+        }
+    )
+}
+
+pub fn build_nextreg_n_a() -> Opcode {
+    Opcode::new(
+        "NEXTREG n, A".to_string(),
+        |env: &mut Environment| {
+            let register = env.advance_pc();
+            let value = env.state.reg.a();
+            env.sys.nextreg(register, value);
+        }
+    )
+}
+
+pub fn build_jp_c() -> Opcode {
+    Opcode::new(
+        "JP (C)".to_string(),
+        |env: &mut Environment| {
+            // Jump within the current 16K block to the port value times 64
+            let address = env.state.reg.get16(Reg16::BC);
+            let value = env.port_in(address);
+            let pc = (env.state.reg.pc() & 0xc000) | ((value as u16) << 6);
+            env.state.reg.set_pc(pc);
+            env.state.wz = pc;
+        }
+    )
+}
@@ -63,7 +63,10 @@ pub enum Reg16 {
 }
 
 /// Z80 flags
-#[derive(Copy, Clone, Debug)]
+///
+/// On the LR35902 only Z, N, H and C exist, on the bits 7 to 4 of F.
+/// The other flags read as false and are not updated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Flag {
     /// Carry flag
@@ -85,6 +88,14 @@ pub enum Flag {
     S  = 128
 }
 
+impl Flag {
+    /// Overflow flag of the 8085, undocumented. Same bit as N
+    pub const V: Flag = Flag::N;
+    /// Signed underflow flag of the 8085, undocumented, also known as
+    /// X5 or UI. Same bit as the fifth flag
+    pub const K: Flag = Flag::_5;
+}
+
 impl fmt::Display for Reg8 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -95,7 +106,7 @@ impl fmt::Display for Reg8 {
 }
 
 /// Z80 internal register values
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Registers {
     data: [u8; REG_COUNT8],
     shadow: [u8; REG_COUNT8],
@@ -103,7 +114,33 @@ pub struct Registers {
     iff1: bool,
     iff2: bool,
     im: u8,
-    mode8080: bool
+    mode8080: bool,
+    mode8085: bool,
+    mode_lr35902: bool,
+    flags_modified: bool,
+    // eZ80 upper bytes of BC, DE, HL, IX and IY, and of BC', DE' and HL'
+    mode_ez80: bool,
+    upper: [u8; UPPER_COUNT],
+    shadow_upper: [u8; UPPER_COUNT],
+    spl: u32,
+    pcu: u8,
+    mbase: u8,
+    adl: bool,
+    madl: bool,
+}
+
+const UPPER_COUNT: usize = 5;
+
+// AF has no upper byte, it stays on 16 bits on ADL mode
+fn upper_index(rr: Reg16) -> Option<usize> {
+    match rr {
+        Reg16::BC => Some(0),
+        Reg16::DE => Some(1),
+        Reg16::HL => Some(2),
+        Reg16::IX => Some(3),
+        Reg16::IY => Some(4),
+        _ => None,
+    }
 }
 
 impl Registers {
@@ -116,7 +153,18 @@ impl Registers {
             iff1: false,
             iff2: false,
             im: 0,
-            mode8080: false
+            mode8080: false,
+            mode8085: false,
+            mode_lr35902: false,
+            flags_modified: false,
+            mode_ez80: false,
+            upper: [0; UPPER_COUNT],
+            shadow_upper: [0; UPPER_COUNT],
+            spl: 0xffffff,
+            pcu: 0,
+            mbase: 0,
+            adl: false,
+            madl: false,
         };
 
         reg.reset();
@@ -131,6 +179,11 @@ impl Registers {
         self.set16(Reg16::AF, 0xffff);
         self.set16(Reg16::SP, 0xffff);
         self.set_pc(0x0000);
+        self.spl = 0xffffff;
+        self.pcu = 0;
+        self.mbase = 0;
+        self.adl = false;
+        self.madl = false;
     }
 
     pub(crate) fn set_8080(&mut self) {
@@ -140,6 +193,37 @@ impl Registers {
         self.set_flag(Flag::N);
     }
 
+    pub(crate) fn is_8080(&self) -> bool {
+        self.mode8080
+    }
+
+    pub(crate) fn set_8085(&mut self) {
+        self.set_8080();
+        self.mode8085 = true;
+        self.set16(Reg16::AF, 0xffff);
+    }
+
+    pub(crate) fn is_8085(&self) -> bool {
+        self.mode8085
+    }
+
+    pub(crate) fn set_lr35902(&mut self) {
+        self.mode_lr35902 = true;
+        self.set16(Reg16::AF, 0xffff);
+    }
+
+    pub(crate) fn is_lr35902(&self) -> bool {
+        self.mode_lr35902
+    }
+
+    pub(crate) fn set_ez80(&mut self) {
+        self.mode_ez80 = true;
+    }
+
+    pub(crate) fn is_ez80(&self) -> bool {
+        self.mode_ez80
+    }
+
     /// Returns the value of the A register
     #[inline]
     pub fn a(&self) -> u8 {
@@ -177,6 +261,13 @@ impl Registers {
         v
     }
 
+    pub(crate) fn increment_r(&mut self) {
+        // The memory refresh counter increments on each M1 cycle. Only
+        // the lower 7 bits are incremented, bit 7 is preserved.
+        let r = self.data[Reg8::R as usize];
+        self.data[Reg8::R as usize] = (r & 0x80) | (r.wrapping_add(1) & 0x7f);
+    }
+
     /// Returns the value of a 16 bit register
     #[inline]
     pub fn get16(&self, rr: Reg16) -> u16 {
@@ -190,12 +281,17 @@ impl Registers {
     pub fn set16(&mut self, rr: Reg16, value: u16) {
         self.data[rr as usize +1] = value as u8;
         self.data[rr as usize] = (value >> 8) as u8;
-        //if self.mode8080 && rr == Reg16::AF {
-        if self.mode8080 && rr == Reg16::AF {
+        if self.mode8085 && rr == Reg16::AF {
+            // V and K exist on the 8085, only bit 3 is always zero
+            self.clear_flag(Flag::_3);
+        } else if self.mode8080 && rr == Reg16::AF {
             // Ensure non existent flags have proper values
             self.set_flag(Flag::N);
             self.clear_flag(Flag::_3);
             self.clear_flag(Flag::_5);
+        } else if self.mode_lr35902 && rr == Reg16::AF {
+            // The low nibble of F is always zero
+            self.data[Reg8::F as usize] &= 0xf0;
         }
     }
 
@@ -216,24 +312,87 @@ impl Registers {
 
         let il = rr as usize + 1;
         mem::swap(&mut self.data[il], &mut self.shadow[il]);
+
+        if let Some(iu) = upper_index(rr) {
+            mem::swap(&mut self.upper[iu], &mut self.shadow_upper[iu]);
+        }
+    }
+
+    /// Returns the value of a 16 bit register of the alternate set: AF',
+    /// BC', DE' or HL'
+    pub fn get16_shadow(&self, rr: Reg16) -> u16 {
+        self.shadow[rr as usize +1] as u16
+        + ((self.shadow[rr as usize] as u16) << 8)
+    }
+
+    /// Sets the value of a 16 bit register of the alternate set: AF',
+    /// BC', DE' or HL'
+    pub fn set16_shadow(&mut self, rr: Reg16, value: u16) {
+        self.shadow[rr as usize +1] = value as u8;
+        self.shadow[rr as usize] = (value >> 8) as u8;
+    }
+
+    /// Returns the 24 bit value of a register of the eZ80, with the
+    /// upper byte. SP is SPL, the stack pointer of the ADL mode. AF has
+    /// no upper byte, its 16 bit value is returned.
+    pub fn get24(&self, rr: Reg16) -> u32 {
+        if rr == Reg16::SP {
+            self.spl
+        } else if let Some(iu) = upper_index(rr) {
+            ((self.upper[iu] as u32) << 16) | self.get16(rr) as u32
+        } else {
+            self.get16(rr) as u32
+        }
+    }
+
+    /// Sets the 24 bit value of a register of the eZ80, with the upper
+    /// byte. SP is SPL, the stack pointer of the ADL mode. AF has no
+    /// upper byte, only the low 16 bits are set.
+    pub fn set24(&mut self, rr: Reg16, value: u32) {
+        if rr == Reg16::SP {
+            self.spl = value & 0xffffff;
+        } else {
+            if let Some(iu) = upper_index(rr) {
+                self.upper[iu] = (value >> 16) as u8;
+            }
+            self.set16(rr, value as u16);
+        }
+    }
+
+    #[inline]
+    fn flag_mask(&self, flag: Flag) -> u8 {
+        if self.mode_lr35902 {
+            // Z N H C on the high nibble, no S, P/V or undocumented flags
+            match flag {
+                Flag::Z => 0x80,
+                Flag::N => 0x40,
+                Flag::H => 0x20,
+                Flag::C => 0x10,
+                _ => 0,
+            }
+        } else {
+            flag as u8
+        }
     }
 
     /// Returns the value of a flag
     #[inline]
     pub fn get_flag(&self, flag: Flag) -> bool {
-        self.get8(Reg8::F) & flag as u8 != 0
+        self.get8(Reg8::F) & self.flag_mask(flag) != 0
     }
 
     /// Sets a flag. Sets the value to true
     #[inline]
     pub fn set_flag(&mut self, flag: Flag) {
-        self.data[Reg8::F as usize] |= flag as u8;
+        self.data[Reg8::F as usize] |= self.flag_mask(flag);
+        self.flags_modified = true;
     }
 
     /// Clears a flag. Sets the value to false
     #[inline]
     pub fn clear_flag(&mut self, flag: Flag) {
-        self.data[Reg8::F as usize] &= !(flag as u8);
+        self.data[Reg8::F as usize] &= !self.flag_mask(flag);
+        self.flags_modified = true;
     }
 
     /// Sets the value of a flag
@@ -246,6 +405,20 @@ impl Registers {
         }
     }
 
+    pub(crate) fn clear_flags_modified(&mut self) {
+        self.flags_modified = false;
+    }
+
+    pub(crate) fn q(&self) -> u8 {
+        // Internal Q register. It has the flags if the last instruction
+        // modified them, zero otherwise. POP AF and EX AF, AF' don't count.
+        if self.flags_modified {
+            self.get8(Reg8::F)
+        } else {
+            0
+        }
+    }
+
     pub(crate) fn update_hn_flags(&mut self, hf: bool, nf: bool) {
         if !self.mode8080 {
             self.put_flag(Flag::H, hf);
@@ -256,7 +429,7 @@ impl Registers {
 
     pub(crate) fn update_p_flag(&mut self, reference: u8) {
         let bits = reference.count_ones();
-        self.put_flag(Flag::P, bits % 2 == 0);
+        self.put_flag(Flag::P, bits & 1 == 0);
     }
 
     pub(crate) fn update_sz53_flags(&mut self, reference: u8) {
@@ -324,6 +497,19 @@ impl Registers {
                 let neg_half_bit = (!a_b3 && !b_b3 && !r_b3) || (a_b3 && !(b_b3 && r_b3));
                 self.put_flag(Flag::H, neg_half_bit);
             }
+            if self.mode8085 {
+                let top_xor = (xor & 0x80) != 0;
+                self.put_flag(Flag::V, carry_bit != top_xor);
+                // See "Unspecified 8085 op codes enhance programming",
+                // Dehnhardt and Sorensen. The sign of b is complemented
+                // on the substractions.
+                let s1 = (a & 0x80) != 0;
+                let s2 = ((b & 0x80) != 0) != neg;
+                let r = (reference & 0x80) != 0;
+                #[allow(clippy::nonminimal_bool)]
+                let k = (s1 && s2) || (s1 && !r) || (s2 && !r);
+                self.put_flag(Flag::K, k);
+            }
         } else {
             let top_xor = (xor & 0x80) != 0;
             self.put_flag(Flag::P, carry_bit != top_xor); // As overflow flag
@@ -336,7 +522,11 @@ impl Registers {
         self.update_p_flag(reference);
         self.clear_flag(Flag::C);
 
-        if self.mode8080 {
+        if self.mode8085 {
+            self.put_flag(Flag::H, is_and);
+            self.clear_flag(Flag::V);
+            self.clear_flag(Flag::K);
+        } else if self.mode8080 {
             self.put_flag(Flag::H, is_and && (((a | b) & 0x08) != 0));
         } else {
             self.clear_flag(Flag::N);
@@ -381,6 +571,55 @@ impl Registers {
         self.pc = value;
     }
 
+    pub(crate) fn inc_pc(&mut self) {
+        self.pc = self.pc.wrapping_add(1);
+        if self.adl && self.pc == 0 {
+            // 24 bit program counter in ADL mode
+            self.pcu = self.pcu.wrapping_add(1);
+        }
+    }
+
+    /// Returns the 24 bit address of the program counter of the eZ80.
+    /// The upper byte is PCU in ADL mode and MBASE in Z80 mode.
+    pub fn pc24(&self) -> u32 {
+        let upper = if self.adl { self.pcu } else { self.mbase };
+        ((upper as u32) << 16) | self.pc as u32
+    }
+
+    /// Changes the 24 bit program counter of the eZ80. The upper byte is
+    /// only used in ADL mode.
+    pub fn set_pc24(&mut self, value: u32) {
+        self.pc = value as u16;
+        self.pcu = (value >> 16) as u8;
+    }
+
+    /// Returns MBASE, the upper byte of the addresses of the eZ80 in
+    /// Z80 mode
+    pub fn mbase(&self) -> u8 {
+        self.mbase
+    }
+
+    /// Sets MBASE, the upper byte of the addresses of the eZ80 in Z80
+    /// mode
+    pub fn set_mbase(&mut self, value: u8) {
+        self.mbase = value;
+    }
+
+    /// Returns true if the eZ80 is on ADL mode, with 24 bit registers
+    /// and addresses
+    pub fn is_adl(&self) -> bool {
+        self.adl
+    }
+
+    /// Sets the ADL mode of the eZ80
+    pub fn set_adl(&mut self, adl: bool) {
+        self.adl = adl;
+    }
+
+    pub(crate) fn set_madl(&mut self, madl: bool) {
+        self.madl = madl;
+    }
+
     pub(crate) fn set_interrupts(&mut self, v: bool) {
         self.iff1 = v;
         self.iff2 = v;
@@ -394,6 +633,10 @@ impl Registers {
         (self.iff1, self.im)
     }
 
+    pub(crate) fn iff2(&self) -> bool {
+        self.iff2
+    }
+
     pub(crate) fn start_nmi(&mut self) {
         self.iff2 = self.iff1;
         self.iff1 = false;
@@ -443,6 +686,36 @@ impl Registers {
         self.im = data[i+4];
         Ok(())
     }
+
+    pub(crate) const SERIALIZE_SIZE_EZ80: usize = UPPER_COUNT + UPPER_COUNT + 3 + 4;
+
+    pub(crate) fn serialize_ez80(&self) -> [u8; Registers::SERIALIZE_SIZE_EZ80] {
+        let mut data = [0; Registers::SERIALIZE_SIZE_EZ80];
+        data[0..UPPER_COUNT].copy_from_slice(&self.upper);
+        data[UPPER_COUNT..2*UPPER_COUNT].copy_from_slice(&self.shadow_upper);
+        let i = 2*UPPER_COUNT;
+        data[i..i+3].copy_from_slice(&self.spl.to_le_bytes()[0..3]);
+        data[i+3] = self.pcu;
+        data[i+4] = self.mbase;
+        data[i+5] = self.adl as u8;
+        data[i+6] = self.madl as u8;
+        data
+    }
+
+    pub(crate) fn deserialize_ez80(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.len() < Registers::SERIALIZE_SIZE_EZ80 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Data too short"));
+        }
+        self.upper.copy_from_slice(&data[0..UPPER_COUNT]);
+        self.shadow_upper.copy_from_slice(&data[UPPER_COUNT..2*UPPER_COUNT]);
+        let i = 2*UPPER_COUNT;
+        self.spl = u32::from_le_bytes([data[i], data[i+1], data[i+2], 0]);
+        self.pcu = data[i+3];
+        self.mbase = data[i+4];
+        self.adl = data[i+5] != 0;
+        self.madl = data[i+6] != 0;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,57 @@
 use std::io;
 
+use super::cpu::CpuModel;
 use super::registers::{Reg16, Registers};
+use super::tracer::Access;
+use super::z180::Z180;
+
+/// Max size of the instruction placed on the bus for IM 0
+pub const INT_DATA_SIZE: usize = 4;
+
+/// Suffix of an eZ80 instruction, it selects the width of the data and
+/// of the immediate values for the instruction only
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Suffix {
+    /// .SIS, 16 bit data and 16 bit immediate
+    Sis,
+    /// .LIS, 24 bit data and 16 bit immediate
+    Lis,
+    /// .SIL, 16 bit data and 24 bit immediate
+    Sil,
+    /// .LIL, 24 bit data and 24 bit immediate
+    Lil,
+}
+
+impl Suffix {
+    pub(crate) fn from_code(code: u8) -> Option<Suffix> {
+        match code {
+            0x40 => Some(Suffix::Sis),
+            0x49 => Some(Suffix::Lis),
+            0x52 => Some(Suffix::Sil),
+            0x5b => Some(Suffix::Lil),
+            _ => None,
+        }
+    }
+
+    /// The data and the stack are on 24 bits
+    pub fn long(self) -> bool {
+        matches!(self, Suffix::Lis | Suffix::Lil)
+    }
+
+    /// The immediate values are on 24 bits
+    pub fn long_immediate(self) -> bool {
+        matches!(self, Suffix::Sil | Suffix::Lil)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Suffix::Sis => ".SIS",
+            Suffix::Lis => ".LIS",
+            Suffix::Sil => ".SIL",
+            Suffix::Lil => ".LIL",
+        }
+    }
+}
 
 /// Internal state of the CPU
 /// 
@@ -11,17 +62,49 @@ pub struct State {
     pub reg: Registers,
     /// Cycle counter
     pub cycle: u64,
+    /// Hidden register WZ, also known as MEMPTR
+    pub wz: u16,
+    /// Hidden register Q, flags set by the previous instruction
+    pub q: u8,
+    /// Variant of the Z80 emulated
+    pub model: CpuModel,
     pub branch_taken: bool,
     /// Halt state of the CPU
     pub halted: bool,
     /// Maskable interrupt signaled
     pub int_signaled: bool,
+    /// Bytes placed on the data bus by the interrupting device
+    pub int_data: [u8; INT_DATA_SIZE],
+    /// Position on int_data while fetching an instruction from the bus
+    pub int_data_index: Option<usize>,
     /// Non maskable interrupt signaled
     pub nmi_pending: bool,
     /// Reset signaled
     pub reset_pending: bool,
     /// Interrupts just enabled
     pub int_just_enabled: bool,
+    /// LD A, I or LD A, R just executed, P/V has IFF2
+    pub ld_a_ir: bool,
+    /// 8085 RST 5.5, 6.5 and 7.5 masks set with SIM, bits 0 to 2
+    pub rst_mask: u8,
+    /// 8085 RST 5.5 and 6.5 inputs and RST 7.5 latch, bits 0 to 2
+    pub rst_pending: u8,
+    /// Z180 internal I/O registers and peripherals
+    pub z180: Option<Z180>,
+    /// R800 DRAM page of the last memory access, the high byte of the
+    /// address. Not serialized, the next access is a page break.
+    pub memory_page: Option<u8>,
+    /// eZ80 suffix of the instruction being executed
+    pub suffix: Option<Suffix>,
+    /// First access hit by a watchpoint since the last `Cpu::run()` step.
+    /// Not serialized.
+    pub watch_hit: Option<Access>,
+    /// Subroutine calls minus returns, the interrupts count as calls.
+    /// Used to step over and out. Not serialized.
+    pub call_depth: i64,
+    /// call_depth when the last opcode was fetched, deeper than before
+    /// if an interrupt was taken. Not serialized.
+    pub opcode_depth: i64,
     // Alternate index management
     pub index: Reg16, // Using HL, IX or IY
     pub displacement: i8, // Used for (IX+d) and (iY+d)
@@ -33,18 +116,55 @@ impl State {
         State {
             reg: Registers::new(),
             cycle: 0,
+            wz: 0,
+            q: 0,
+            model: CpuModel::ZilogNmos,
             branch_taken: false,
             halted: false,
             int_signaled: false,
+            int_data: [0xff; INT_DATA_SIZE],
+            int_data_index: None,
             nmi_pending: false,
             reset_pending: false,
             int_just_enabled: false,
+            ld_a_ir: false,
+            rst_mask: 0x07,
+            rst_pending: 0,
+            z180: None,
+            memory_page: None,
+            suffix: None,
+            watch_hit: None,
+            call_depth: 0,
+            opcode_depth: 0,
             index: Reg16::HL,
             displacement: 0,
         }
     }
 
-    pub const SERIALIZE_SIZE: usize = Registers::SERIALIZE_SIZE + 8 + 8;
+    /// Size of the layout without a version, as saved before the
+    /// hidden registers were added. It is still restored.
+    const SERIALIZE_SIZE_LEGACY: usize = Registers::SERIALIZE_SIZE + 8 + 8;
+    /// Version of the layout, saved after the legacy layout
+    const SERIALIZE_VERSION: u8 = 1;
+    pub const SERIALIZE_SIZE: usize = State::SERIALIZE_SIZE_LEGACY + 1 + INT_DATA_SIZE + 2 + 1 + 2 + 2 + 1;
+
+    // Tag of the CPU at the end of the state, it selects the variant
+    // tail. The Z80N and the R800 have the state of the Z80.
+    fn variant_tag(&self) -> u8 {
+        if self.z180.is_some() {
+            1
+        } else if self.reg.is_ez80() {
+            2
+        } else if self.reg.is_8085() {
+            4
+        } else if self.reg.is_8080() {
+            3
+        } else if self.reg.is_lr35902() {
+            5
+        } else {
+            0
+        }
+    }
 
     pub fn serialize(&self) -> Vec<u8> {
         let mut data = Vec::with_capacity(State::SERIALIZE_SIZE);
@@ -56,19 +176,55 @@ impl State {
         data.push(self.nmi_pending as u8);
         data.push(self.reset_pending as u8);
         data.push(self.int_just_enabled as u8);
-       match self.index {
+        match self.index {
             Reg16::IX => data.push(1),
             Reg16::IY => data.push(2),
             _ => data.push(0),
         }
         data.push(self.displacement as u8);
+        data.push(State::SERIALIZE_VERSION);
+        data.extend_from_slice(&self.int_data);
+        data.extend_from_slice(&self.wz.to_le_bytes());
+        data.push(self.q);
+        data.push(self.rst_mask);
+        data.push(self.rst_pending);
+        data.push(match self.model {
+            CpuModel::ZilogNmos => 0,
+            CpuModel::ZilogCmos => 1,
+            CpuModel::NecNmos => 2,
+            CpuModel::StCmos => 3,
+        });
+        data.push(self.ld_a_ir as u8);
+        data.push(self.variant_tag());
+        if let Some(z180) = &self.z180 {
+            data.extend_from_slice(&z180.serialize());
+        }
+        if self.reg.is_ez80() {
+            data.extend_from_slice(&self.reg.serialize_ez80());
+        }
         data
     }
 
     pub fn deserialize(&mut self, data: &[u8]) -> io::Result<()> {
-        if data.len() < State::SERIALIZE_SIZE {
+        if data.len() < State::SERIALIZE_SIZE_LEGACY {
             return Err(io::Error::new(io::ErrorKind::InvalidData, "Data too short"));
         }
+        let legacy = data.len() == State::SERIALIZE_SIZE_LEGACY;
+        // The legacy layout was only saved by the Z80 and the 8080
+        if legacy && !matches!(self.variant_tag(), 0 | 3) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "State of another CPU"));
+        }
+        if !legacy {
+            if data[State::SERIALIZE_SIZE_LEGACY] != State::SERIALIZE_VERSION {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Unknown state version"));
+            }
+            if data.len() < State::SERIALIZE_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Data too short"));
+            }
+            if data[State::SERIALIZE_SIZE - 1] != self.variant_tag() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "State of another CPU"));
+            }
+        }
         let err = self.reg.deserialize(&data[0..]);
         err?;
 
@@ -86,6 +242,36 @@ impl State {
             _ => self.index = Reg16::HL,
         }
         self.displacement = data[i+15] as i8;
+        if legacy {
+            // The hidden state saved since then is reset, the model is kept
+            let initial = State::new();
+            self.int_data = initial.int_data;
+            self.wz = initial.wz;
+            self.q = initial.q;
+            self.rst_mask = initial.rst_mask;
+            self.rst_pending = initial.rst_pending;
+            self.ld_a_ir = initial.ld_a_ir;
+            return Ok(());
+        }
+        self.int_data.copy_from_slice(&data[i+17..i+17+INT_DATA_SIZE]);
+        let i = i + 17 + INT_DATA_SIZE;
+        self.wz = u16::from_le_bytes([data[i], data[i+1]]);
+        self.q = data[i+2];
+        self.rst_mask = data[i+3];
+        self.rst_pending = data[i+4];
+        self.model = match data[i+5] {
+            1 => CpuModel::ZilogCmos,
+            2 => CpuModel::NecNmos,
+            3 => CpuModel::StCmos,
+            _ => CpuModel::ZilogNmos,
+        };
+        self.ld_a_ir = data[i+6] != 0;
+        if let Some(z180) = &mut self.z180 {
+            z180.deserialize(&data[State::SERIALIZE_SIZE..])?;
+        }
+        if self.reg.is_ez80() {
+            self.reg.deserialize_ez80(&data[State::SERIALIZE_SIZE..])?;
+        }
         Ok(())
     }
 }
@@ -5,7 +5,8 @@ use super::machine::Machine;
 
 /// Helper to emulate real CPU speed.
 ///
-/// Runs the CPU counting cycle and wall time.
+/// Runs the CPU counting cycle and wall time. The cycles include the
+/// wait states requested by the Machine with `Machine::wait_states()`.
 pub struct TimedRunner {
     mhz: f64,
     quantum_cycles: u64,
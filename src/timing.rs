@@ -0,0 +1,199 @@
+/// Type of machine cycle on the bus
+///
+/// Passed to `Machine::bus_cycle()` before each access to memory or
+/// to the ports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BusCycle {
+    /// Opcode fetch, M1 cycle. Used for the opcode and for the prefixes
+    OpcodeFetch,
+    /// Memory refresh, on the last two T-states of the M1 cycle. The
+    /// address is I on the high byte and R on the low byte. Only on the
+    /// Z80
+    Refresh,
+    /// Memory read, including the immediate operands
+    MemoryRead,
+    /// Memory write
+    MemoryWrite,
+    /// Port input
+    IoRead,
+    /// Port output
+    IoWrite,
+    /// Interrupt acknowledge, the M1 cycle where the device places data
+    /// on the bus. The address is the PC
+    InterruptAck,
+    /// Internal operation, one T-state. The address is the one left on
+    /// the bus, without memory or port access
+    Internal,
+}
+
+/// Length in T-states of the machine cycles of a CPU variant
+pub(crate) struct TimingModel {
+    /// Opcode fetch, M1
+    pub opcode_fetch: u8,
+    /// Memory refresh on the last two T-states of the M1 cycle
+    pub refresh: bool,
+    /// Memory read
+    pub memory_read: u8,
+    /// Memory write
+    pub memory_write: u8,
+    /// Port input or output
+    pub io: u8,
+    /// Interrupt acknowledge, it replaces the opcode fetch
+    pub interrupt_ack: u8,
+    /// The internal cycles are notified to the machine
+    pub internal: bool,
+    /// Internal cycles between the read and the write on instructions
+    /// like INC (HL)
+    pub read_modify_write: u8,
+    /// Internal cycles of the 16 bit increments and of LD SP, HL
+    pub inc_dec_16: u8,
+    /// Internal cycles of the 16 bit additions
+    pub add_16: u8,
+    /// Internal cycles of EX (SP), HL between the reads and the writes
+    pub ex_sp_hl_read: u8,
+    /// Internal cycles at the end of EX (SP), HL
+    pub ex_sp_hl: u8,
+    /// Extra T-states of a memory access on a 256 byte page other than
+    /// the one of the previous access, for the DRAM page mode of the R800
+    pub page_break: u8,
+    /// The cycle table has only the cycles beyond the bus cycles, the
+    /// length of the instructions depends on the mode as on the eZ80
+    pub bus_timed: bool,
+}
+
+// Z80 CPU User Manual, Zilog UM0080, "Instruction Timing"
+pub(crate) const TIMING_Z80: TimingModel = TimingModel {
+    opcode_fetch: 4,
+    refresh: true,
+    memory_read: 3,
+    memory_write: 3,
+    io: 4, // Includes the automatic wait state
+    interrupt_ack: 6, // Includes two automatic wait states
+    internal: true,
+    read_modify_write: 1,
+    inc_dec_16: 2,
+    add_16: 7,
+    ex_sp_hl_read: 1,
+    ex_sp_hl: 2,
+    page_break: 0,
+    bus_timed: false,
+};
+
+// Intel 8080 Microcomputer Systems User's Manual, "Instruction Cycle"
+pub(crate) const TIMING_8080: TimingModel = TimingModel {
+    opcode_fetch: 4,
+    refresh: false,
+    memory_read: 3,
+    memory_write: 3,
+    io: 3,
+    interrupt_ack: 4,
+    internal: true,
+    read_modify_write: 0,
+    inc_dec_16: 1,
+    add_16: 6,
+    ex_sp_hl_read: 1,
+    ex_sp_hl: 1,
+    page_break: 0,
+    bus_timed: false,
+};
+
+// Intel 8085 Microcomputer Systems User's Manual, "Instruction Set"
+pub(crate) const TIMING_8085: TimingModel = TimingModel {
+    opcode_fetch: 4, // 6 on some instructions, counted on the cycle table
+    refresh: false,
+    memory_read: 3,
+    memory_write: 3,
+    io: 3,
+    interrupt_ack: 4, // The longer M1 of CALL and RST is on the cycle table
+    internal: true,
+    read_modify_write: 0,
+    inc_dec_16: 2,
+    add_16: 6,
+    ex_sp_hl_read: 0,
+    ex_sp_hl: 0,
+    page_break: 0,
+    bus_timed: false,
+};
+
+// Z8018x Family MPU User Manual, Zilog UM0050, "Instruction Summary". The
+// internal cycles overlap with the bus cycles differently than on the
+// Z80, only the states on the cycle table are counted.
+pub(crate) const TIMING_Z180: TimingModel = TimingModel {
+    opcode_fetch: 3,
+    refresh: false, // Refresh cycles are inserted by the refresh controller
+    memory_read: 3,
+    memory_write: 3,
+    io: 3,
+    interrupt_ack: 6, // Includes two automatic wait states
+    internal: false,
+    read_modify_write: 0,
+    inc_dec_16: 0,
+    add_16: 0,
+    ex_sp_hl_read: 0,
+    ex_sp_hl: 0,
+    page_break: 0,
+    bus_timed: false,
+};
+
+// Game Boy CPU Manual and Pan Docs, "CPU Instruction Set". Counted in
+// machine cycles of 4 clocks, the memory accesses take one each.
+pub(crate) const TIMING_LR35902: TimingModel = TimingModel {
+    opcode_fetch: 1,
+    refresh: false,
+    memory_read: 1,
+    memory_write: 1,
+    io: 1, // There are no ports, the I/O registers are on the memory map
+    interrupt_ack: 1,
+    internal: false,
+    read_modify_write: 0,
+    inc_dec_16: 0,
+    add_16: 0,
+    ex_sp_hl_read: 0,
+    ex_sp_hl: 0,
+    page_break: 0,
+    bus_timed: false,
+};
+
+// R800 Technical Data Book, ASCII, "Instruction Execution Time". Counted
+// in clocks, the accesses take one if they are on the same DRAM page as
+// the previous one. The refresh steals bus time periodically and is left
+// to the machine with `Machine::wait_states()`, as the I/O waits of the
+// MSX turboR.
+pub(crate) const TIMING_R800: TimingModel = TimingModel {
+    opcode_fetch: 1,
+    refresh: false,
+    memory_read: 1,
+    memory_write: 1,
+    io: 1,
+    interrupt_ack: 1,
+    internal: false,
+    read_modify_write: 0,
+    inc_dec_16: 0,
+    add_16: 0,
+    ex_sp_hl_read: 0,
+    ex_sp_hl: 0,
+    page_break: 1,
+    bus_timed: false,
+};
+
+// eZ80 CPU User Manual, Zilog UM0077, "Instruction Set". Counted in
+// clocks without wait states, one for each memory or port access. The
+// number of accesses depends on the ADL mode and on the suffixes, the
+// cycle tables have only the extra cycles of the jumps and of the read
+// modify write instructions.
+pub(crate) const TIMING_EZ80: TimingModel = TimingModel {
+    opcode_fetch: 1,
+    refresh: false,
+    memory_read: 1,
+    memory_write: 1,
+    io: 1,
+    interrupt_ack: 1,
+    internal: false,
+    read_modify_write: 0,
+    inc_dec_16: 0,
+    add_16: 0,
+    ex_sp_hl_read: 0,
+    ex_sp_hl: 0,
+    page_break: 0,
+    bus_timed: true,
+};
@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use super::registers::{Reg16, Reg8, Registers};
+
+/// Memory or I/O access done by an instruction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Memory read. The address is 24 bits on the eZ80 long accesses.
+    MemoryRead { address: u32, value: u8 },
+    /// Memory write. The address is 24 bits on the eZ80 long accesses.
+    MemoryWrite { address: u32, value: u8 },
+    /// Port read
+    PortIn { address: u16, value: u8 },
+    /// Port write
+    PortOut { address: u16, value: u8 },
+}
+
+/// Record of an executed instruction, as received by a `Tracer`
+#[derive(Clone, Debug)]
+pub struct TraceRecord {
+    /// Address of the instruction
+    pub pc: u16,
+    /// Bytes of the instruction, with the prefixes and the arguments
+    pub bytes: Vec<u8>,
+    /// The four bytes at PC before the execution, as some trace formats
+    /// show them
+    pub memory: [u8; 4],
+    /// The instruction disassembled with the syntax of the CPU
+    pub disasm: String,
+    /// Registers before the execution
+    pub before: Registers,
+    /// Registers after the execution
+    pub after: Registers,
+    /// Cycle count when the instruction started
+    pub cycle: u64,
+    /// Cycles used by the instruction, with the wait states
+    pub cycles: u64,
+    /// Memory and port accesses, in order. The opcode fetches are not
+    /// included.
+    pub accesses: Vec<Access>,
+}
+
+/// Receiver of the instructions executed by the CPU
+///
+/// Set with `Cpu::set_tracer()`. The record is built only when a tracer
+/// is set.
+pub trait Tracer {
+    /// Called after each instruction executed
+    fn trace(&mut self, record: &TraceRecord);
+}
+
+/// Text format of a trace line, used by `StdoutTracer` and `WriterTracer`
+pub trait TraceFormat {
+    /// Returns the line for the record, without the line break
+    fn format(&self, record: &TraceRecord) -> String;
+}
+
+/// The format of `Cpu::set_trace()`: the instruction and the registers
+/// after the execution.
+pub struct DefaultFormat;
+
+impl TraceFormat for DefaultFormat {
+    fn format(&self, record: &TraceRecord) -> String {
+        let reg = &record.after;
+        let bytes: Vec<String> = record.bytes.iter().map(|b| format!("{b:02x}")).collect();
+        format!("==> {:04x}: {:20} PC:{:04x} AF:{:04x} BC:{:04x} DE:{:04x} HL:{:04x} SP:{:04x} IX:{:04x} IY:{:04x} Flags:{:08b} Cycle:{:04} [{}]",
+            record.pc,
+            record.disasm,
+            reg.pc(),
+            reg.get16(Reg16::AF),
+            reg.get16(Reg16::BC),
+            reg.get16(Reg16::DE),
+            reg.get16(Reg16::HL),
+            reg.get16(Reg16::SP),
+            reg.get16(Reg16::IX),
+            reg.get16(Reg16::IY),
+            reg.get8(Reg8::F),
+            record.cycle + record.cycles,
+            bytes.join(" ")
+        )
+    }
+}
+
+/// The format of the MAME debugger `trace` command: the address in
+/// uppercase and the instruction in lowercase.
+pub struct MameFormat;
+
+impl TraceFormat for MameFormat {
+    fn format(&self, record: &TraceRecord) -> String {
+        format!("{:04X}: {}", record.pc, record.disasm.to_lowercase())
+    }
+}
+
+/// The format of the Gameboy Doctor logs, used to compare LR35902
+/// emulators: the registers before the execution and the bytes at PC.
+pub struct GameboyDoctorFormat;
+
+impl TraceFormat for GameboyDoctorFormat {
+    fn format(&self, record: &TraceRecord) -> String {
+        let reg = &record.before;
+        format!("A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            reg.a(),
+            reg.get8(Reg8::F),
+            reg.get8(Reg8::B),
+            reg.get8(Reg8::C),
+            reg.get8(Reg8::D),
+            reg.get8(Reg8::E),
+            reg.get8(Reg8::H),
+            reg.get8(Reg8::L),
+            reg.get16(Reg16::SP),
+            record.pc,
+            record.memory[0],
+            record.memory[1],
+            record.memory[2],
+            record.memory[3]
+        )
+    }
+}
+
+/// Tracer printing a line per instruction on stdout
+pub struct StdoutTracer<F: TraceFormat> {
+    format: F,
+}
+
+impl<F: TraceFormat> StdoutTracer<F> {
+    /// Returns a tracer printing with the given format
+    pub fn new(format: F) -> StdoutTracer<F> {
+        StdoutTracer { format }
+    }
+}
+
+impl<F: TraceFormat> Tracer for StdoutTracer<F> {
+    fn trace(&mut self, record: &TraceRecord) {
+        println!("{}", self.format.format(record));
+    }
+}
+
+/// Tracer writing a line per instruction, to a file or any other writer
+///
+/// It panics if the write fails, as `println!` does.
+pub struct WriterTracer<W: Write, F: TraceFormat> {
+    writer: W,
+    format: F,
+}
+
+impl<W: Write, F: TraceFormat> WriterTracer<W, F> {
+    /// Returns a tracer writing with the given format
+    pub fn new(writer: W, format: F) -> WriterTracer<W, F> {
+        WriterTracer { writer, format }
+    }
+
+    /// Returns the writer. It is not flushed.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<F: TraceFormat> WriterTracer<BufWriter<File>, F> {
+    /// Returns a tracer writing to a new file with the given format
+    pub fn create<P: AsRef<Path>>(path: P, format: F) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(WriterTracer::new(BufWriter::new(file), format))
+    }
+}
+
+impl<W: Write, F: TraceFormat> Tracer for WriterTracer<W, F> {
+    fn trace(&mut self, record: &TraceRecord) {
+        writeln!(self.writer, "{}", self.format.format(record))
+            .unwrap_or_else(|e| panic!("failed writing the trace: {e}"));
+    }
+}
+
+/// Tracer keeping the last records in memory
+///
+/// The clones share the buffer: keep a clone to read the records while
+/// the CPU owns the tracer.
+#[derive(Clone)]
+pub struct RingBufferTracer {
+    capacity: usize,
+    records: Arc<Mutex<VecDeque<TraceRecord>>>,
+}
+
+impl RingBufferTracer {
+    /// Returns a tracer keeping up to `capacity` records
+    pub fn new(capacity: usize) -> RingBufferTracer {
+        assert!(capacity > 0, "the capacity must be at least 1");
+        RingBufferTracer {
+            capacity,
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// Returns the records kept, the oldest first
+    pub fn records(&self) -> Vec<TraceRecord> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Removes the records kept
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+}
+
+impl Tracer for RingBufferTracer {
+    fn trace(&mut self, record: &TraceRecord) {
+        let mut records = self.records.lock().unwrap();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record.clone());
+    }
+}
@@ -0,0 +1,476 @@
+use std::io;
+
+use super::machine::Machine;
+
+/*
+    Z180 on-chip peripherals. See the Zilog Z8018x Family MPU User
+    Manual, UM0050.
+
+    The 64 internal I/O registers are at ports 0x0000-0x003f, relocatable
+    in blocks of 64 with ICR. They are accessed with IN0/OUT0 or with any
+    I/O instruction with the high byte of the port as 0.
+
+    Not emulated: the CSI/O, the wait states set with DCNTL and the
+    DREQ pacing of the DMA, that transfers everything once enabled.
+*/
+
+// Internal I/O registers
+const CNTLA0: usize = 0x00;
+const STAT0: usize = 0x04;
+const TDR0: usize = 0x06;
+const RDR0: usize = 0x08;
+const TMDR0L: usize = 0x0c;
+const TMDR0H: usize = 0x0d;
+const RLDR0L: usize = 0x0e;
+const TCR: usize = 0x10;
+const TMDR1L: usize = 0x14;
+const TMDR1H: usize = 0x15;
+const RLDR1L: usize = 0x16;
+const FRC: usize = 0x18;
+const SAR0L: usize = 0x20;
+const DAR0L: usize = 0x23;
+const BCR0L: usize = 0x26;
+const MAR1L: usize = 0x28;
+const IAR1L: usize = 0x2b;
+const BCR1L: usize = 0x2e;
+const DSTAT: usize = 0x30;
+const DMODE: usize = 0x31;
+const DCNTL: usize = 0x32;
+const IL: usize = 0x33;
+const ITC: usize = 0x34;
+const RCR: usize = 0x36;
+const CBR: usize = 0x38;
+const BBR: usize = 0x39;
+const CBAR: usize = 0x3a;
+const OMCR: usize = 0x3e;
+const ICR: usize = 0x3f;
+
+const TMDRL: [usize; 2] = [TMDR0L, TMDR1L];
+const RLDRL: [usize; 2] = [RLDR0L, RLDR1L];
+
+// CNTLA
+const CNTLA_RE: u8 = 0x40;
+const CNTLA_TE: u8 = 0x20;
+// STAT
+const STAT_RDRF: u8 = 0x80;
+const STAT_RIE: u8 = 0x08;
+const STAT_TDRE: u8 = 0x02;
+const STAT_TIE: u8 = 0x01;
+// TCR, for channel 0. Shifted left by one for channel 1.
+const TCR_TIF0: u8 = 0x40;
+const TCR_TIE0: u8 = 0x10;
+const TCR_TDE0: u8 = 0x01;
+// DSTAT, for channel 0. Shifted left by one for channel 1.
+const DSTAT_DE0: u8 = 0x40;
+const DSTAT_DWE0: u8 = 0x10;
+const DSTAT_DIE0: u8 = 0x04;
+const DSTAT_DME: u8 = 0x01;
+// ITC
+const ITC_TRAP: u8 = 0x80;
+const ITC_UFO: u8 = 0x40;
+const ITC_ITE0: u8 = 0x01;
+const ITC_ITE1: u8 = 0x02;
+const ITC_ITE2: u8 = 0x04;
+
+// Clocks per count of the PRT and of the FRC
+const PRT_PRESCALER: u64 = 20;
+const FRC_PRESCALER: u64 = 10;
+// Memory read and write of each DMA transfer
+const DMA_TRANSFER_CYCLES: u64 = 6;
+
+/// State of the Z180 internal I/O registers and peripherals
+pub(crate) struct Z180 {
+    regs: [u8; 64],
+    // INT1 and INT2 lines
+    int1: bool,
+    int2: bool,
+    // Cycle of the last update of the timers
+    last_cycle: u64,
+    prt_prescaler: u64,
+    frc_prescaler: u64,
+    // TCR read with TIF set, TIF is cleared when TMDR is read next
+    tif_read: [bool; 2],
+}
+
+impl Z180 {
+    pub fn new() -> Z180 {
+        let mut regs = [0; 64];
+        regs[CNTLA0] = 0x10;
+        regs[STAT0] = STAT_TDRE;
+        regs[STAT0 + 1] = STAT_TDRE;
+        regs[TMDR0L] = 0xff;
+        regs[TMDR0H] = 0xff;
+        regs[RLDR0L] = 0xff;
+        regs[RLDR0L + 1] = 0xff;
+        regs[TMDR1L] = 0xff;
+        regs[TMDR1H] = 0xff;
+        regs[RLDR1L] = 0xff;
+        regs[RLDR1L + 1] = 0xff;
+        regs[FRC] = 0xff;
+        regs[DCNTL] = 0xf0;
+        regs[ITC] = ITC_ITE0;
+        regs[RCR] = 0xc0;
+        regs[CBAR] = 0xf0;
+        regs[OMCR] = 0xe0;
+        Z180 {
+            regs,
+            int1: false,
+            int2: false,
+            last_cycle: 0,
+            prt_prescaler: 0,
+            frc_prescaler: 0,
+            tif_read: [false; 2],
+        }
+    }
+
+    pub fn reset(&mut self, cycle: u64) {
+        *self = Z180::new();
+        self.last_cycle = cycle;
+    }
+
+    /// Returns the internal register of a port, if it is mapped there
+    pub fn internal_register(&self, port: u16) -> Option<usize> {
+        if port & 0xff00 == 0 && (port as u8 & 0xc0) == (self.regs[ICR] & 0xc0) {
+            Some((port & 0x3f) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Translates a logical address to the physical 20 bit address
+    pub fn translate(&self, address: u16) -> u32 {
+        /*
+        CBAR splits the logical space in the common area 0, the bank
+        area and the common area 1. The high nibble is the start of the
+        common area 1, the low nibble the start of the bank area, in 4KB
+        pages. BBR and CBR are added to the bank and to the common area 1.
+        */
+        let page = (address >> 12) as u8;
+        let cbar = self.regs[CBAR];
+        let base = if page >= cbar >> 4 {
+            self.regs[CBR]
+        } else if page >= cbar & 0x0f {
+            self.regs[BBR]
+        } else {
+            0
+        };
+        (((base as u32) << 12) + address as u32) & 0xfffff
+    }
+
+    pub fn read(&mut self, reg: usize) -> u8 {
+        match reg {
+            0x08 | 0x09 => {
+                // RDR, the receive register is empty again
+                self.regs[STAT0 + reg - RDR0] &= !STAT_RDRF;
+                self.regs[reg]
+            },
+            TCR => {
+                for ch in 0..2 {
+                    self.tif_read[ch] = self.regs[TCR] & (TCR_TIF0 << ch) != 0;
+                }
+                self.regs[TCR]
+            },
+            0x0c | 0x0d | 0x14 | 0x15 => {
+                let ch = if reg < TMDR1L {0} else {1};
+                if self.tif_read[ch] {
+                    self.tif_read[ch] = false;
+                    self.regs[TCR] &= !(TCR_TIF0 << ch);
+                }
+                self.regs[reg]
+            },
+            DSTAT => self.regs[DSTAT] | (DSTAT_DWE0 << 1) | DSTAT_DWE0,
+            _ => self.regs[reg],
+        }
+    }
+
+    pub fn write(&mut self, reg: usize, value: u8, sys: &mut dyn Machine) {
+        match reg {
+            0x04 | 0x05 => {
+                // STAT, only the interrupt enables and CTS1E/DCD0
+                self.regs[reg] = (self.regs[reg] & 0xf2) | (value & 0x0d);
+            },
+            0x06 | 0x07 => {
+                // TDR, sent right away and empty again
+                let ch = reg - TDR0;
+                self.regs[reg] = value;
+                if self.regs[CNTLA0 + ch] & CNTLA_TE != 0 {
+                    sys.asci_transmit(ch, value);
+                }
+            },
+            TCR => {
+                // TIF is read only
+                self.regs[TCR] = (self.regs[TCR] & 0xc0) | (value & 0x3f);
+            },
+            FRC => {}, // Read only
+            DSTAT => {
+                // DE0 and DE1 are written only with DWE0 and DWE1 low
+                let mut mask = (DSTAT_DIE0 << 1) | DSTAT_DIE0 | DSTAT_DME;
+                for ch in 0..2 {
+                    if value & (DSTAT_DWE0 << ch) == 0 {
+                        mask |= DSTAT_DE0 << ch;
+                    }
+                }
+                self.regs[DSTAT] = (self.regs[DSTAT] & !mask) | (value & mask);
+            },
+            ITC => {
+                // TRAP can only be cleared, UFO is read only
+                let trap = self.regs[ITC] & value & ITC_TRAP;
+                self.regs[ITC] = trap | (self.regs[ITC] & ITC_UFO) | (value & 0x07);
+            },
+            _ => self.regs[reg] = value,
+        }
+    }
+
+    /// Undefined opcode. The UFO flag is set if it was the third byte of
+    /// the instruction.
+    pub fn trap(&mut self, ufo: bool) {
+        self.regs[ITC] = (self.regs[ITC] & !ITC_UFO) | ITC_TRAP | if ufo {ITC_UFO} else {0};
+    }
+
+    /// The NMI stops the DMA
+    pub fn nmi(&mut self) {
+        self.regs[DSTAT] &= !DSTAT_DME;
+    }
+
+    pub fn signal_int1(&mut self, active: bool) {
+        self.int1 = active;
+    }
+
+    pub fn signal_int2(&mut self, active: bool) {
+        self.int2 = active;
+    }
+
+    pub fn int0_enabled(&self) -> bool {
+        self.regs[ITC] & ITC_ITE0 != 0
+    }
+
+    /// Returns if the peripherals can raise an interrupt while halted
+    pub fn is_running(&self) -> bool {
+        self.int1
+            || self.int2
+            || self.regs[TCR] & (TCR_TDE0 << 1 | TCR_TDE0) != 0
+            || self.regs[CNTLA0] & CNTLA_RE != 0
+            || self.regs[CNTLA0 + 1] & CNTLA_RE != 0
+            || self.interrupt_vector().is_some()
+    }
+
+    /// Returns the low byte of the vector of the highest priority
+    /// interrupt requested by INT1, INT2 or the peripherals
+    pub fn interrupt_vector(&self) -> Option<u8> {
+        let itc = self.regs[ITC];
+        let tcr = self.regs[TCR];
+        let dstat = self.regs[DSTAT];
+        let sources = [
+            self.int1 && itc & ITC_ITE1 != 0,
+            self.int2 && itc & ITC_ITE2 != 0,
+            tcr & TCR_TIF0 != 0 && tcr & TCR_TIE0 != 0,
+            tcr & (TCR_TIF0 << 1) != 0 && tcr & (TCR_TIE0 << 1) != 0,
+            dstat & DSTAT_DIE0 != 0 && dstat & DSTAT_DE0 == 0,
+            dstat & (DSTAT_DIE0 << 1) != 0 && dstat & (DSTAT_DE0 << 1) == 0,
+            false, // CSI/O
+            self.asci_interrupt(0),
+            self.asci_interrupt(1),
+        ];
+        sources.iter().position(|&source| source)
+            .map(|i| (self.regs[IL] & 0xe0) | (i as u8 * 2))
+    }
+
+    fn asci_interrupt(&self, ch: usize) -> bool {
+        let stat = self.regs[STAT0 + ch];
+        (stat & STAT_RDRF != 0 && stat & STAT_RIE != 0)
+            || (stat & STAT_TDRE != 0 && stat & STAT_TIE != 0)
+    }
+
+    /// Updates the peripherals up to the cycle. Returns the cycles used
+    /// by the DMA.
+    pub fn step(&mut self, sys: &mut dyn Machine, cycle: u64) -> u64 {
+        let elapsed = cycle.wrapping_sub(self.last_cycle);
+        self.last_cycle = cycle;
+        self.timers(elapsed);
+        self.asci_receive(sys);
+        self.dma(sys)
+    }
+
+    fn timers(&mut self, elapsed: u64) {
+        // The FRC counts down continuously
+        self.frc_prescaler += elapsed;
+        let ticks = self.frc_prescaler / FRC_PRESCALER;
+        self.frc_prescaler %= FRC_PRESCALER;
+        self.regs[FRC] = self.regs[FRC].wrapping_sub(ticks as u8);
+
+        self.prt_prescaler += elapsed;
+        let ticks = self.prt_prescaler / PRT_PRESCALER;
+        self.prt_prescaler %= PRT_PRESCALER;
+        for ch in 0..2 {
+            if ticks > 0 && self.regs[TCR] & (TCR_TDE0 << ch) != 0 {
+                self.prt_count(ch, ticks);
+            }
+        }
+    }
+
+    fn prt_count(&mut self, ch: usize, ticks: u64) {
+        // TMDR counts down, when it reaches zero TIF is set and it is
+        // reloaded from RLDR. Zero is taken as 65536.
+        let tmdr = match self.reg16(TMDRL[ch]) {
+            0 => 0x10000,
+            v => v as u64,
+        };
+        let value = if ticks < tmdr {
+            tmdr - ticks
+        } else {
+            self.regs[TCR] |= TCR_TIF0 << ch;
+            let period = match self.reg16(RLDRL[ch]) {
+                0 => 0x10000,
+                v => v as u64,
+            };
+            period - (ticks - tmdr) % period
+        };
+        self.set_reg16(TMDRL[ch], value as u16);
+    }
+
+    fn asci_receive(&mut self, sys: &mut dyn Machine) {
+        for ch in 0..2 {
+            if self.regs[CNTLA0 + ch] & CNTLA_RE != 0 && self.regs[STAT0 + ch] & STAT_RDRF == 0 {
+                if let Some(value) = sys.asci_receive(ch) {
+                    self.regs[RDR0 + ch] = value;
+                    self.regs[STAT0 + ch] |= STAT_RDRF;
+                }
+            }
+        }
+    }
+
+    fn dma(&mut self, sys: &mut dyn Machine) -> u64 {
+        let dstat = self.regs[DSTAT];
+        if dstat & DSTAT_DME == 0 {
+            return 0;
+        }
+        let mut cycles = 0;
+
+        if dstat & DSTAT_DE0 != 0 {
+            // Channel 0, memory or I/O to memory or I/O. The modes are
+            // increment, decrement, fixed and I/O.
+            let dmode = self.regs[DMODE];
+            let dm = (dmode >> 4) & 3;
+            let sm = (dmode >> 2) & 3;
+            let mut sar = self.reg24(SAR0L);
+            let mut dar = self.reg24(DAR0L);
+            for _ in 0..self.byte_count(BCR0L) {
+                let value = if sm == 3 {
+                    self.dma_port_in(sys, sar as u16)
+                } else {
+                    sys.peek_physical(sar)
+                };
+                if dm == 3 {
+                    self.dma_port_out(sys, dar as u16, value);
+                } else {
+                    sys.poke_physical(dar, value);
+                }
+                sar = Self::dma_next(sar, sm);
+                dar = Self::dma_next(dar, dm);
+                cycles += DMA_TRANSFER_CYCLES;
+            }
+            self.set_reg24(SAR0L, sar);
+            self.set_reg24(DAR0L, dar);
+            self.set_reg16(BCR0L, 0);
+            self.regs[DSTAT] &= !DSTAT_DE0;
+        }
+
+        if dstat & (DSTAT_DE0 << 1) != 0 {
+            // Channel 1, memory to I/O or I/O to memory, set with DIM
+            let dim = self.regs[DCNTL] & 3;
+            let mar_mode = if dim & 1 == 0 {0} else {1};
+            let mut mar = self.reg24(MAR1L);
+            let iar = self.reg16(IAR1L);
+            for _ in 0..self.byte_count(BCR1L) {
+                if dim & 2 == 0 {
+                    let value = sys.peek_physical(mar);
+                    self.dma_port_out(sys, iar, value);
+                } else {
+                    let value = self.dma_port_in(sys, iar);
+                    sys.poke_physical(mar, value);
+                }
+                mar = Self::dma_next(mar, mar_mode);
+                cycles += DMA_TRANSFER_CYCLES;
+            }
+            self.set_reg24(MAR1L, mar);
+            self.set_reg16(BCR1L, 0);
+            self.regs[DSTAT] &= !(DSTAT_DE0 << 1);
+        }
+        cycles
+    }
+
+    fn byte_count(&self, reg: usize) -> u32 {
+        // Zero is taken as 65536
+        match self.reg16(reg) {
+            0 => 0x10000,
+            v => v as u32,
+        }
+    }
+
+    fn dma_next(address: u32, mode: u8) -> u32 {
+        match mode {
+            0 => address.wrapping_add(1) & 0xfffff,
+            1 => address.wrapping_sub(1) & 0xfffff,
+            _ => address,
+        }
+    }
+
+    fn dma_port_in(&mut self, sys: &mut dyn Machine, port: u16) -> u8 {
+        match self.internal_register(port) {
+            Some(reg) => self.read(reg),
+            None => sys.port_in(port),
+        }
+    }
+
+    fn dma_port_out(&mut self, sys: &mut dyn Machine, port: u16, value: u8) {
+        match self.internal_register(port) {
+            Some(reg) => self.write(reg, value, sys),
+            None => sys.port_out(port, value),
+        }
+    }
+
+    fn reg16(&self, reg: usize) -> u16 {
+        u16::from_le_bytes([self.regs[reg], self.regs[reg + 1]])
+    }
+
+    fn set_reg16(&mut self, reg: usize, value: u16) {
+        self.regs[reg..reg + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn reg24(&self, reg: usize) -> u32 {
+        u32::from_le_bytes([self.regs[reg], self.regs[reg + 1], self.regs[reg + 2] & 0x0f, 0])
+    }
+
+    fn set_reg24(&mut self, reg: usize, value: u32) {
+        self.regs[reg..reg + 3].copy_from_slice(&value.to_le_bytes()[0..3]);
+    }
+
+    pub const SERIALIZE_SIZE: usize = 64 + 2 + 8 + 8 + 8 + 2;
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Z180::SERIALIZE_SIZE);
+        data.extend_from_slice(&self.regs);
+        data.push(self.int1 as u8);
+        data.push(self.int2 as u8);
+        data.extend_from_slice(&self.last_cycle.to_le_bytes());
+        data.extend_from_slice(&self.prt_prescaler.to_le_bytes());
+        data.extend_from_slice(&self.frc_prescaler.to_le_bytes());
+        data.push(self.tif_read[0] as u8);
+        data.push(self.tif_read[1] as u8);
+        data
+    }
+
+    pub fn deserialize(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.len() < Z180::SERIALIZE_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Data too short"));
+        }
+        self.regs.copy_from_slice(&data[0..64]);
+        self.int1 = data[64] != 0;
+        self.int2 = data[65] != 0;
+        self.last_cycle = u64::from_le_bytes(data[66..74].try_into().unwrap());
+        self.prt_prescaler = u64::from_le_bytes(data[74..82].try_into().unwrap());
+        self.frc_prescaler = u64::from_le_bytes(data[82..90].try_into().unwrap());
+        self.tif_read = [data[90] != 0, data[91] != 0];
+        Ok(())
+    }
+}
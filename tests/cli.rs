@@ -0,0 +1,132 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const PROGRAM: [u8; 0x22] = [
+    0x31, 0x00, 0x01, // LD SP, 0100h
+    0xcd, 0x10, 0x00, // CALL SUB1
+    0x76, // HALT
+    0, 0, 0, 0, 0, 0, 0, 0, 0,
+    // SUB1
+    0x3c, // INC A
+    0xcd, 0x20, 0x00, // CALL SUB2
+    0xc9, // RET
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    // SUB2
+    0x04, // INC B
+    0xc9, // RET
+];
+
+fn temp_file(name: &str, content: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("iz80_cli_{}_{name}", std::process::id()));
+    fs::write(&path, content).unwrap();
+    path
+}
+
+fn intel_hex(data: &[u8]) -> String {
+    let mut hex = String::new();
+    for (i, chunk) in data.chunks(16).enumerate() {
+        let address = (i * 16) as u16;
+        let mut record = vec![chunk.len() as u8, (address >> 8) as u8, address as u8, 0x00];
+        record.extend_from_slice(chunk);
+        let checksum = record.iter().fold(0_u8, |sum, b| sum.wrapping_sub(*b));
+        record.push(checksum);
+        hex += ":";
+        for b in record {
+            hex += &format!("{b:02X}");
+        }
+        hex += "\n";
+    }
+    hex + ":00000001FF\n"
+}
+
+fn iz80(args: &[&str], script: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_iz80"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(script.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_cpm_program() {
+    let image = temp_file("hello.com", &[
+        0x0e, 0x09, // LD C, 9
+        0x11, 0x0a, 0x01, // LD DE, 010ah
+        0xcd, 0x05, 0x00, // CALL 5
+        0xc9, // RET
+        0x00,
+        b'H', b'e', b'l', b'l', b'o', b'$',
+    ]);
+
+    let output = iz80(&["--run", image.to_str().unwrap()], "");
+    assert_eq!("Hello", output);
+
+    // The monitor steps over the system calls
+    let output = iz80(&[image.to_str().unwrap()], "s 2\nn\ng\n");
+    assert!(output.contains("  0105  cd 05 00      CALL 0005h\n"));
+    assert!(output.contains("Hello"));
+    assert!(output.contains("Program exited"));
+    fs::remove_file(image).unwrap();
+}
+
+#[test]
+fn test_step_over_and_out() {
+    let image = temp_file("program.hex", intel_hex(&PROGRAM).as_bytes());
+    let symbols = temp_file("program.sym", b"SUB1 EQU 0010h\nSUB2 = $0020 ; inner\n");
+
+    let output = iz80(&["--symbols", symbols.to_str().unwrap(), image.to_str().unwrap()],
+        "s\nn\nr PC 0\ns 3\no\nd SUB1 2\nq\n");
+    let positions: Vec<&str> = output.lines()
+        .filter(|line| line.contains("PC="))
+        .map(|line| &line[line.find("PC=").unwrap()..][..7])
+        .collect();
+    assert_eq!(vec!["PC=0000", "PC=0003", "PC=0006", "PC=0011", "PC=0006"], positions);
+    assert!(output.contains("SUB1:\n  0010  3c            INC A\n  0011  cd 20 00      CALL SUB2\n"));
+    fs::remove_file(image).unwrap();
+    fs::remove_file(symbols).unwrap();
+}
+
+#[test]
+fn test_symbols_as_hex_numbers() {
+    let image = temp_file("hexnames.bin", &PROGRAM);
+    let symbols = temp_file("hexnames.sym", b"0010 each\nCAFE EQU 0020h\n");
+
+    let output = iz80(&["--symbols", symbols.to_str().unwrap(), image.to_str().unwrap()],
+        "g CAFE\nd each 1\nq\n");
+    assert!(output.contains("PC=0020"));
+    assert!(output.contains("each:\n  0010  3c            INC A\n"));
+    fs::remove_file(image).unwrap();
+    fs::remove_file(symbols).unwrap();
+}
+
+#[test]
+fn test_breakpoints() {
+    let image = temp_file("program.bin", &PROGRAM);
+
+    let output = iz80(&[image.to_str().unwrap()], "b 20\ng\ng\nbc *\nb\ng\nq\n");
+    assert!(output.contains("Breakpoint at 0020\nPC=0020"));
+    assert!(output.contains("Halted\nPC=0007"));
+    fs::remove_file(image).unwrap();
+}
+
+#[test]
+fn test_memory_and_state() {
+    let image = temp_file("org.bin", &PROGRAM);
+    let state = temp_file("state.bin", b"");
+
+    let output = iz80(&["--org", "8000", image.to_str().unwrap()], &format!(
+        "e 9000 12 34\nm 9000 2\nr BC 5678\nsave {0}\ne 9000 0 0\nr BC 0\nrestore {0}\nm 9000 2\nq\n",
+        state.display()));
+    assert_eq!(2, output.matches("9000  12 34").count());
+    assert!(output.contains("BC=5678"));
+    assert!(output.contains("PC=8000"));
+    fs::remove_file(image).unwrap();
+    fs::remove_file(state).unwrap();
+}
@@ -0,0 +1,18 @@
+// Helpers shared by the integration tests, each test uses a part of them
+#![allow(dead_code)]
+
+use iz80::*;
+
+/// Places the code on the machine at the address
+pub fn load(sys: &mut dyn Machine, address: u16, code: &[u8]) {
+    for (i, e) in code.iter().enumerate() {
+        sys.poke(address.wrapping_add(i as u16), *e);
+    }
+}
+
+/// Returns the cpu with a PlainMachine with the code at 0000h
+pub fn setup(cpu: Cpu, code: &[u8]) -> (Cpu, PlainMachine) {
+    let mut sys = PlainMachine::new();
+    load(&mut sys, 0, code);
+    (cpu, sys)
+}
@@ -0,0 +1,133 @@
+use iz80::*;
+
+struct SpectrumMachine {
+    plain: PlainMachine,
+    contention: SpectrumContention,
+}
+
+impl SpectrumMachine {
+    fn new(contention: SpectrumContention) -> SpectrumMachine {
+        SpectrumMachine {
+            plain: PlainMachine::new(),
+            contention,
+        }
+    }
+}
+
+impl Machine for SpectrumMachine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.plain.peek(address)
+    }
+    fn poke(&mut self, address: u16, value: u8) {
+        self.plain.poke(address, value);
+    }
+
+    fn port_in(&mut self, address: u16) -> u8 {
+        self.plain.port_in(address)
+    }
+    fn port_out(&mut self, address: u16, value: u8) {
+        self.plain.port_out(address, value);
+    }
+
+    fn wait_states(&mut self, cycle: BusCycle, address: u16, t_state: u64) -> u8 {
+        self.contention.delay(cycle, address, t_state)
+    }
+}
+
+#[test]
+fn test_delay_pattern_48k() {
+    let contention = SpectrumContention::new_48k();
+
+    assert_eq!(0, contention.delay_at(14334));
+    assert_eq!(6, contention.delay_at(14335));
+    assert_eq!(5, contention.delay_at(14336));
+    assert_eq!(1, contention.delay_at(14340));
+    assert_eq!(0, contention.delay_at(14341));
+    assert_eq!(0, contention.delay_at(14342));
+    assert_eq!(6, contention.delay_at(14343));
+    assert_eq!(0, contention.delay_at(14335 + 128));
+    assert_eq!(6, contention.delay_at(14335 + 224));
+    assert_eq!(0, contention.delay_at(14335 + 192 * 224));
+}
+
+#[test]
+fn test_delay_pattern_128k() {
+    let contention = SpectrumContention::new_128k();
+
+    assert_eq!(0, contention.delay_at(14360));
+    assert_eq!(6, contention.delay_at(14361));
+    assert_eq!(6, contention.delay_at(14361 + 228));
+}
+
+#[test]
+fn test_contended_banks_128k() {
+    let mut contention = SpectrumContention::new_128k();
+
+    assert!(contention.is_contended(0x4000));
+    assert!(!contention.is_contended(0x8000));
+    assert!(!contention.is_contended(0xc000));
+    contention.set_paged_bank(7);
+    assert!(contention.is_contended(0xc000));
+    contention.set_paged_bank(2);
+    assert!(!contention.is_contended(0xc000));
+}
+
+#[test]
+fn test_frame_t_state() {
+    let mut contention = SpectrumContention::new_48k();
+    contention.set_frame_start(1000);
+
+    assert_eq!(0, contention.frame_t_state(1000));
+    assert_eq!(10, contention.frame_t_state(1000 + 69898));
+    assert_eq!(69887, contention.frame_t_state(999));
+    assert_eq!(6, contention.delay(BusCycle::MemoryRead, 0x4000, 1000 + 14335));
+    assert_eq!(0, contention.delay(BusCycle::MemoryRead, 0x8000, 1000 + 14335));
+}
+
+#[test]
+fn test_io_contention() {
+    let contention = SpectrumContention::new_48k();
+
+    // High byte uncontended, even port: N:1, C:3
+    assert_eq!(6, contention.delay(BusCycle::IoWrite, 0x00fe, 14334));
+    // High byte uncontended, odd port: N:4
+    assert_eq!(0, contention.delay(BusCycle::IoWrite, 0x00ff, 14335));
+    // High byte contended, even port: C:1, C:3
+    assert_eq!(6, contention.delay(BusCycle::IoWrite, 0x40fe, 14335));
+    // High byte contended, odd port: C:1, C:1, C:1, C:1
+    // Delays of 6, 0, 6 and 0
+    assert_eq!(12, contention.delay(BusCycle::IoRead, 0x40ff, 14335));
+}
+
+#[test]
+fn test_contended_nop() {
+    let mut sys = SpectrumMachine::new(SpectrumContention::new_48k());
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xc3); // JP 4000h
+    sys.poke16(0x0001, 0x4000);
+    sys.poke(0x4000, 0x00); // NOP
+    sys.contention.set_frame_start(10 + 69888 - 14335);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+
+    // The NOP fetch starts on frame T-state 14335
+    assert_eq!(10 + 6 + 4, cpu.cycle_count());
+}
+
+#[test]
+fn test_contended_internal_cycles() {
+    let mut sys = SpectrumMachine::new(SpectrumContention::new_48k());
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0x34); // INC (HL)
+    cpu.registers().set16(Reg16::HL, 0x4000);
+    sys.contention.set_frame_start(7 + 69888 - 14335);
+
+    cpu.execute_instruction(&mut sys);
+
+    // The read at T-state 4 is before the screen, the internal cycle at 7
+    // is delayed 6 and the write at 14 is on a T-state without delay
+    assert_eq!(11 + 6, cpu.cycle_count());
+}
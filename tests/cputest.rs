@@ -18,10 +18,8 @@ fn cpu_test(mut cpu: Cpu) {
     let mut machine = PlainMachine::new();
 
     // Load program
-    let code = CODE;
-    let size = code.len();
-    for i in 0..size {
-        machine.poke(0x100 + i as u16, code[i]);
+    for (i, e) in CODE.iter().enumerate() {
+        machine.poke(0x100 + i as u16, *e);
     }
 
     /*
@@ -32,8 +30,8 @@ fn cpu_test(mut cpu: Cpu) {
     */
     //let code = [0xD3, 0x00, 0xC9];
     let code = [0xC9];
-    for i in 0..code.len() {
-        machine.poke(5 + i as u16, code[i]);
+    for (i, e) in code.iter().enumerate() {
+        machine.poke(5 + i as u16, *e);
     }
 
     cpu.registers().set_pc(0x100);
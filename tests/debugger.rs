@@ -0,0 +1,293 @@
+use iz80::*;
+
+mod common;
+
+fn setup(code: &[u8]) -> (Cpu, PlainMachine) {
+    common::setup(Cpu::new(), code)
+}
+
+const LOOP: [u8; 6] = [
+    0x06, 0x05, // LD B, 5
+    0x3c, // INC A
+    0x10, 0xfd, // DJNZ -3
+    0x76, // HALT
+];
+
+#[test]
+fn test_run_to_halt_and_limit() {
+    let (mut cpu, mut sys) = setup(&LOOP);
+    cpu.registers().set_a(0x00);
+
+    assert_eq!(StopReason::InstructionLimit, cpu.run(&mut sys, 3));
+    assert_eq!(0x0002, cpu.registers().pc());
+
+    assert_eq!(StopReason::Halted, cpu.run(&mut sys, 100));
+    assert_eq!(0x05, cpu.registers().a());
+}
+
+#[test]
+fn test_breakpoint_and_resume() {
+    let (mut cpu, mut sys) = setup(&LOOP);
+    cpu.registers().set_a(0x00);
+    cpu.debugger().add_breakpoint(0x0002);
+
+    assert_eq!(StopReason::Breakpoint(0x0002), cpu.run(&mut sys, 100));
+    assert_eq!(0x00, cpu.registers().a());
+
+    // Resuming executes the instruction on the breakpoint
+    assert_eq!(StopReason::Breakpoint(0x0002), cpu.run(&mut sys, 100));
+    assert_eq!(0x01, cpu.registers().a());
+
+    cpu.debugger().remove_breakpoint(0x0002);
+    assert_eq!(StopReason::Halted, cpu.run(&mut sys, 100));
+    assert_eq!(0x05, cpu.registers().a());
+}
+
+#[test]
+fn test_conditional_breakpoint() {
+    let (mut cpu, mut sys) = setup(&LOOP);
+    cpu.registers().set_a(0x00);
+    cpu.debugger().add_conditional_breakpoint(0x0002, Condition::Reg8(Reg8::B, 2));
+
+    assert_eq!(StopReason::Breakpoint(0x0002), cpu.run(&mut sys, 100));
+    assert_eq!(0x03, cpu.registers().a());
+    assert_eq!(1, cpu.debugger().breakpoints().len());
+}
+
+#[test]
+fn test_run_to() {
+    let (mut cpu, mut sys) = setup(&LOOP);
+
+    assert_eq!(StopReason::Breakpoint(0x0005), cpu.run_to(&mut sys, 0x0005, 100));
+    assert!(cpu.debugger().breakpoints().is_empty());
+}
+
+#[test]
+fn test_memory_watchpoints() {
+    let (mut cpu, mut sys) = setup(&[
+        0x3a, 0x00, 0x80, // LD A, (8000h)
+        0x32, 0x01, 0x80, // LD (8001h), A
+        0xc5, // PUSH BC
+        0x76, // HALT
+    ]);
+    cpu.registers().set16(Reg16::SP, 0x9000);
+    cpu.debugger().add_watchpoint(Watchpoint::MemoryWrite(0x8001));
+    cpu.debugger().add_watchpoint(Watchpoint::MemoryWrite(0x8fff));
+    sys.poke(0x8000, 0x42);
+
+    // The read is not watched
+    assert_eq!(StopReason::Watchpoint(Access::MemoryWrite { address: 0x8001, value: 0x42 }),
+        cpu.run(&mut sys, 100));
+    assert_eq!(0x0006, cpu.registers().pc());
+
+    // The stack is watched too
+    cpu.registers().set16(Reg16::BC, 0x1234);
+    assert_eq!(StopReason::Watchpoint(Access::MemoryWrite { address: 0x8fff, value: 0x12 }),
+        cpu.run(&mut sys, 100));
+
+    cpu.debugger().add_watchpoint(Watchpoint::MemoryRead(0x8000));
+    cpu.registers().set_pc(0x0000);
+    assert_eq!(StopReason::Watchpoint(Access::MemoryRead { address: 0x8000, value: 0x42 }),
+        cpu.run(&mut sys, 100));
+    assert_eq!(0x0003, cpu.registers().pc());
+}
+
+#[test]
+fn test_port_watchpoints() {
+    let (mut cpu, mut sys) = setup(&[
+        0xd3, 0x10, // OUT (10h), A
+        0xed, 0x78, // IN A, (C)
+        0x76, // HALT
+    ]);
+    cpu.registers().set_a(0x77);
+    cpu.registers().set16(Reg16::BC, 0x1220);
+    cpu.debugger().add_watchpoint(Watchpoint::PortIn(0x0020));
+    cpu.debugger().add_watchpoint(Watchpoint::PortIn(0x1220));
+    cpu.debugger().add_watchpoint(Watchpoint::PortOut(0x7710));
+
+    // The whole port address is compared, 0020h is not hit
+    assert_eq!(StopReason::Watchpoint(Access::PortOut { address: 0x7710, value: 0x77 }),
+        cpu.run(&mut sys, 100));
+    assert_eq!(StopReason::Watchpoint(Access::PortIn { address: 0x1220, value: 0x00 }),
+        cpu.run(&mut sys, 100));
+
+    cpu.debugger().clear();
+    cpu.registers().set_pc(0x0000);
+    assert_eq!(StopReason::Halted, cpu.run(&mut sys, 100));
+}
+
+const SUBROUTINES: [(u16, &[u8]); 4] = [
+    (0x0000, &[
+        0x31, 0x00, 0x01, // LD SP, 0100h
+        0xcd, 0x10, 0x00, // CALL 0010h
+        0xcd, 0x20, 0x00, // CALL 0020h
+        0xcd, 0x30, 0x00, // CALL 0030h
+        0x76, // HALT
+    ]),
+    // Recursive, B times
+    (0x0010, &[
+        0x3c, // INC A
+        0x05, // DEC B
+        0xc4, 0x10, 0x00, // CALL NZ, 0010h
+        0xc9, // RET
+    ]),
+    // Jumps with PUSH and RET, then returns
+    (0x0020, &[
+        0x21, 0x28, 0x00, // LD HL, 0028h
+        0xe5, // PUSH HL
+        0xc9, // RET
+        0x00, 0x00, 0x00,
+        0x3c, // INC A
+        0xc9, // RET
+    ]),
+    // Drops its return address, returns to the caller of the caller
+    (0x0030, &[
+        0xcd, 0x34, 0x00, // CALL 0034h
+        0x76, // HALT
+        0xe1, // POP HL
+        0xc9, // RET
+    ]),
+];
+
+fn setup_subroutines() -> (Cpu, PlainMachine) {
+    let (cpu, mut sys) = setup(&[]);
+    for (address, code) in SUBROUTINES {
+        common::load(&mut sys, address, code);
+    }
+    sys.poke(0x0066, 0x04); // INC B
+    sys.poke(0x0067, 0xed); // RETN
+    sys.poke(0x0068, 0x45);
+    (cpu, sys)
+}
+
+#[test]
+fn test_step_over_recursion() {
+    let (mut cpu, mut sys) = setup_subroutines();
+    cpu.registers().set_a(0x00);
+    cpu.registers().set8(Reg8::B, 3);
+
+    assert_eq!(StopReason::Breakpoint(0x0012), cpu.run_to(&mut sys, 0x0012, 100));
+    // The inner calls return to the same address, deeper on the stack
+    assert_eq!(StopReason::Stepped, cpu.step_over(&mut sys, 100));
+    assert_eq!(0x0015, cpu.registers().pc());
+    assert_eq!(0x00fe, cpu.registers().get16(Reg16::SP));
+    assert_eq!(0x03, cpu.registers().a());
+
+    assert_eq!(StopReason::Stepped, cpu.step_out(&mut sys, 100));
+    assert_eq!(0x0006, cpu.registers().pc());
+    assert_eq!(0x0100, cpu.registers().get16(Reg16::SP));
+
+    // Not a call, a single instruction
+    cpu.registers().set_pc(0x0000);
+    assert_eq!(StopReason::Stepped, cpu.step_over(&mut sys, 100));
+    assert_eq!(0x0003, cpu.registers().pc());
+}
+
+#[test]
+fn test_step_over_loops() {
+    let (mut cpu, mut sys) = setup(&[
+        0x06, 0x03, // LD B, 3
+        0x3c, // INC A
+        0x10, 0xfd, // DJNZ -3
+        0xed, 0xb0, // LDIR
+        0x76, // HALT
+    ]);
+    cpu.registers().set_a(0x00);
+    cpu.registers().set16(Reg16::HL, 0x0000);
+    cpu.registers().set16(Reg16::DE, 0x8000);
+    cpu.registers().set16(Reg16::BC, 0x0010);
+
+    assert_eq!(StopReason::Breakpoint(0x0003), cpu.run_to(&mut sys, 0x0003, 100));
+    assert_eq!(StopReason::Stepped, cpu.step_over(&mut sys, 100));
+    assert_eq!(0x0005, cpu.registers().pc());
+    assert_eq!(0x03, cpu.registers().a());
+
+    cpu.registers().set16(Reg16::BC, 0x0008);
+    assert_eq!(StopReason::Stepped, cpu.step_over(&mut sys, 100));
+    assert_eq!(0x0007, cpu.registers().pc());
+    assert_eq!(0x0000, cpu.registers().get16(Reg16::BC));
+    assert_eq!(0x76, sys.peek(0x8007));
+}
+
+#[test]
+fn test_step_with_manual_stack() {
+    let (mut cpu, mut sys) = setup_subroutines();
+    cpu.registers().set_a(0x00);
+
+    // The jump done with RET does not leave the subroutine
+    cpu.registers().set_pc(0x0006);
+    cpu.registers().set16(Reg16::SP, 0x0100);
+    assert_eq!(StopReason::Stepped, cpu.step_over(&mut sys, 100));
+    assert_eq!(0x0009, cpu.registers().pc());
+    assert_eq!(0x01, cpu.registers().a());
+
+    // Returning past the caller ends the step
+    assert_eq!(StopReason::Stepped, cpu.step_over(&mut sys, 100));
+    assert_eq!(0x000c, cpu.registers().pc());
+    assert_eq!(0x0100, cpu.registers().get16(Reg16::SP));
+
+    cpu.registers().set_pc(0x0020);
+    cpu.registers().set16(Reg16::SP, 0x00fe);
+    sys.poke16(0x00fe, 0x0009);
+    assert_eq!(StopReason::Stepped, cpu.step_out(&mut sys, 100));
+    assert_eq!(0x0009, cpu.registers().pc());
+    assert_eq!(0x02, cpu.registers().a());
+    assert_eq!(0x0100, cpu.registers().get16(Reg16::SP));
+}
+
+#[test]
+fn test_step_with_stack_wrapping() {
+    let (mut cpu, mut sys) = setup_subroutines();
+    cpu.registers().set8(Reg8::B, 1);
+    cpu.registers().set_pc(0x0003);
+    cpu.registers().set16(Reg16::SP, 0x0000);
+
+    // The calls push at FFFEh
+    assert_eq!(StopReason::Stepped, cpu.step_over(&mut sys, 100));
+    assert_eq!(0x0006, cpu.registers().pc());
+    assert_eq!(0x0000, cpu.registers().get16(Reg16::SP));
+
+    assert_eq!(StopReason::Breakpoint(0x0020), cpu.run_to(&mut sys, 0x0020, 100));
+    assert_eq!(StopReason::Stepped, cpu.step_out(&mut sys, 100));
+    assert_eq!(0x0009, cpu.registers().pc());
+    assert_eq!(0x0000, cpu.registers().get16(Reg16::SP));
+}
+
+#[test]
+fn test_step_over_interrupt() {
+    let (mut cpu, mut sys) = setup_subroutines();
+    cpu.registers().set_a(0x00);
+    cpu.registers().set8(Reg8::B, 1);
+    cpu.registers().set_pc(0x0003);
+    cpu.registers().set16(Reg16::SP, 0x0100);
+
+    // The NMI is serviced before the call and returns to it. It adds a
+    // level to the recursion.
+    cpu.signal_nmi();
+    assert_eq!(StopReason::Stepped, cpu.step_over(&mut sys, 100));
+    assert_eq!(0x0006, cpu.registers().pc());
+    assert_eq!(0x0100, cpu.registers().get16(Reg16::SP));
+    assert_eq!(0x02, cpu.registers().a());
+
+    // Not a call, the instruction runs after the NMI routine
+    cpu.registers().set_pc(0x0000);
+    cpu.registers().set16(Reg16::SP, 0x0200);
+    cpu.signal_nmi();
+    assert_eq!(StopReason::Stepped, cpu.step_over(&mut sys, 100));
+    assert_eq!(0x0003, cpu.registers().pc());
+    assert_eq!(0x0100, cpu.registers().get16(Reg16::SP));
+    assert_eq!(0x01, cpu.registers().get8(Reg8::B));
+}
+
+#[test]
+fn test_step_limits() {
+    let (mut cpu, mut sys) = setup_subroutines();
+    cpu.registers().set8(Reg8::B, 5);
+    cpu.debugger().add_breakpoint(0x0012);
+
+    cpu.registers().set_pc(0x0003);
+    assert_eq!(StopReason::Breakpoint(0x0012), cpu.step_over(&mut sys, 100));
+    cpu.debugger().clear();
+    assert_eq!(StopReason::InstructionLimit, cpu.step_out(&mut sys, 3));
+    assert_eq!(StopReason::InstructionLimit, cpu.step_over(&mut sys, 0));
+}
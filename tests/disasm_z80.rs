@@ -4,8 +4,8 @@ fn test_disasm_z80(code: &[u8], expected: &str) {
     let mut sys = PlainMachine::new();
     let mut cpu = Cpu::new();
 
-    for i in 0..code.len() {
-        sys.poke(i as u16, code[i]);
+    for (i, e) in code.iter().enumerate() {
+        sys.poke(i as u16, *e);
     }
 
     let disasm = cpu.disasm_instruction(&mut sys);
@@ -26,3 +26,43 @@ fn test_disasm_ld_hl_n() {
 fn test_disasm_ld_ix_d_n() {
     test_disasm_z80(&[0xdd, 0x36, 22, 0x33], "LD (IX+22), 33h");
 }
+
+#[test]
+fn test_disasm_intel_keeps_zilog() {
+    // The Z80 has no Intel mnemonics
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+    sys.poke(0x0000, 0x78);
+
+    assert_eq!("LD A, B", cpu.disasm_instruction_with(&mut sys, Syntax::Intel));
+}
+
+#[test]
+fn test_disasm_at_lengths() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+    let code = [
+        0x00, // NOP
+        0x3e, 0x12, // LD A, 12h
+        0xcd, 0x34, 0x12, // CALL 1234h
+        0x18, 0xfe, // JR +0
+        0xdd, 0x36, 0x05, 0x33, // LD (IX+5), 33h
+        0xfd, 0xcb, 0x02, 0x46, // BIT 0, (IY+2)
+        0xed, 0xb0, // LDIR
+        0xdd, 0xe9, // JP (IX)
+    ];
+    for (i, e) in code.iter().enumerate() {
+        sys.poke(i as u16, *e);
+    }
+
+    let mut address = 0;
+    let mut lengths = Vec::new();
+    while (address as usize) < code.len() {
+        let (_, length) = cpu.disasm_at(&mut sys, address);
+        lengths.push(length);
+        address += length;
+    }
+    assert_eq!(vec![1, 2, 3, 2, 4, 4, 2, 2], lengths);
+    assert_eq!(("LD (IX+5), 33h".to_string(), 4), cpu.disasm_at(&mut sys, 0x0008));
+    assert_eq!(0x0000, cpu.registers().pc());
+}
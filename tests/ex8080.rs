@@ -8,15 +8,14 @@ static CODE: &[u8] = include_bytes!("res/8080EX1.COM");
 
 #[test]
 #[ignore]
+#[allow(clippy::overly_complex_bool_expr)] // The state dump is disabled
 fn test_ex8080() {
     let mut machine = PlainMachine::new();
     let mut cpu = Cpu::new_8080();
 
     // Load program
-    let code = CODE;
-    let size = code.len();
-    for i in 0..size {
-        machine.poke(0x100 + i as u16, code[i]);
+    for (i, e) in CODE.iter().enumerate() {
+        machine.poke(0x100 + i as u16, *e);
     }
 
     /*
@@ -27,8 +26,8 @@ fn test_ex8080() {
     */
     //let code = [0xD3, 0x00, 0xC9];
     let code = [0xC9];
-    for i in 0..code.len() {
-        machine.poke(5 + i as u16, code[i]);
+    for (i, e) in code.iter().enumerate() {
+        machine.poke(5 + i as u16, *e);
     }
 
     // Patch to run a single test
@@ -0,0 +1,402 @@
+use iz80::*;
+
+// 24 bit address space, as on the Agon Light
+struct AgonMachine {
+    mem: Vec<u8>,
+}
+
+impl AgonMachine {
+    fn new() -> AgonMachine {
+        AgonMachine {
+            mem: vec![0; 0x1000000],
+        }
+    }
+
+    fn load(&mut self, address: u32, code: &[u8]) {
+        let start = address as usize;
+        self.mem[start..start + code.len()].copy_from_slice(code);
+    }
+}
+
+impl Machine for AgonMachine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.mem[address as usize]
+    }
+    fn poke(&mut self, address: u16, value: u8) {
+        self.mem[address as usize] = value;
+    }
+
+    fn peek_physical(&mut self, address: u32) -> u8 {
+        self.mem[address as usize]
+    }
+    fn poke_physical(&mut self, address: u32, value: u8) {
+        self.mem[address as usize] = value;
+    }
+
+    fn port_in(&mut self, _address: u16) -> u8 {
+        0
+    }
+    fn port_out(&mut self, _address: u16, _value: u8) {}
+}
+
+fn setup(code: &[u8], adl: bool) -> (Cpu, AgonMachine) {
+    let mut sys = AgonMachine::new();
+    let mut cpu = Cpu::new_ez80();
+    cpu.registers().set_adl(adl);
+    sys.load(0, code);
+    (cpu, sys)
+}
+
+#[test]
+fn test_ld_rr_nn_adl() {
+    let (mut cpu, mut sys) = setup(&[
+        0x21, 0x56, 0x34, 0x12, // LD HL, 123456h
+        0xdd, 0x21, 0x03, 0x02, 0x01, // LD IX, 010203h
+        0x31, 0x00, 0x00, 0x05, // LD SP, 050000h
+    ], true);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x123456, cpu.registers().get24(Reg16::HL));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x010203, cpu.registers().get24(Reg16::IX));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x050000, cpu.registers().get24(Reg16::SP));
+    assert_eq!(0xffff, cpu.registers().get16(Reg16::SP)); // SPS unchanged
+    assert_eq!(13, cpu.registers().pc24());
+}
+
+#[test]
+fn test_z80_mode_mbase() {
+    let mut sys = AgonMachine::new();
+    let mut cpu = Cpu::new_ez80();
+    sys.load(0x120100, &[
+        0x7e, // LD A, (HL)
+        0x21, 0x00, 0x20, // LD HL, 2000h
+        0x77, // LD (HL), A
+    ]);
+    sys.poke_physical(0x123456, 0xaa);
+    cpu.registers().set_mbase(0x12);
+    cpu.registers().set_pc(0x0100);
+    cpu.registers().set24(Reg16::HL, 0x773456);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xaa, cpu.registers().a());
+
+    // The upper byte is kept on Z80 mode
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x772000, cpu.registers().get24(Reg16::HL));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xaa, sys.peek_physical(0x122000));
+    assert_eq!(0x120105, cpu.registers().pc24());
+}
+
+#[test]
+fn test_suffixes() {
+    let (mut cpu, mut sys) = setup(&[
+        0x5b, 0x21, 0x56, 0x34, 0x12, // LD.LIL HL, 123456h
+        0x49, 0x7e, // LD.LIS A, (HL)
+        0x7e, // LD A, (HL)
+    ], false);
+    sys.poke_physical(0x123456, 0x55);
+    sys.poke_physical(0x003456, 0x66);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x123456, cpu.registers().get24(Reg16::HL));
+    assert_eq!(5, cpu.registers().pc());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x55, cpu.registers().a());
+
+    // The suffix is for one instruction only
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x66, cpu.registers().a());
+    assert!(!cpu.registers().is_adl());
+}
+
+#[test]
+fn test_jp_lil_sis_switch_mode() {
+    let (mut cpu, mut sys) = setup(&[
+        0x5b, 0xc3, 0x00, 0x00, 0x04, // JP.LIL 040000h
+    ], false);
+    sys.load(0x040000, &[
+        0x40, 0xc3, 0x00, 0x10, // JP.SIS 1000h
+    ]);
+
+    cpu.execute_instruction(&mut sys);
+    assert!(cpu.registers().is_adl());
+    assert_eq!(0x040000, cpu.registers().pc24());
+
+    cpu.execute_instruction(&mut sys);
+    assert!(!cpu.registers().is_adl());
+    assert_eq!(0x1000, cpu.registers().pc());
+}
+
+#[test]
+fn test_push_pop_call_ret_adl() {
+    let (mut cpu, mut sys) = setup(&[
+        0xc5, // PUSH BC
+        0xd1, // POP DE
+        0xcd, 0x00, 0x00, 0x02, // CALL 020000h
+    ], true);
+    sys.load(0x020000, &[
+        0xc9, // RET
+    ]);
+    cpu.registers().set24(Reg16::SP, 0x030000);
+    cpu.registers().set24(Reg16::BC, 0xabcdef);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x02fffd, cpu.registers().get24(Reg16::SP));
+    assert_eq!(0xab, sys.peek_physical(0x02ffff));
+    assert_eq!(0xef, sys.peek_physical(0x02fffd));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xabcdef, cpu.registers().get24(Reg16::DE));
+    assert_eq!(0x030000, cpu.registers().get24(Reg16::SP));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x020000, cpu.registers().pc24());
+    assert_eq!(0x02fffd, cpu.registers().get24(Reg16::SP));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x000006, cpu.registers().pc24());
+    assert_eq!(0x030000, cpu.registers().get24(Reg16::SP));
+}
+
+#[test]
+fn test_im2_adl() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x5e, // IM 2
+        0xfb, // EI
+        0x00, // NOP
+    ], true);
+    sys.load(0x001220, &[0x00, 0x00, 0x02]); // Vector table entry
+    sys.load(0x020000, &[
+        0x00, // NOP
+        0xed, 0x4d, // RETI
+    ]);
+    cpu.registers().set8(Reg8::I, 0x12);
+    cpu.registers().set24(Reg16::SP, 0x030000);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.set_interrupt_data(0x20);
+    cpu.signal_interrupt(true);
+    cpu.execute_instruction(&mut sys);
+    cpu.signal_interrupt(false);
+    assert_eq!(0x020001, cpu.registers().pc24());
+    assert_eq!(0x02fffd, cpu.registers().get24(Reg16::SP));
+    assert_eq!([0x04, 0x00, 0x00], sys.mem[0x02fffd..0x030000]);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x000004, cpu.registers().pc24());
+    assert_eq!(0x030000, cpu.registers().get24(Reg16::SP));
+}
+
+#[test]
+fn test_breakpoint_adl() {
+    let (mut cpu, mut sys) = setup(&[
+        0xc3, 0x00, 0x00, 0x02, // JP 020000h
+    ], true);
+    sys.load(0x020000, &[
+        0x00, // NOP
+        0x00, // NOP
+        0x76, // HALT
+    ]);
+
+    // The breakpoints compare the 24 bits of PC, 000001h is not hit
+    cpu.debugger().add_breakpoint(0x000001);
+    cpu.debugger().add_breakpoint(0x020002);
+    assert_eq!(StopReason::Breakpoint(0x020002), cpu.run(&mut sys, 100));
+}
+
+#[test]
+fn test_lea_pea() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x02, 0x05, // LEA BC, IX+5
+        0xed, 0x55, 0xfe, // LEA IY, IX-2
+        0xed, 0x66, 0x10, // PEA IY+10h
+    ], true);
+    cpu.registers().set24(Reg16::IX, 0x10fffe);
+    cpu.registers().set24(Reg16::SP, 0x030000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x110003, cpu.registers().get24(Reg16::BC));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x10fffc, cpu.registers().get24(Reg16::IY));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x02fffd, cpu.registers().get24(Reg16::SP));
+    assert_eq!(0x0c, sys.peek_physical(0x02fffd));
+    assert_eq!(0x00, sys.peek_physical(0x02fffe));
+    assert_eq!(0x11, sys.peek_physical(0x02ffff));
+}
+
+#[test]
+fn test_ld_rr_phl() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x17, // LD DE, (HL)
+        0xed, 0x3e, // LD (HL), IY
+        0x40, 0xed, 0x07, // LD.SIS BC, (HL)
+    ], true);
+    sys.load(0x050000, &[0x11, 0x22, 0x33]);
+    cpu.registers().set24(Reg16::HL, 0x050000);
+    cpu.registers().set24(Reg16::IY, 0x445566);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x332211, cpu.registers().get24(Reg16::DE));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x66, sys.peek_physical(0x050000));
+    assert_eq!(0x44, sys.peek_physical(0x050002));
+
+    // On 16 bits with MBASE as the upper byte
+    sys.load(0x000000, &[0x77, 0x88]);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x8877, cpu.registers().get16(Reg16::BC));
+}
+
+#[test]
+fn test_mlt_tst() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x4c, // MLT BC
+        0xed, 0x64, 0x0f, // TST A, 0Fh
+        0xed, 0x0c, // TST A, C
+    ], false);
+    cpu.registers().set16(Reg16::BC, 0x1234);
+    cpu.registers().set_a(0xf0);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x03a8, cpu.registers().get16(Reg16::BC));
+
+    cpu.execute_instruction(&mut sys);
+    assert!(cpu.registers().get_flag(Flag::Z));
+
+    cpu.execute_instruction(&mut sys);
+    assert!(!cpu.registers().get_flag(Flag::Z));
+    assert_eq!(0xf0, cpu.registers().a());
+}
+
+#[test]
+fn test_add_hl_24_bits() {
+    let (mut cpu, mut sys) = setup(&[
+        0x09, // ADD HL, BC
+        0x09, // ADD HL, BC
+        0xed, 0x42, // SBC HL, BC
+    ], true);
+    cpu.registers().set24(Reg16::HL, 0x7fffff);
+    cpu.registers().set24(Reg16::BC, 0x800001);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x000000, cpu.registers().get24(Reg16::HL));
+    assert!(cpu.registers().get_flag(Flag::C));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x800001, cpu.registers().get24(Reg16::HL));
+    assert!(!cpu.registers().get_flag(Flag::C));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x000000, cpu.registers().get24(Reg16::HL));
+    assert!(cpu.registers().get_flag(Flag::Z));
+    assert!(!cpu.registers().get_flag(Flag::C));
+}
+
+#[test]
+fn test_exx_and_jr_adl() {
+    let mut sys = AgonMachine::new();
+    let mut cpu = Cpu::new_ez80();
+    cpu.registers().set_adl(true);
+    cpu.registers().set_pc24(0x00fffe);
+    sys.load(0x00fffe, &[
+        0xd9, // EXX
+        0x18, 0x10, // JR +12h
+    ]);
+    cpu.registers().set24(Reg16::HL, 0x123456);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x000000, cpu.registers().get24(Reg16::HL) & 0xff0000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x010011, cpu.registers().pc24());
+
+    cpu.registers().set_pc24(0x010011);
+    sys.poke_physical(0x010011, 0xd9); // EXX
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x123456, cpu.registers().get24(Reg16::HL));
+}
+
+#[test]
+fn test_cycles() {
+    let (mut cpu, mut sys) = setup(&[
+        0x00, // NOP
+        0x21, 0x00, 0x00, // LD HL, 0000h
+        0x5b, 0x21, 0x00, 0x00, 0x00, // LD.LIL HL, 000000h
+        0xc3, 0x0c, 0x00, // JP 000Ch
+        0xed, 0x4c, // MLT BC
+        0x34, // INC (HL)
+    ], false);
+
+    let mut cycles = Vec::new();
+    for _ in 0..6 {
+        let before = cpu.cycle_count();
+        cpu.execute_instruction(&mut sys);
+        cycles.push(cpu.cycle_count() - before);
+    }
+    assert_eq!(vec![1, 3, 5, 4, 6, 4], cycles);
+}
+
+#[test]
+fn test_disasm() {
+    let (mut cpu, mut sys) = setup(&[
+        0x5b, 0x21, 0x56, 0x34, 0x12, // LD.LIL HL, 123456h
+        0xed, 0x02, 0x05, // LEA BC, IX+5
+        0xed, 0x17, // LD DE, (HL)
+        0xed, 0x4c, // MLT BC
+        0xcd, 0x00, 0x10, // CALL 1000h
+    ], false);
+
+    let mut disasm = Vec::new();
+    for pc in [0x0000, 0x0005, 0x0008, 0x000a, 0x000c] {
+        cpu.registers().set_pc(pc);
+        disasm.push(cpu.disasm_instruction(&mut sys));
+    }
+    assert_eq!(vec![
+        "LD.LIL HL, 123456h",
+        "LEA BC, IX+05h",
+        "LD DE, (HL)",
+        "MLT BC",
+        "CALL 1000h",
+    ], disasm);
+}
+
+#[test]
+fn test_af_without_upper_byte() {
+    let (mut cpu, mut sys) = setup(&[
+        0x08, // EX AF, AF'
+    ], true);
+
+    cpu.registers().set24(Reg16::AF, 0x123456);
+    assert_eq!(0x003456, cpu.registers().get24(Reg16::AF));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x003456, cpu.registers().get16_shadow(Reg16::AF) as u32);
+}
+
+#[test]
+fn test_serialize_ez80_registers() {
+    let (mut cpu, _) = setup(&[], true);
+    cpu.registers().set24(Reg16::HL, 0x123456);
+    cpu.registers().set24(Reg16::SP, 0x0abcde);
+    cpu.registers().set_mbase(0x42);
+    cpu.registers().set_pc24(0x0a0000);
+    let data = cpu.serialize();
+
+    let mut restored = Cpu::new_ez80();
+    restored.deserialize(&data).unwrap();
+    assert!(restored.registers().is_adl());
+    assert_eq!(0x123456, restored.registers().get24(Reg16::HL));
+    assert_eq!(0x0abcde, restored.registers().get24(Reg16::SP));
+    assert_eq!(0x42, restored.registers().mbase());
+    assert_eq!(0x0a0000, restored.registers().pc24());
+}
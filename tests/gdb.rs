@@ -0,0 +1,280 @@
+#![cfg(feature = "gdb")]
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+use iz80::*;
+
+mod common;
+
+// Client replaying a script, without network
+struct ScriptedClient {
+    input: VecDeque<u8>,
+    output: Vec<u8>,
+}
+
+impl Read for ScriptedClient {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.input.pop_front() {
+            Some(b) if !buf.is_empty() => {
+                buf[0] = b;
+                Ok(1)
+            },
+            _ => Ok(0),
+        }
+    }
+}
+
+impl Write for ScriptedClient {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Connection for ScriptedClient {
+    fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.input.front().copied())
+    }
+}
+
+fn packet(data: &str) -> String {
+    let sum = data.bytes().fold(0_u8, |sum, b| sum.wrapping_add(b));
+    format!("${data}#{sum:02x}")
+}
+
+// Sends the requests acknowledging the replies, returns the exit and the
+// replies
+fn session(cpu: &mut Cpu, sys: &mut PlainMachine, requests: &[&str]) -> (GdbExit, Vec<String>) {
+    let mut script = String::new();
+    for request in requests {
+        if *request == "\x03" {
+            // Sent while running, before acknowledging the stop reply
+            script.pop();
+            script.push_str("\x03+");
+        } else {
+            script.push_str(&packet(request));
+            script.push('+');
+        }
+    }
+    let client = ScriptedClient {
+        input: script.bytes().collect(),
+        output: Vec::new(),
+    };
+    let mut stub = GdbStub::new(client);
+    let exit = stub.serve(cpu, sys).unwrap();
+
+    let output = String::from_utf8(stub.into_inner().output).unwrap();
+    let replies = output.split('$').skip(1)
+        .map(|r| r.split('#').next().unwrap().to_string())
+        .collect();
+    (exit, replies)
+}
+
+fn setup(code: &[u8]) -> (Cpu, PlainMachine) {
+    common::setup(Cpu::new(), code)
+}
+
+#[test]
+fn test_handshake() {
+    let (mut cpu, mut sys) = setup(&[]);
+
+    let (exit, replies) = session(&mut cpu, &mut sys, &[
+        "qSupported:multiprocess+;swbreak+",
+        "Hg0",
+        "qAttached",
+        "?",
+        "vMustReplyEmpty",
+        "D",
+    ]);
+    assert_eq!(GdbExit::Detached, exit);
+    assert_eq!(vec!["PacketSize=1000;QStartNoAckMode+", "OK", "1", "S05", "", "OK"], replies);
+}
+
+#[test]
+fn test_registers() {
+    let (mut cpu, mut sys) = setup(&[]);
+    cpu.registers().set16(Reg16::AF, 0x1234);
+    cpu.registers().set16(Reg16::SP, 0xfffe);
+    cpu.registers().set_pc(0x0100);
+    cpu.registers().set16_shadow(Reg16::HL, 0xabcd);
+    cpu.registers().set8(Reg8::I, 0x3f);
+
+    // Register n set to (n + 1) << 8
+    let values: String = (1..=13).map(|n| format!("00{n:02x}")).collect();
+    let (exit, replies) = session(&mut cpu, &mut sys, &[
+        "g",
+        "p5",
+        "P3=3412",
+        "Pb=0100",
+        "Pd=0000",
+        &format!("G{values}"),
+        "k",
+    ]);
+    assert_eq!(GdbExit::Killed, exit);
+    assert_eq!(vec![
+        "3412000000000000feff000100000000000000000000cdab003f".to_string(),
+        "0001".to_string(),
+        "OK".to_string(),
+        "OK".to_string(),
+        "E01".to_string(),
+        "OK".to_string(),
+    ], replies[..6]);
+    assert_eq!(0x0100, cpu.registers().get16(Reg16::AF));
+    assert_eq!(0x0600, cpu.registers().pc());
+    assert_eq!(0x0b00, cpu.registers().get16_shadow(Reg16::DE));
+    assert_eq!(0x0d, cpu.registers().get8(Reg8::I));
+    assert_eq!(0x00, cpu.registers().get8(Reg8::R));
+}
+
+#[test]
+fn test_memory() {
+    let (mut cpu, mut sys) = setup(&[0x3e, 0x12, 0x76]);
+
+    let (_, replies) = session(&mut cpu, &mut sys, &[
+        "m0,3",
+        "M8000,2:cafe",
+        "M8000,3:00",
+        "m7fff,3",
+        "m0,10000",
+    ]);
+    assert_eq!(vec!["3e1276", "OK", "E01", "00cafe"], replies[..4]);
+    assert_eq!(0xfe, sys.peek(0x8001));
+    // Truncated to the packet size, not to 16 bits
+    assert_eq!(0x1000, replies[4].len());
+    assert!(replies[4].starts_with("3e1276"));
+}
+
+#[test]
+fn test_step_and_breakpoints() {
+    let (mut cpu, mut sys) = setup(&[
+        0x3c, // INC A
+        0x3c, // INC A
+        0x3c, // INC A
+        0x18, 0xfb, // JR 0000h
+    ]);
+    cpu.registers().set_a(0x00);
+
+    let (_, replies) = session(&mut cpu, &mut sys, &[
+        "s",
+        "Z0,2,1",
+        "c",
+        "p5",
+        "c",
+        "p5",
+        "z0,2,1",
+        "Z1,1,1",
+        "c",
+        "p5",
+    ]);
+    assert_eq!(vec!["S05", "OK", "S05", "0200", "S05", "0200", "OK", "OK", "S05", "0100"], replies);
+    assert_eq!(0x07, cpu.registers().a());
+}
+
+#[test]
+fn test_watchpoint() {
+    let (mut cpu, mut sys) = setup(&[
+        0x3a, 0x00, 0x80, // LD A, (8000h)
+        0x32, 0x01, 0x80, // LD (8001h), A
+        0x76, // HALT
+    ]);
+
+    let (_, replies) = session(&mut cpu, &mut sys, &[
+        "Z2,8001,1",
+        "Z3,8000,1",
+        "c",
+        "c",
+        "z2,8001,1",
+        "c",
+    ]);
+    assert_eq!(vec!["OK", "OK", "T05rwatch:8000;", "T05watch:8001;", "OK", "S05"], replies);
+    assert!(cpu.is_halted());
+}
+
+#[test]
+fn test_watchpoint_length() {
+    let (mut cpu, mut sys) = setup(&[
+        0x22, 0x02, 0x80, // LD (8002h), HL
+        0x22, 0x04, 0x80, // LD (8004h), HL
+        0x76, // HALT
+    ]);
+
+    let (_, replies) = session(&mut cpu, &mut sys, &[
+        "Z2,8000,4",
+        "c",
+        "z2,8000,4",
+        "c",
+    ]);
+    assert_eq!(vec!["OK", "T05watch:8002;", "OK", "S05"], replies);
+    assert!(cpu.debugger().watchpoints().is_empty());
+    assert!(cpu.is_halted());
+}
+
+#[test]
+fn test_overlapping_watchpoints() {
+    let (mut cpu, mut sys) = setup(&[
+        0x22, 0x02, 0x80, // LD (8002h), HL
+        0x76, // HALT
+    ]);
+
+    let (_, replies) = session(&mut cpu, &mut sys, &[
+        "Z2,8000,4",
+        "Z2,8002,2",
+        "z2,8000,4",
+        "c",
+    ]);
+    assert_eq!(vec!["OK", "OK", "OK", "T05watch:8002;"], replies);
+    assert_eq!(&[Watchpoint::MemoryWrite(0x8002), Watchpoint::MemoryWrite(0x8003)],
+        cpu.debugger().watchpoints());
+}
+
+#[test]
+fn test_port_watchpoint() {
+    let (mut cpu, mut sys) = setup(&[
+        0xd3, 0x10, // OUT (10h), A
+        0x76, // HALT
+    ]);
+    cpu.registers().set_a(0x12);
+    cpu.debugger().add_watchpoint(Watchpoint::PortOut(0x1210));
+
+    let (_, replies) = session(&mut cpu, &mut sys, &[
+        "c",
+        "p5",
+    ]);
+    assert_eq!(vec!["S05", "0200"], replies);
+}
+
+#[test]
+fn test_interrupt() {
+    let (mut cpu, mut sys) = setup(&[
+        0x18, 0xfe, // JR 0000h
+    ]);
+
+    let (_, replies) = session(&mut cpu, &mut sys, &[
+        "c",
+        "\x03",
+        "p5",
+    ]);
+    assert_eq!(vec!["S02", "0000"], replies);
+}
+
+#[test]
+fn test_acknowledges() {
+    let (mut cpu, mut sys) = setup(&[]);
+
+    // A request with a wrong checksum is rejected, a reply not
+    // acknowledged is sent again
+    let script = format!("$?#00{}-+{}+{}", packet("?"), packet("QStartNoAckMode"), packet("qAttached"));
+    let client = ScriptedClient {
+        input: script.bytes().collect(),
+        output: Vec::new(),
+    };
+    let mut stub = GdbStub::new(client);
+    assert_eq!(GdbExit::Disconnected, stub.serve(&mut cpu, &mut sys).unwrap());
+
+    let output = String::from_utf8(stub.into_inner().output).unwrap();
+    assert_eq!(format!("-+{}{}+{}{}", packet("S05"), packet("S05"), packet("OK"), packet("1")), output);
+}
@@ -109,3 +109,269 @@ fn test_ei_on_handler_int_not_lowered() {
     // On the handler again, as interrupts are raised and enabled
     assert_eq!(IRQ_ADDRESS+1, cpu.registers().pc());
 }
+
+#[test]
+fn test_im2() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_z80();
+
+    sys.poke(0x0000, 0x3e); // LD A, 12h
+    sys.poke(0x0001, 0x12);
+    sys.poke(0x0002, 0xed); // LD I, A
+    sys.poke(0x0003, 0x47);
+    sys.poke(0x0004, 0xed); // IM 2
+    sys.poke(0x0005, 0x5e);
+    sys.poke(0x0006, 0xfb); // EI
+    sys.poke16(0x1234, 0x4000); // Vector table entry
+    sys.poke(0x4000, 0x00); // NOP
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.set_interrupt_data(0x34);
+    cpu.signal_interrupt(true);
+    let cycles = cpu.cycle_count();
+    cpu.execute_instruction(&mut sys);
+
+    // On the handler, NOP executed
+    assert_eq!(0x4001, cpu.registers().pc());
+    assert_eq!(0x0008, sys.peek16(cpu.registers().get16(Reg16::SP)));
+    assert_eq!(19 + 4, cpu.cycle_count() - cycles);
+
+    assert!(cpu.set_interrupt_instruction(&[0xdd, 0xcd, 0x34, 0x12, 0x00]).is_err());
+}
+
+#[test]
+fn test_im0_rst() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_z80();
+
+    sys.poke(0x0000, 0xfb); // EI
+    sys.poke(0x0028, 0x00); // NOP
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.set_interrupt_data(0xef); // RST 28h
+    cpu.signal_interrupt(true);
+    let cycles = cpu.cycle_count();
+    cpu.execute_instruction(&mut sys);
+
+    // On the handler, NOP executed
+    assert_eq!(0x0029, cpu.registers().pc());
+    assert_eq!(0x0002, sys.peek16(cpu.registers().get16(Reg16::SP)));
+    assert_eq!(13 + 4, cpu.cycle_count() - cycles);
+}
+
+#[test]
+fn test_im0_call() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_z80();
+
+    sys.poke(0x0000, 0xfb); // EI
+    sys.poke(0x1234, 0x00); // NOP
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.set_interrupt_instruction(&[0xcd, 0x34, 0x12]).unwrap(); // CALL 1234h
+    cpu.signal_interrupt(true);
+    let cycles = cpu.cycle_count();
+    cpu.execute_instruction(&mut sys);
+
+    // On the handler, NOP executed
+    assert_eq!(0x1235, cpu.registers().pc());
+    assert_eq!(0x0002, sys.peek16(cpu.registers().get16(Reg16::SP)));
+    assert_eq!(19 + 4, cpu.cycle_count() - cycles);
+}
+
+#[test]
+fn test_8080_interrupt_rst() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8080();
+
+    sys.poke(0x0000, 0xfb); // EI
+    sys.poke(0x0010, 0x00); // NOP
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.set_interrupt_data(0xd7); // RST 10h
+    cpu.signal_interrupt(true);
+    let cycles = cpu.cycle_count();
+    cpu.execute_instruction(&mut sys);
+
+    // On the handler, NOP executed
+    assert_eq!(0x0011, cpu.registers().pc());
+    assert_eq!(0x0002, sys.peek16(cpu.registers().get16(Reg16::SP)));
+    assert_eq!(11 + 4, cpu.cycle_count() - cycles);
+}
+
+struct AckMachine {
+    plain: PlainMachine,
+    bus: Vec<u8>,
+    acks: usize,
+    retis: usize,
+    retns: usize,
+}
+
+impl Machine for AckMachine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.plain.peek(address)
+    }
+    fn poke(&mut self, address: u16, value: u8) {
+        self.plain.poke(address, value);
+    }
+
+    fn port_in(&mut self, address: u16) -> u8 {
+        self.plain.port_in(address)
+    }
+    fn port_out(&mut self, address: u16, value: u8) {
+        self.plain.port_out(address, value);
+    }
+
+    fn interrupt_ack(&mut self) -> Option<Vec<u8>> {
+        self.acks += 1;
+        Some(self.bus.clone())
+    }
+
+    fn reti(&mut self) {
+        self.retis += 1;
+    }
+
+    fn retn(&mut self) {
+        self.retns += 1;
+    }
+}
+
+#[test]
+fn test_interrupt_ack_im0() {
+    let mut sys = AckMachine {
+        plain: PlainMachine::new(),
+        bus: vec![0xcd, 0x34, 0x12], // CALL 1234h
+        acks: 0,
+        retis: 0,
+        retns: 0,
+    };
+    let mut cpu = Cpu::new_z80();
+
+    sys.poke(0x0000, 0xfb); // EI
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0, sys.acks);
+    cpu.set_interrupt_data(0xd7); // Ignored, the machine provides the data
+    cpu.signal_interrupt(true);
+    cpu.execute_instruction(&mut sys);
+
+    // Acknowledged once for the whole instruction
+    assert_eq!(1, sys.acks);
+    assert_eq!(0x1235, cpu.registers().pc());
+    assert_eq!(0x0002, sys.peek16(cpu.registers().get16(Reg16::SP)));
+}
+
+#[test]
+fn test_interrupt_ack_im2() {
+    let mut sys = AckMachine {
+        plain: PlainMachine::new(),
+        bus: vec![0x20],
+        acks: 0,
+        retis: 0,
+        retns: 0,
+    };
+    let mut cpu = Cpu::new_z80();
+
+    sys.poke(0x0000, 0xed); // IM 2
+    sys.poke(0x0001, 0x5e);
+    sys.poke(0x0002, 0xfb); // EI
+    sys.poke16(0x0020, 0x4000); // Vector table entry, I is 0
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.set_interrupt_data(0x10); // Ignored, the machine provides the data
+    cpu.signal_interrupt(true);
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(1, sys.acks);
+    assert_eq!(0x4001, cpu.registers().pc());
+}
+
+#[test]
+fn test_reti_retn_notification() {
+    let mut sys = AckMachine {
+        plain: PlainMachine::new(),
+        bus: Vec::new(),
+        acks: 0,
+        retis: 0,
+        retns: 0,
+    };
+    let mut cpu = Cpu::new_z80();
+
+    sys.poke(0x0000, 0xed); // RETI
+    sys.poke(0x0001, 0x4d);
+    sys.poke(0x0002, 0xed); // RETN
+    sys.poke(0x0003, 0x45);
+    sys.poke(0x0004, 0xc9); // RET
+    sys.poke16(0x1000, 0x0002);
+    sys.poke16(0x1002, 0x0004);
+    sys.poke16(0x1004, 0x0000);
+    cpu.registers().set16(Reg16::SP, 0x1000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!((1, 0), (sys.retis, sys.retns));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!((1, 1), (sys.retis, sys.retns));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!((1, 1), (sys.retis, sys.retns));
+}
+
+// Returns P/V after an interrupt accepted at the end of LD A, I with
+// 0x57 or LD A, R with 0x5f
+fn ld_a_ir_interrupted(model: CpuModel, opcode: u8) -> bool {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_z80();
+    cpu.set_model(model);
+
+    sys.poke(0x0000, 0xfb); // EI
+    sys.poke(0x0001, 0xed); // IM 1
+    sys.poke(0x0002, 0x56);
+    sys.poke(0x0003, 0xed); // LD A, I or LD A, R
+    sys.poke(0x0004, opcode);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert!(cpu.registers().get_flag(Flag::P));
+
+    // The interrupt is accepted at the end of LD A, I or LD A, R
+    cpu.signal_interrupt(true);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(IRQ_ADDRESS+1, cpu.registers().pc());
+    cpu.registers().get_flag(Flag::P)
+}
+
+// The NMOS parts clear IFF2 before P/V is set, the CMOS parts do not
+
+#[test]
+fn test_ld_a_ir_interrupted_zilog_nmos() {
+    assert!(!ld_a_ir_interrupted(CpuModel::ZilogNmos, 0x57));
+    assert!(!ld_a_ir_interrupted(CpuModel::ZilogNmos, 0x5f));
+}
+
+#[test]
+fn test_ld_a_ir_interrupted_nec_nmos() {
+    assert!(!ld_a_ir_interrupted(CpuModel::NecNmos, 0x57));
+    assert!(!ld_a_ir_interrupted(CpuModel::NecNmos, 0x5f));
+}
+
+#[test]
+fn test_ld_a_ir_interrupted_zilog_cmos() {
+    assert!(ld_a_ir_interrupted(CpuModel::ZilogCmos, 0x57));
+    assert!(ld_a_ir_interrupted(CpuModel::ZilogCmos, 0x5f));
+}
+
+#[test]
+fn test_ld_a_ir_interrupted_st_cmos() {
+    assert!(ld_a_ir_interrupted(CpuModel::StCmos, 0x57));
+    assert!(ld_a_ir_interrupted(CpuModel::StCmos, 0x5f));
+}
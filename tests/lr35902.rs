@@ -0,0 +1,357 @@
+use iz80::*;
+
+mod common;
+
+const IE: u16 = 0xffff;
+const IF: u16 = 0xff0f;
+
+fn setup(code: &[u8]) -> (Cpu, PlainMachine) {
+    let (mut cpu, sys) = common::setup(Cpu::new_lr35902(), code);
+    cpu.registers().set16(Reg16::SP, 0xfffe);
+    (cpu, sys)
+}
+
+#[test]
+fn test_flags_layout() {
+    let (mut cpu, mut sys) = setup(&[
+        0xaf, // XOR A
+        0xd6, 0x01, // SUB 1
+    ]);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x80, cpu.registers().get8(Reg8::F)); // Z
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xff, cpu.registers().a());
+    assert_eq!(0x70, cpu.registers().get8(Reg8::F)); // N H C
+    assert!(cpu.registers().get_flag(Flag::C));
+    assert!(!cpu.registers().get_flag(Flag::S));
+    assert!(!cpu.registers().get_flag(Flag::P));
+}
+
+#[test]
+fn test_pop_af_low_nibble() {
+    let (mut cpu, mut sys) = setup(&[
+        0xf1, // POP AF
+    ]);
+    cpu.registers().set16(Reg16::SP, 0xc000);
+    sys.poke16(0xc000, 0x12ff);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x12f0, cpu.registers().get16(Reg16::AF));
+    assert_eq!(3, cpu.cycle_count());
+}
+
+#[test]
+fn test_ldi_ldd() {
+    let (mut cpu, mut sys) = setup(&[
+        0x22, // LDI (HL), A
+        0x3a, // LDD A, (HL)
+    ]);
+    cpu.registers().set16(Reg16::HL, 0xc000);
+    cpu.registers().set_a(0x55);
+    sys.poke(0xc001, 0xaa);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x55, sys.peek(0xc000));
+    assert_eq!(0xc001, cpu.registers().get16(Reg16::HL));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xaa, cpu.registers().a());
+    assert_eq!(0xc000, cpu.registers().get16(Reg16::HL));
+    assert_eq!(4, cpu.cycle_count());
+}
+
+#[test]
+fn test_ldh() {
+    let (mut cpu, mut sys) = setup(&[
+        0xe0, 0x80, // LDH (80h), A
+        0xf2, // LDH A, (C)
+    ]);
+    cpu.registers().set_a(0x12);
+    cpu.registers().set8(Reg8::C, 0x81);
+    sys.poke(0xff81, 0x34);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x12, sys.peek(0xff80));
+    assert_eq!(3, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x34, cpu.registers().a());
+    assert_eq!(5, cpu.cycle_count());
+}
+
+#[test]
+fn test_ld_pnn_sp() {
+    let (mut cpu, mut sys) = setup(&[
+        0x08, 0x00, 0xc0, // LD (C000h), SP
+    ]);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xfffe, sys.peek16(0xc000));
+    assert_eq!(5, cpu.cycle_count());
+}
+
+#[test]
+fn test_add_sp_s() {
+    let (mut cpu, mut sys) = setup(&[
+        0xe8, 0xff, // ADD SP, -1
+        0xf8, 0x02, // LD HL, SP+2
+    ]);
+    cpu.registers().set16(Reg16::SP, 0x00ff);
+    cpu.registers().set_flag(Flag::Z);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x00fe, cpu.registers().get16(Reg16::SP));
+    // Carries from the unsigned addition of FFh to FFh
+    assert_eq!(0x30, cpu.registers().get8(Reg8::F));
+    assert_eq!(4, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0100, cpu.registers().get16(Reg16::HL));
+    assert_eq!(0x00fe, cpu.registers().get16(Reg16::SP));
+    assert_eq!(0x30, cpu.registers().get8(Reg8::F));
+    assert_eq!(7, cpu.cycle_count());
+}
+
+#[test]
+fn test_swap() {
+    let (mut cpu, mut sys) = setup(&[
+        0xcb, 0x37, // SWAP A
+        0xcb, 0x36, // SWAP (HL)
+    ]);
+    cpu.registers().set_a(0x12);
+    cpu.registers().set_flag(Flag::C);
+    cpu.registers().set16(Reg16::HL, 0xc000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x21, cpu.registers().a());
+    assert_eq!(0x00, cpu.registers().get8(Reg8::F));
+    assert_eq!(2, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x00, sys.peek(0xc000));
+    assert_eq!(0x80, cpu.registers().get8(Reg8::F));
+    assert_eq!(6, cpu.cycle_count());
+}
+
+#[test]
+fn test_rla_clears_z() {
+    let (mut cpu, mut sys) = setup(&[
+        0x17, // RLA
+    ]);
+    cpu.registers().set_a(0x80);
+    cpu.registers().set16(Reg16::AF, 0x8080);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x00, cpu.registers().a());
+    assert_eq!(0x10, cpu.registers().get8(Reg8::F)); // Only C
+}
+
+#[test]
+fn test_daa() {
+    let (mut cpu, mut sys) = setup(&[
+        0xc6, 0x38, // ADD A, 38h
+        0x27, // DAA
+        0xd6, 0x49, // SUB 49h
+        0x27, // DAA
+    ]);
+    cpu.registers().set_a(0x45);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x83, cpu.registers().a());
+    assert!(!cpu.registers().get_flag(Flag::C));
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x34, cpu.registers().a());
+    assert_eq!(0x40, cpu.registers().get8(Reg8::F)); // N kept
+}
+
+#[test]
+fn test_ccf() {
+    let (mut cpu, mut sys) = setup(&[
+        0x3f, // CCF
+    ]);
+    cpu.registers().set16(Reg16::AF, 0x00f0);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x80, cpu.registers().get8(Reg8::F)); // H cleared
+}
+
+#[test]
+fn test_no_prefixes() {
+    let (mut cpu, mut sys) = setup(&[
+        0xfb, // EI
+        0xed, // Illegal, the CPU hangs
+    ]);
+    sys.poke(IE, 0x01);
+    sys.poke(IF, 0x01);
+
+    for _ in 0..10 {
+        cpu.execute_instruction(&mut sys);
+    }
+    assert_eq!(0x0001, cpu.registers().pc());
+    assert_eq!(10, cpu.cycle_count());
+}
+
+#[test]
+fn test_cycles() {
+    let (mut cpu, mut sys) = setup(&[
+        0x20, 0x01, // JR NZ, +1
+        0x00, // NOP
+        0x28, 0x01, // JR Z, +1
+        0xcd, 0x00, 0x01, // CALL 0100h
+    ]);
+    sys.poke(0x0100, 0xd0); // RET NC
+    sys.poke(0x0101, 0xc9); // RET
+    cpu.registers().set16(Reg16::AF, 0x0010);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0003, cpu.registers().pc());
+    assert_eq!(3, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0005, cpu.registers().pc());
+    assert_eq!(5, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0100, cpu.registers().pc());
+    assert_eq!(11, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0101, cpu.registers().pc());
+    assert_eq!(13, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0008, cpu.registers().pc());
+    assert_eq!(17, cpu.cycle_count());
+}
+
+#[test]
+fn test_interrupt_vectors() {
+    let (mut cpu, mut sys) = setup(&[
+        0xfb, // EI
+        0x00, // NOP
+        0x00, // NOP
+    ]);
+    sys.poke(0x0050, 0x00); // NOP
+    sys.poke(0x0051, 0xd9); // RETI
+    sys.poke(IE, 0x1c);
+    sys.poke(IF, 0x07); // VBlank not enabled
+
+    cpu.execute_instruction(&mut sys);
+    // Delayed one instruction after EI
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0002, cpu.registers().pc());
+    assert_eq!(2, cpu.cycle_count());
+
+    // The timer interrupt, the STAT one is not enabled
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0051, cpu.registers().pc());
+    assert_eq!(0x03, sys.peek(IF));
+    assert_eq!(0x0002, sys.peek16(0xfffc));
+    assert!(!cpu.registers().get_interrupt_mode().0);
+    assert_eq!(8, cpu.cycle_count());
+
+    // RETI enables IME right away
+    sys.poke(IF, 0x00);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0002, cpu.registers().pc());
+    assert!(cpu.registers().get_interrupt_mode().0);
+    assert_eq!(12, cpu.cycle_count());
+}
+
+#[test]
+fn test_halt() {
+    let (mut cpu, mut sys) = setup(&[
+        0x76, // HALT
+        0x3c, // INC A
+    ]);
+    sys.poke(IE, 0x10);
+    cpu.registers().set_a(0x00);
+
+    cpu.execute_instruction(&mut sys);
+    for _ in 0..5 {
+        cpu.execute_instruction(&mut sys);
+    }
+    assert!(!cpu.is_halted());
+    assert_eq!(0x0001, cpu.registers().pc());
+    assert_eq!(6, cpu.cycle_count());
+
+    // The joypad request wakes it up with IME off, without the jump
+    sys.poke(IF, 0x10);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0002, cpu.registers().pc());
+    assert_eq!(0x01, cpu.registers().a());
+    assert_eq!(0x10, sys.peek(IF));
+}
+
+#[test]
+fn test_halt_run() {
+    let (mut cpu, mut sys) = setup(&[
+        0xf3, // DI
+        0x76, // HALT
+        0x3c, // INC A
+    ]);
+    sys.poke(IE, 0x10);
+    cpu.registers().set_a(0x00);
+
+    // Nothing can wake it up without the machine
+    assert_eq!(StopReason::Halted, cpu.run(&mut sys, 1000));
+    assert_eq!(0x0002, cpu.registers().pc());
+    assert_eq!(2, cpu.cycle_count());
+
+    sys.poke(IF, 0x10);
+    assert_eq!(StopReason::InstructionLimit, cpu.run(&mut sys, 1));
+    assert_eq!(0x0003, cpu.registers().pc());
+    assert_eq!(0x01, cpu.registers().a());
+}
+
+#[test]
+fn test_stop() {
+    let (mut cpu, mut sys) = setup(&[
+        0xfb, // EI
+        0x10, 0x00, // STOP
+        0x3c, // INC A
+    ]);
+    sys.poke(IE, 0x10);
+    sys.poke(0x0060, 0x00); // NOP
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0003, cpu.registers().pc());
+
+    sys.poke(IF, 0x10);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0003, sys.peek16(0xfffc));
+    assert_eq!(0x0061, cpu.registers().pc());
+}
+
+#[test]
+fn test_disasm() {
+    let (mut cpu, mut sys) = setup(&[
+        0xe8, 0xfe, // ADD SP, -2
+        0xf8, 0x05, // LD HL, SP+5
+        0xe0, 0x44, // LDH (44h), A
+        0x2a, // LDI A, (HL)
+        0xcb, 0x31, // SWAP C
+        0xd3, // Illegal
+    ]);
+
+    let mut disasm = Vec::new();
+    for pc in [0x0000, 0x0002, 0x0004, 0x0006, 0x0007, 0x0009] {
+        cpu.registers().set_pc(pc);
+        disasm.push(cpu.disasm_instruction(&mut sys));
+    }
+    assert_eq!(vec![
+        "ADD SP, -02h",
+        "LD HL, SP+05h",
+        "LDH (44h), A",
+        "LDI A, (HL)",
+        "SWAP C",
+        "ILLEGAL D3h",
+    ], disasm);
+}
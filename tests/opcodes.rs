@@ -33,3 +33,80 @@ fn test_push_pop_rr() {
     assert_eq!(0x1234, cpu.registers().get16(Reg16::BC));
     assert_eq!(0x1234, cpu.registers().get16(Reg16::AF));
 }
+
+#[test]
+fn test_r_increment() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0x00);  // NOP
+    sys.poke(0x0001, 0xdd);  // LD IX, $1234
+    sys.poke(0x0002, 0x21);
+    sys.poke(0x0003, 0x34);
+    sys.poke(0x0004, 0x12);
+    sys.poke(0x0005, 0xcb);  // RLC B
+    sys.poke(0x0006, 0x00);
+    sys.poke(0x0007, 0xdd);  // RLC (IX+1)
+    sys.poke(0x0008, 0xcb);
+    sys.poke(0x0009, 0x01);
+    sys.poke(0x000a, 0x06);
+    sys.poke(0x000b, 0xed);  // NEG
+    sys.poke(0x000c, 0x44);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(1, cpu.registers().get8(Reg8::R));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(3, cpu.registers().get8(Reg8::R));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(5, cpu.registers().get8(Reg8::R));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(7, cpu.registers().get8(Reg8::R));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(9, cpu.registers().get8(Reg8::R));
+}
+
+#[test]
+fn test_r_increment_keeps_bit_7() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0x00);  // NOP
+    sys.poke(0x0001, 0x00);  // NOP
+    cpu.registers().set8(Reg8::R, 0xff);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x80, cpu.registers().get8(Reg8::R));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x81, cpu.registers().get8(Reg8::R));
+}
+
+#[test]
+fn test_ld_a_r() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0x00);  // NOP
+    sys.poke(0x0001, 0xed);  // LD A, R
+    sys.poke(0x0002, 0x5f);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(3, cpu.registers().a());
+}
+
+#[test]
+fn test_r_increment_ldir() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xed);  // LDIR
+    sys.poke(0x0001, 0xb0);
+    cpu.registers().set16(Reg16::BC, 3);
+    cpu.registers().set16(Reg16::HL, 0x1000);
+    cpu.registers().set16(Reg16::DE, 0x2000);
+
+    while cpu.registers().pc() == 0x0000 {
+        cpu.execute_instruction(&mut sys);
+    }
+    assert_eq!(6, cpu.registers().get8(Reg8::R));
+}
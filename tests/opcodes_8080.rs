@@ -0,0 +1,190 @@
+use iz80::*;
+
+mod common;
+
+struct StrictMachine {
+    plain: PlainMachine,
+    undocumented: Vec<(u16, u8)>,
+}
+
+impl Machine for StrictMachine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.plain.peek(address)
+    }
+    fn poke(&mut self, address: u16, value: u8) {
+        self.plain.poke(address, value);
+    }
+
+    fn port_in(&mut self, address: u16) -> u8 {
+        self.plain.port_in(address)
+    }
+    fn port_out(&mut self, address: u16, value: u8) {
+        self.plain.port_out(address, value);
+    }
+
+    fn undocumented_opcode(&mut self, address: u16, opcode: u8) {
+        self.undocumented.push((address, opcode));
+    }
+}
+
+fn setup(code: &[u8]) -> (Cpu, StrictMachine) {
+    let mut sys = StrictMachine {
+        plain: PlainMachine::new(),
+        undocumented: Vec::new(),
+    };
+    common::load(&mut sys, 0, code);
+    (Cpu::new_8080(), sys)
+}
+
+#[test]
+fn test_nop_aliases() {
+    let (mut cpu, mut sys) = setup(&[0x08, 0x10, 0x18, 0x20, 0x28, 0x30, 0x38]);
+    cpu.registers().set16(Reg16::AF, 0x12d7);
+
+    for _ in 0..7 {
+        cpu.execute_instruction(&mut sys);
+    }
+    assert_eq!(7, cpu.registers().pc());
+    assert_eq!(0x12d7, cpu.registers().get16(Reg16::AF));
+    assert_eq!(7 * 4, cpu.cycle_count());
+}
+
+#[test]
+fn test_jmp_ret_call_aliases() {
+    let (mut cpu, mut sys) = setup(&[
+        0xcb, 0x10, 0x00, // *JMP 0010h
+    ]);
+    sys.poke(0x0010, 0xdd); // *CALL 0020h
+    sys.poke(0x0011, 0x20);
+    sys.poke(0x0012, 0x00);
+    sys.poke(0x0013, 0xed); // *CALL 0020h
+    sys.poke(0x0014, 0x20);
+    sys.poke(0x0015, 0x00);
+    sys.poke(0x0016, 0xfd); // *CALL 0020h
+    sys.poke(0x0017, 0x20);
+    sys.poke(0x0018, 0x00);
+    sys.poke(0x0020, 0xd9); // *RET
+    cpu.registers().set16(Reg16::SP, 0x1000);
+
+    let mut cycles = Vec::new();
+    let mut pcs = Vec::new();
+    for _ in 0..7 {
+        let before = cpu.cycle_count();
+        cpu.execute_instruction(&mut sys);
+        cycles.push(cpu.cycle_count() - before);
+        pcs.push(cpu.registers().pc());
+    }
+    assert_eq!(vec![0x10, 0x20, 0x13, 0x20, 0x16, 0x20, 0x19], pcs);
+    assert_eq!(vec![10, 17, 10, 17, 10, 17, 10], cycles);
+    assert_eq!(0x1000, cpu.registers().get16(Reg16::SP));
+}
+
+#[test]
+fn test_disasm_aliases() {
+    let (mut cpu, mut sys) = setup(&[
+        0x00, // NOP
+        0x08, // *NOP
+        0xc3, 0x34, 0x12, // JP 1234h
+        0xcb, 0x34, 0x12, // *JMP 1234h
+        0xc9, // RET
+        0xd9, // *RET
+        0xcd, 0x34, 0x12, // CALL 1234h
+        0xfd, 0x34, 0x12, // *CALL 1234h
+    ]);
+
+    let mut disasm = Vec::new();
+    for pc in [0x0000, 0x0001, 0x0002, 0x0005, 0x0008, 0x0009, 0x000a, 0x000d] {
+        cpu.registers().set_pc(pc);
+        disasm.push(cpu.disasm_instruction(&mut sys));
+    }
+    assert_eq!(vec![
+        "NOP",
+        "*NOP",
+        "JP 1234h",
+        "*JMP 1234h",
+        "RET",
+        "*RET",
+        "CALL 1234h",
+        "*CALL 1234h",
+    ], disasm);
+}
+
+#[test]
+fn test_strict_reports_aliases() {
+    let (mut cpu, mut sys) = setup(&[
+        0x00, // NOP
+        0x38, // *NOP
+        0xed, 0x07, 0x00, // *CALL 0007h
+        0x00, // NOP
+        0x00, // NOP
+        0xd9, // *RET
+    ]);
+    cpu.registers().set16(Reg16::SP, 0x1000);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert!(sys.undocumented.is_empty());
+
+    cpu.set_strict(true);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0005, cpu.registers().pc());
+    assert_eq!(vec![(0x0002, 0xed), (0x0007, 0xd9)], sys.undocumented);
+}
+
+#[test]
+fn test_disasm_intel() {
+    let (mut cpu, mut sys) = setup(&[
+        0x78, // MOV A,B
+        0x36, 0x12, // MVI M,12h
+        0x01, 0x34, 0x12, // LXI B,1234h
+        0x12, // STAX D
+        0x29, // DAD H
+        0xeb, // XCHG
+        0x9e, // SBB M
+        0xfe, 0x05, // CPI 05h
+        0xfa, 0x34, 0x12, // JM 1234h
+        0xf1, // POP PSW
+        0xd3, 0x10, // OUT 10h
+        0xcb, 0x34, 0x12, // *JMP 1234h
+    ]);
+    cpu.set_syntax(Syntax::Intel);
+
+    let mut pc = 0;
+    let mut disasm = Vec::new();
+    for size in [1, 2, 3, 1, 1, 1, 1, 2, 3, 1, 2, 3] {
+        cpu.registers().set_pc(pc);
+        disasm.push(cpu.disasm_instruction(&mut sys));
+        pc += size;
+    }
+    assert_eq!(vec![
+        "MOV A,B",
+        "MVI M,12h",
+        "LXI B,1234h",
+        "STAX D",
+        "DAD H",
+        "XCHG",
+        "SBB M",
+        "CPI 05h",
+        "JM 1234h",
+        "POP PSW",
+        "OUT 10h",
+        "*JMP 1234h",
+    ], disasm);
+}
+
+#[test]
+fn test_disasm_syntax_per_call() {
+    let (mut cpu, mut sys) = setup(&[
+        0xc3, 0x34, 0x12, // JMP 1234h
+    ]);
+    assert_eq!(Syntax::Zilog, cpu.syntax());
+
+    assert_eq!("JMP 1234h", cpu.disasm_instruction_with(&mut sys, Syntax::Intel));
+    cpu.registers().set_pc(0x0000);
+    assert_eq!("JP 1234h", cpu.disasm_instruction(&mut sys));
+
+    cpu.set_syntax(Syntax::Intel);
+    cpu.registers().set_pc(0x0000);
+    assert_eq!("JP 1234h", cpu.disasm_instruction_with(&mut sys, Syntax::Zilog));
+}
@@ -0,0 +1,382 @@
+use iz80::*;
+
+struct SerialMachine {
+    plain: PlainMachine,
+    sid: bool,
+    sod: Vec<bool>,
+}
+
+impl Machine for SerialMachine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.plain.peek(address)
+    }
+    fn poke(&mut self, address: u16, value: u8) {
+        self.plain.poke(address, value);
+    }
+
+    fn port_in(&mut self, address: u16) -> u8 {
+        self.plain.port_in(address)
+    }
+    fn port_out(&mut self, address: u16, value: u8) {
+        self.plain.port_out(address, value);
+    }
+
+    fn serial_out(&mut self, value: bool) {
+        self.sod.push(value);
+    }
+    fn serial_in(&mut self) -> bool {
+        self.sid
+    }
+}
+
+#[test]
+fn test_dsub() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x08); // DSUB
+    cpu.registers().set16(Reg16::HL, 0x1234);
+    cpu.registers().set16(Reg16::BC, 0x1235);
+    cpu.registers().set_flag(Flag::C);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0xffff, cpu.registers().get16(Reg16::HL));
+    assert!(cpu.registers().get_flag(Flag::C));
+    assert!(cpu.registers().get_flag(Flag::S));
+    assert!(!cpu.registers().get_flag(Flag::Z));
+    assert!(!cpu.registers().get_flag(Flag::V));
+    assert_eq!(10, cpu.cycle_count());
+}
+
+#[test]
+fn test_dsub_overflow() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x08); // DSUB
+    cpu.registers().set16(Reg16::HL, 0x8000);
+    cpu.registers().set16(Reg16::BC, 0x0001);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0x7fff, cpu.registers().get16(Reg16::HL));
+    assert!(cpu.registers().get_flag(Flag::V));
+    assert!(!cpu.registers().get_flag(Flag::C));
+}
+
+#[test]
+fn test_arhl() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x10); // ARHL
+    cpu.registers().set16(Reg16::HL, 0x8765);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0xc3b2, cpu.registers().get16(Reg16::HL));
+    assert!(cpu.registers().get_flag(Flag::C));
+    assert_eq!(7, cpu.cycle_count());
+}
+
+#[test]
+fn test_rdel() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x18); // RDEL
+    cpu.registers().set16(Reg16::DE, 0x4321);
+    cpu.registers().set_flag(Flag::C);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0x8643, cpu.registers().get16(Reg16::DE));
+    assert!(!cpu.registers().get_flag(Flag::C));
+    assert!(cpu.registers().get_flag(Flag::V));
+}
+
+#[test]
+fn test_ldhi_ldsi() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x28); // LDHI 10h
+    sys.poke(0x0001, 0x10);
+    sys.poke(0x0002, 0x38); // LDSI 20h
+    sys.poke(0x0003, 0x20);
+    cpu.registers().set16(Reg16::HL, 0x1234);
+    cpu.registers().set16(Reg16::SP, 0x8000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x1244, cpu.registers().get16(Reg16::DE));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x8020, cpu.registers().get16(Reg16::DE));
+}
+
+#[test]
+fn test_shlx_lhlx() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0xd9); // SHLX
+    sys.poke(0x0001, 0x21); // LD HL, 0000h
+    sys.poke16(0x0002, 0x0000);
+    sys.poke(0x0004, 0xed); // LHLX
+    cpu.registers().set16(Reg16::HL, 0x1234);
+    cpu.registers().set16(Reg16::DE, 0x4000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x1234, sys.peek16(0x4000));
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x1234, cpu.registers().get16(Reg16::HL));
+}
+
+#[test]
+fn test_inx_k_flag() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x03); // INC BC
+    sys.poke(0x0001, 0xdd); // JNK 1234h
+    sys.poke16(0x0002, 0x1234);
+    sys.poke(0x0004, 0xfd); // JK 2000h
+    sys.poke16(0x0005, 0x2000);
+    cpu.registers().set16(Reg16::BC, 0xffff);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0000, cpu.registers().get16(Reg16::BC));
+    assert!(cpu.registers().get_flag(Flag::K));
+    assert_eq!(6, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0004, cpu.registers().pc());
+    assert_eq!(6 + 7, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x2000, cpu.registers().pc());
+    assert_eq!(6 + 7 + 10, cpu.cycle_count());
+}
+
+#[test]
+fn test_rstv() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x3e); // LD A, 7fh
+    sys.poke(0x0001, 0x7f);
+    sys.poke(0x0002, 0xcb); // RSTV
+    sys.poke(0x0003, 0x3c); // INC A
+    sys.poke(0x0004, 0xcb); // RSTV
+    cpu.registers().set16(Reg16::SP, 0x8000);
+    cpu.registers().clear_flag(Flag::V);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0003, cpu.registers().pc());
+
+    cpu.execute_instruction(&mut sys);
+    assert!(cpu.registers().get_flag(Flag::V));
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0040, cpu.registers().pc());
+    assert_eq!(0x0005, sys.peek16(0x7ffe));
+    assert_eq!(7 + 6 + 4 + 12, cpu.cycle_count());
+}
+
+#[test]
+fn test_v_k_flags_preserved_on_pop_psw() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0xf1); // POP AF
+    sys.poke16(0x8000, 0x12ff);
+    cpu.registers().set16(Reg16::SP, 0x8000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xf7, cpu.registers().get8(Reg8::F));
+}
+
+#[test]
+fn test_8085_cycles() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x41); // LD B, C
+    sys.poke(0x0001, 0xcd); // CALL 1000h
+    sys.poke16(0x0002, 0x1000);
+    sys.poke(0x1000, 0xc4); // CALL NZ, 2000h
+    sys.poke16(0x1001, 0x2000);
+    cpu.registers().set16(Reg16::SP, 0x8000);
+    cpu.registers().set_flag(Flag::Z);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(4, cpu.cycle_count());
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(4 + 18, cpu.cycle_count());
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x1003, cpu.registers().pc());
+    assert_eq!(4 + 18 + 9, cpu.cycle_count());
+}
+
+#[test]
+fn test_sim_rim_masks() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x3e); // LD A, 0dh
+    sys.poke(0x0001, 0x0d);
+    sys.poke(0x0002, 0x30); // SIM
+    sys.poke(0x0003, 0xfb); // EI
+    sys.poke(0x0004, 0x20); // RIM
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.signal_rst7_5();
+    cpu.signal_rst6_5(true);
+    cpu.execute_instruction(&mut sys);
+
+    // 7.5 and 5.5 masked, 6.5 delayed by EI. Both are pending.
+    assert_eq!(0x60 | 0x08 | 0x05, cpu.registers().a());
+}
+
+#[test]
+fn test_rst_priority() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0xaf); // XOR A
+    sys.poke(0x0001, 0x30); // SIM, unchanged masks
+    sys.poke(0x0002, 0x3e); // LD A, 08h
+    sys.poke(0x0003, 0x08);
+    sys.poke(0x0004, 0x30); // SIM, unmask all
+    sys.poke(0x0005, 0xfb); // EI
+    sys.poke(0x0006, 0x00); // NOP
+    sys.poke(0x0007, 0x00); // NOP
+    cpu.registers().set16(Reg16::SP, 0x8000);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.signal_rst5_5(true);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0004, cpu.registers().pc()); // Interrupts disabled
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.signal_rst7_5();
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0007, cpu.registers().pc()); // Interrupt shadow after EI
+
+    let cycles = cpu.cycle_count();
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x003c + 1, cpu.registers().pc());
+    assert_eq!(0x0007, sys.peek16(0x7ffe));
+    assert_eq!(cycles + 12 + 4, cpu.cycle_count());
+
+    // The latch is reset when accepted
+    sys.poke(0x003d, 0xfb); // EI
+    sys.poke(0x003e, 0x00); // NOP
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x002c + 1, cpu.registers().pc());
+}
+
+#[test]
+fn test_trap() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0xfb); // EI
+    sys.poke(0x0001, 0x00); // NOP
+    sys.poke(0x0024, 0x20); // RIM
+    sys.poke(0x0025, 0xfb); // EI
+    cpu.registers().set16(Reg16::SP, 0x8000);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.signal_trap();
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0x0025, cpu.registers().pc());
+    assert_eq!(0x0002, sys.peek16(0x7ffe));
+    // Interrupts were enabled before the TRAP
+    assert_eq!(0x08, cpu.registers().a() & 0x08);
+    let (enabled, _) = cpu.registers().get_interrupt_mode();
+    assert!(!enabled);
+}
+
+#[test]
+fn test_serial_io() {
+    let mut sys = SerialMachine {
+        plain: PlainMachine::new(),
+        sid: true,
+        sod: Vec::new(),
+    };
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x3e); // LD A, c0h
+    sys.poke(0x0001, 0xc0);
+    sys.poke(0x0002, 0x30); // SIM
+    sys.poke(0x0003, 0x3e); // LD A, 80h
+    sys.poke(0x0004, 0x80);
+    sys.poke(0x0005, 0x30); // SIM, SDE not set
+    sys.poke(0x0006, 0x20); // RIM
+
+    for _ in 0..5 {
+        cpu.execute_instruction(&mut sys);
+    }
+
+    assert_eq!(vec![true], sys.sod);
+    assert_eq!(0x80, cpu.registers().a() & 0x80);
+}
+
+#[test]
+fn test_disasm_8085() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+
+    sys.poke(0x0000, 0x28); // LDHI 10h
+    sys.poke(0x0001, 0x10);
+    sys.poke(0x0002, 0xdd); // JNK 1234h
+    sys.poke16(0x0003, 0x1234);
+    sys.poke(0x0005, 0x20); // RIM
+
+    assert_eq!("LDHI 10h", cpu.disasm_instruction(&mut sys));
+    cpu.registers().set_pc(0x0002);
+    assert_eq!("JNK 1234h", cpu.disasm_instruction(&mut sys));
+    cpu.registers().set_pc(0x0005);
+    assert_eq!("RIM", cpu.disasm_instruction(&mut sys));
+}
+
+#[test]
+fn test_disasm_intel() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8085();
+    cpu.set_syntax(Syntax::Intel);
+
+    let code = [
+        0x20, // RIM
+        0x28, 0x12, // LDHI 12h
+        0xdd, 0x34, 0x12, // JNK 1234h
+        0x2a, 0x34, 0x12, // LHLD 1234h
+    ];
+    for (i, e) in code.iter().enumerate() {
+        sys.poke(i as u16, *e);
+    }
+
+    let mut disasm = Vec::new();
+    for pc in [0x0000, 0x0001, 0x0003, 0x0006] {
+        cpu.registers().set_pc(pc);
+        disasm.push(cpu.disasm_instruction(&mut sys));
+    }
+    assert_eq!(vec![
+        "RIM",
+        "LDHI 12h",
+        "JNK 1234h",
+        "LHLD 1234h",
+    ], disasm);
+}
@@ -236,4 +236,77 @@ fn test_rrd() {
 
     assert_eq!(0xad, cpu.registers().a());
     assert_eq!(0xbc, sys.peek(0xccdd));
-}
\ No newline at end of file
+}
+#[test]
+fn test_bit_phl_flags_from_memptr() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0x3a); // LD A, (2800h)
+    sys.poke16(0x0001, 0x2800);
+    sys.poke(0x0003, 0xcb); // BIT 0, (HL)
+    sys.poke(0x0004, 0x46);
+    cpu.registers().set16(Reg16::HL, 0x1000);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+
+    // Flags 3 and 5 from the high byte of MEMPTR, 0x2801
+    assert!(cpu.registers().get_flag(Flag::_3));
+    assert!(cpu.registers().get_flag(Flag::_5));
+}
+
+#[test]
+fn test_scf_after_flags_modified() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xa7); // AND A
+    sys.poke(0x0001, 0x37); // SCF
+    cpu.registers().set_a(0x00);
+    cpu.registers().set8(Reg8::F, 0x28);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+
+    // Flags 3 and 5 from A
+    assert!(!cpu.registers().get_flag(Flag::_3));
+    assert!(!cpu.registers().get_flag(Flag::_5));
+}
+
+#[test]
+fn test_scf_after_flags_not_modified() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0x00); // NOP
+    sys.poke(0x0001, 0x37); // SCF
+    cpu.registers().set_a(0x00);
+    cpu.registers().set8(Reg8::F, 0x28);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+
+    // Flags 3 and 5 from A or F
+    assert!(cpu.registers().get_flag(Flag::_3));
+    assert!(cpu.registers().get_flag(Flag::_5));
+}
+
+#[test]
+fn test_scf_after_flags_not_modified_nec() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+    cpu.set_model(CpuModel::NecNmos);
+
+    sys.poke(0x0000, 0x00); // NOP
+    sys.poke(0x0001, 0x37); // SCF
+    cpu.registers().set_a(0x00);
+    cpu.registers().set8(Reg8::F, 0x28);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+
+    // Flags 3 and 5 from A
+    assert!(!cpu.registers().get_flag(Flag::_3));
+    assert!(!cpu.registers().get_flag(Flag::_5));
+}
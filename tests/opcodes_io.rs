@@ -29,3 +29,23 @@ fn test_in_e() {
 
     assert_eq!(0x8a, sys.port_in(0x6345));
 }
+
+#[test]
+fn test_out_c_0_nmos_cmos() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xed); // OUT (C), 0
+    sys.poke(0x0001, 0x71);
+    sys.poke(0x0002, 0xed); // OUT (C), 0
+    sys.poke(0x0003, 0x71);
+    cpu.registers().set16(Reg16::BC, 0x6345);
+    sys.port_out(0x6345, 0x8a);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x00, sys.port_in(0x6345));
+
+    cpu.set_model(CpuModel::ZilogCmos);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xff, sys.port_in(0x6345));
+}
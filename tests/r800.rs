@@ -0,0 +1,172 @@
+use iz80::*;
+
+mod common;
+
+fn setup(code: &[u8]) -> (Cpu, PlainMachine) {
+    common::setup(Cpu::new_r800(), code)
+}
+
+#[test]
+fn test_mulub() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0xc1, // MULUB A, B
+        0xed, 0xd9, // MULUB A, E
+    ]);
+    cpu.registers().set_a(0x12);
+    cpu.registers().set8(Reg8::B, 0x34);
+    cpu.registers().set8(Reg8::E, 0x00);
+    cpu.registers().set8(Reg8::F, 0xff);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x03a8, cpu.registers().get16(Reg16::HL));
+    assert_eq!(0x3b, cpu.registers().get8(Reg8::F)); // C, N, H, 3 and 5 kept
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0000, cpu.registers().get16(Reg16::HL));
+    assert_eq!(0x7a, cpu.registers().get8(Reg8::F)); // Z, not C
+}
+
+#[test]
+fn test_muluw() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0xc3, // MULUW HL, BC
+        0xed, 0xf3, // MULUW HL, SP
+    ]);
+    cpu.registers().set16(Reg16::HL, 0x1234);
+    cpu.registers().set16(Reg16::BC, 0x5678);
+    cpu.registers().set16(Reg16::SP, 0x0002);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0626, cpu.registers().get16(Reg16::DE));
+    assert_eq!(0x0060, cpu.registers().get16(Reg16::HL));
+    assert!(cpu.registers().get_flag(Flag::C));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0000, cpu.registers().get16(Reg16::DE));
+    assert_eq!(0x00c0, cpu.registers().get16(Reg16::HL));
+    assert!(!cpu.registers().get_flag(Flag::C));
+    assert!(!cpu.registers().get_flag(Flag::Z));
+}
+
+#[test]
+fn test_sll_keeps_bit_0() {
+    let (mut cpu, mut sys) = setup(&[
+        0xcb, 0x37, // SLL A
+        0xcb, 0x37, // SLL A
+    ]);
+    cpu.registers().set_a(0x81);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x03, cpu.registers().a());
+    assert!(cpu.registers().get_flag(Flag::C));
+
+    cpu.registers().set_a(0x80);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x00, cpu.registers().a());
+    assert!(cpu.registers().get_flag(Flag::Z));
+}
+
+#[test]
+fn test_scf_ccf_keep_flags_3_5() {
+    let (mut cpu, mut sys) = setup(&[
+        0x37, // SCF
+        0x3f, // CCF
+    ]);
+    cpu.registers().set16(Reg16::AF, 0xff00);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x01, cpu.registers().get8(Reg8::F));
+
+    cpu.registers().set16(Reg16::AF, 0x0029);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x38, cpu.registers().get8(Reg8::F)); // H from the old C
+}
+
+#[test]
+fn test_page_break_on_fetch() {
+    let (mut cpu, mut sys) = setup(&[]);
+    sys.poke(0x01fe, 0x00); // NOP
+    sys.poke(0x01ff, 0x00); // NOP
+    sys.poke(0x0200, 0x00); // NOP
+    cpu.registers().set_pc(0x01fe);
+
+    // The first access is a page break
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(2, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(3, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(5, cpu.cycle_count());
+}
+
+#[test]
+fn test_page_break_on_data() {
+    let (mut cpu, mut sys) = setup(&[
+        0x00, // NOP
+        0x7e, // LD A, (HL)
+        0x77, // LD (HL), A
+        0x00, // NOP
+    ]);
+    cpu.registers().set16(Reg16::HL, 0x8000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(2, cpu.cycle_count());
+
+    // The read is on another page
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(5, cpu.cycle_count());
+
+    // And the fetch is back
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(9, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(11, cpu.cycle_count());
+}
+
+#[test]
+fn test_cycles() {
+    let (mut cpu, mut sys) = setup(&[
+        0x00, // NOP
+        0x20, 0x00, // JR NZ, +2
+        0x28, 0x00, // JR Z, +2
+        0xed, 0xc9, // MULUB A, C
+        0xed, 0xc3, // MULUW HL, BC
+        0xdd, 0x7e, 0x02, // LD A, (IX+2)
+        0xdd, 0xcb, 0x02, 0x46, // BIT 0, (IX+2)
+    ]);
+    cpu.registers().set16(Reg16::AF, 0x0040);
+
+    cpu.execute_instruction(&mut sys);
+    let start = cpu.cycle_count();
+    let mut cycles = Vec::new();
+    for _ in 0..6 {
+        let before = cpu.cycle_count();
+        cpu.execute_instruction(&mut sys);
+        cycles.push(cpu.cycle_count() - before);
+    }
+    assert_eq!(2, start);
+    assert_eq!(vec![2, 3, 14, 36, 5, 5], cycles);
+}
+
+#[test]
+fn test_disasm() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0xc1, // MULUB A, B
+        0xed, 0xf3, // MULUW HL, SP
+        0xcb, 0x31, // SLL C
+    ]);
+
+    let mut disasm = Vec::new();
+    for pc in [0x0000, 0x0002, 0x0004] {
+        cpu.registers().set_pc(pc);
+        disasm.push(cpu.disasm_instruction(&mut sys));
+    }
+    assert_eq!(vec![
+        "MULUB A, B",
+        "MULUW HL, SP",
+        "SLL C",
+    ], disasm);
+}
@@ -48,3 +48,86 @@ fn test_serialization_deserialization() {
     assert_eq!(0xabcd, cpu2.registers().pc(), "Bad serialization of register PC");
  
 }
+
+#[test]
+fn test_serialization_memptr() {
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0x3a); // LD A, (2000h)
+    sys.poke16(0x0001, 0x2000);
+    sys.poke(0x0003, 0xcb); // BIT 0, (HL)
+    sys.poke(0x0004, 0x46);
+    cpu.execute_instruction(&mut sys);
+
+    let serialized = cpu.serialize();
+    let mut cpu2 = Cpu::new();
+    let result = cpu2.deserialize(&serialized);
+    assert!(result.is_ok());
+    cpu2.execute_instruction(&mut sys);
+
+    // Flags 3 and 5 from the high byte of MEMPTR, 0x2001
+    assert!(!cpu2.registers().get_flag(Flag::_3));
+    assert!(cpu2.registers().get_flag(Flag::_5));
+}
+
+#[test]
+fn test_serialization_model() {
+    let mut cpu = Cpu::new();
+    cpu.set_model(CpuModel::StCmos);
+
+    let serialized = cpu.serialize();
+    let mut cpu2 = Cpu::new();
+    let result = cpu2.deserialize(&serialized);
+    assert!(result.is_ok());
+    assert_eq!(CpuModel::StCmos, cpu2.model());
+}
+
+#[test]
+fn test_deserialization_legacy_layout() {
+    // Registers, cycle and 8 bytes of flags, saved without a version
+    const LEGACY_SIZE: usize = 16 + 16 + 5 + 8 + 8;
+
+    let mut cpu = Cpu::new();
+    cpu.registers().set8(Reg8::A, 0x12);
+    cpu.registers().set_pc(0xabcd);
+    let serialized = cpu.serialize();
+
+    let mut cpu2 = Cpu::new();
+    let result = cpu2.deserialize(&serialized[..LEGACY_SIZE]);
+    assert!(result.is_ok());
+    assert_eq!(0x12, cpu2.registers().get8(Reg8::A));
+    assert_eq!(0xabcd, cpu2.registers().pc());
+
+    assert!(Cpu::new_z180().deserialize(&serialized[..LEGACY_SIZE]).is_err());
+    assert!(Cpu::new_lr35902().deserialize(&serialized[..LEGACY_SIZE]).is_err());
+    assert!(Cpu::new_8080().deserialize(&Cpu::new_8080().serialize()[..LEGACY_SIZE]).is_ok());
+    assert!(Cpu::new().deserialize(&serialized[..LEGACY_SIZE - 1]).is_err());
+}
+
+#[test]
+fn test_deserialization_versions_and_variants() {
+    let mut serialized = Cpu::new().serialize();
+    assert!(Cpu::new_ez80().deserialize(&serialized).is_err());
+    assert!(Cpu::new_ez80().deserialize(&Cpu::new_z180().serialize()).is_err());
+
+    serialized[16 + 16 + 5 + 8 + 8] = 0xff;
+    assert!(Cpu::new().deserialize(&serialized).is_err());
+}
+
+#[test]
+fn test_deserialization_of_another_cpu() {
+    let cpus: [fn() -> Cpu; 6] = [Cpu::new_z80, Cpu::new_8080, Cpu::new_8085,
+        Cpu::new_z180, Cpu::new_ez80, Cpu::new_lr35902];
+    for (i, saved) in cpus.iter().enumerate() {
+        let serialized = saved().serialize();
+        for (j, restored) in cpus.iter().enumerate() {
+            assert_eq!(i == j, restored().deserialize(&serialized).is_ok(), "{i} on {j}");
+        }
+    }
+
+    // Same state as the Z80
+    let serialized = Cpu::new_z80().serialize();
+    assert!(Cpu::new_z80n().deserialize(&serialized).is_ok());
+    assert!(Cpu::new_r800().deserialize(&serialized).is_ok());
+}
@@ -0,0 +1,265 @@
+use iz80::*;
+
+struct BusMachine {
+    plain: PlainMachine,
+    cycles: Vec<(BusCycle, u16, u64)>,
+    internal: Vec<(u16, u64)>,
+    m1_wait: u8,
+}
+
+impl BusMachine {
+    fn new() -> BusMachine {
+        BusMachine {
+            plain: PlainMachine::new(),
+            cycles: Vec::new(),
+            internal: Vec::new(),
+            m1_wait: 0,
+        }
+    }
+}
+
+impl Machine for BusMachine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.plain.peek(address)
+    }
+    fn poke(&mut self, address: u16, value: u8) {
+        self.plain.poke(address, value);
+    }
+
+    fn port_in(&mut self, address: u16) -> u8 {
+        self.plain.port_in(address)
+    }
+    fn port_out(&mut self, address: u16, value: u8) {
+        self.plain.port_out(address, value);
+    }
+
+    fn bus_cycle(&mut self, cycle: BusCycle, address: u16, t_state: u64) {
+        if cycle == BusCycle::Internal {
+            self.internal.push((address, t_state));
+        } else {
+            self.cycles.push((cycle, address, t_state));
+        }
+    }
+
+    fn wait_states(&mut self, cycle: BusCycle, _address: u16, _t_state: u64) -> u8 {
+        if cycle == BusCycle::OpcodeFetch {
+            self.m1_wait
+        } else {
+            0
+        }
+    }
+}
+
+#[test]
+fn test_bus_cycles_inc_phl() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0x34); // INC (HL)
+    cpu.registers().set16(Reg16::HL, 0x1234);
+    cpu.registers().set8(Reg8::I, 0x80);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(vec![
+        (BusCycle::OpcodeFetch, 0x0000, 0),
+        (BusCycle::Refresh, 0x8000, 2),
+        (BusCycle::MemoryRead, 0x1234, 4),
+        (BusCycle::MemoryWrite, 0x1234, 8),
+    ], sys.cycles);
+    assert_eq!(vec![(0x1234, 7)], sys.internal);
+    assert_eq!(11, cpu.cycle_count());
+}
+
+#[test]
+fn test_bus_cycles_ld_a_pixd() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xdd); // LD A, (IX+2)
+    sys.poke(0x0001, 0x7e);
+    sys.poke(0x0002, 0x02);
+    cpu.registers().set16(Reg16::IX, 0x1234);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(vec![
+        (BusCycle::OpcodeFetch, 0x0000, 0),
+        (BusCycle::Refresh, 0x0000, 2),
+        (BusCycle::OpcodeFetch, 0x0001, 4),
+        (BusCycle::Refresh, 0x0001, 6),
+        (BusCycle::MemoryRead, 0x0002, 8),
+        (BusCycle::MemoryRead, 0x1236, 16),
+    ], sys.cycles);
+    assert_eq!(vec![(0x0002, 11), (0x0002, 12), (0x0002, 13), (0x0002, 14), (0x0002, 15)],
+        sys.internal);
+    assert_eq!(19, cpu.cycle_count());
+}
+
+#[test]
+fn test_bus_cycles_ld_pixd_n() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xfd); // LD (IY+2), 55h
+    sys.poke(0x0001, 0x36);
+    sys.poke(0x0002, 0x02);
+    sys.poke(0x0003, 0x55);
+    cpu.registers().set16(Reg16::IY, 0x1234);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!((BusCycle::MemoryRead, 0x0003, 11), sys.cycles[5]);
+    assert_eq!((BusCycle::MemoryWrite, 0x1236, 16), sys.cycles[6]);
+    assert_eq!(19, cpu.cycle_count());
+}
+
+#[test]
+fn test_bus_cycles_call() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xcd); // CALL 1234h
+    sys.poke16(0x0001, 0x1234);
+    cpu.registers().set16(Reg16::SP, 0x8000);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(vec![
+        (BusCycle::OpcodeFetch, 0x0000, 0),
+        (BusCycle::Refresh, 0x0000, 2),
+        (BusCycle::MemoryRead, 0x0001, 4),
+        (BusCycle::MemoryRead, 0x0002, 7),
+        (BusCycle::MemoryWrite, 0x7fff, 11),
+        (BusCycle::MemoryWrite, 0x7ffe, 14),
+    ], sys.cycles);
+    assert_eq!(17, cpu.cycle_count());
+}
+
+#[test]
+fn test_bus_cycles_out_n_a() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xd3); // OUT (FEh), A
+    sys.poke(0x0001, 0xfe);
+    cpu.registers().set_a(0x12);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!((BusCycle::IoWrite, 0x12fe, 7), sys.cycles[3]);
+    assert_eq!(11, cpu.cycle_count());
+}
+
+#[test]
+fn test_bus_cycles_jp_cc_not_taken() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xc2); // JP NZ, 1234h
+    sys.poke16(0x0001, 0x1234);
+    cpu.registers().set_flag(Flag::Z);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0x0003, cpu.registers().pc());
+    assert_eq!(10, cpu.cycle_count());
+}
+
+#[test]
+fn test_bus_cycles_im2() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x0000, 0xed); // IM 2
+    sys.poke(0x0001, 0x5e);
+    sys.poke(0x0002, 0xfb); // EI
+    sys.poke(0x0003, 0x00); // NOP
+    sys.poke16(0x12fe, 0x4000); // Vector table entry
+    cpu.registers().set8(Reg8::I, 0x12);
+    cpu.registers().set16(Reg16::SP, 0x8000);
+    cpu.set_interrupt_data(0xfe);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.signal_interrupt(true);
+    let cycles = cpu.cycle_count();
+    sys.cycles.clear();
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(vec![
+        (BusCycle::InterruptAck, 0x0004, cycles),
+        (BusCycle::Refresh, 0x1204, cycles + 4),
+        (BusCycle::MemoryWrite, 0x7fff, cycles + 7),
+        (BusCycle::MemoryWrite, 0x7ffe, cycles + 10),
+        (BusCycle::MemoryRead, 0x12fe, cycles + 13),
+        (BusCycle::MemoryRead, 0x12ff, cycles + 16),
+        (BusCycle::OpcodeFetch, 0x4000, cycles + 19),
+        (BusCycle::Refresh, 0x1205, cycles + 21),
+    ], sys.cycles);
+}
+
+#[test]
+fn test_bus_cycles_8080_no_refresh() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new_8080();
+
+    sys.poke(0x0000, 0xdb); // IN 10h
+    sys.poke(0x0001, 0x10);
+    cpu.registers().set_a(0x10);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(vec![
+        (BusCycle::OpcodeFetch, 0x0000, 0),
+        (BusCycle::MemoryRead, 0x0001, 4),
+        (BusCycle::IoRead, 0x1010, 7),
+    ], sys.cycles);
+    assert_eq!(10, cpu.cycle_count());
+}
+
+#[test]
+fn test_wait_states_m1() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new();
+    sys.m1_wait = 1; // As on the MSX
+
+    sys.poke(0x0000, 0xdd); // LD A, (IX+2)
+    sys.poke(0x0001, 0x7e);
+    sys.poke(0x0002, 0x02);
+    sys.poke(0x0003, 0x00); // NOP
+    cpu.registers().set16(Reg16::IX, 0x1234);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(vec![
+        (BusCycle::OpcodeFetch, 0x0000, 0),
+        (BusCycle::Refresh, 0x0000, 3),
+        (BusCycle::OpcodeFetch, 0x0001, 5),
+        (BusCycle::Refresh, 0x0001, 8),
+        (BusCycle::MemoryRead, 0x0002, 10),
+        (BusCycle::MemoryRead, 0x1236, 18),
+        (BusCycle::OpcodeFetch, 0x0003, 21),
+        (BusCycle::Refresh, 0x0002, 24),
+    ], sys.cycles);
+    assert_eq!(19 + 2 + 4 + 1, cpu.cycle_count());
+}
+
+#[test]
+fn test_internal_cycles_jr() {
+    let mut sys = BusMachine::new();
+    let mut cpu = Cpu::new();
+
+    sys.poke(0x8000, 0x18); // JR 10h
+    sys.poke(0x8001, 0x10);
+    cpu.registers().set_pc(0x8000);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0x8012, cpu.registers().pc());
+    assert_eq!(vec![(0x8001, 7), (0x8001, 8), (0x8001, 9), (0x8001, 10), (0x8001, 11)],
+        sys.internal);
+    assert_eq!(12, cpu.cycle_count());
+}
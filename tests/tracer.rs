@@ -0,0 +1,141 @@
+use iz80::*;
+
+mod common;
+
+fn setup(code: &[u8]) -> (Cpu, PlainMachine, RingBufferTracer) {
+    let (mut cpu, sys) = common::setup(Cpu::new(), code);
+    let tracer = RingBufferTracer::new(4);
+    cpu.set_tracer(Box::new(tracer.clone()));
+    (cpu, sys, tracer)
+}
+
+#[test]
+fn test_record() {
+    let (mut cpu, mut sys, tracer) = setup(&[
+        0xdd, 0x77, 0x02, // LD (IX+2), A
+    ]);
+    cpu.registers().set_a(0x55);
+    cpu.registers().set16(Reg16::IX, 0x1000);
+
+    cpu.execute_instruction(&mut sys);
+
+    let records = tracer.records();
+    assert_eq!(1, records.len());
+    let record = &records[0];
+    assert_eq!(0x0000, record.pc);
+    assert_eq!(vec![0xdd, 0x77, 0x02], record.bytes);
+    assert_eq!([0xdd, 0x77, 0x02, 0x00], record.memory);
+    assert_eq!("LD (IX+2), A", record.disasm);
+    assert_eq!(0x0000, record.before.pc());
+    assert_eq!(0x0003, record.after.pc());
+    assert_eq!(0, record.cycle);
+    assert_eq!(19, record.cycles);
+    assert_eq!(vec![Access::MemoryWrite { address: 0x1002, value: 0x55 }], record.accesses);
+}
+
+#[test]
+fn test_accesses() {
+    let (mut cpu, mut sys, tracer) = setup(&[
+        0xe3, // EX (SP), HL
+        0xdb, 0x10, // IN A, (10h)
+        0xd3, 0x20, // OUT (20h), A
+    ]);
+    sys.poke(0x8000, 0x34);
+    sys.poke(0x8001, 0x12);
+    cpu.registers().set16(Reg16::SP, 0x8000);
+    cpu.registers().set16(Reg16::HL, 0xabcd);
+    cpu.registers().set_a(0x00);
+    sys.port_out(0x0010, 0x99);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+
+    let records = tracer.records();
+    assert_eq!(vec![
+        Access::MemoryRead { address: 0x8000, value: 0x34 },
+        Access::MemoryRead { address: 0x8001, value: 0x12 },
+        Access::MemoryWrite { address: 0x8001, value: 0xab },
+        Access::MemoryWrite { address: 0x8000, value: 0xcd },
+    ], records[0].accesses);
+    assert_eq!(vec![Access::PortIn { address: 0x0010, value: 0x99 }], records[1].accesses);
+    assert_eq!(vec![Access::PortOut { address: 0x9920, value: 0x99 }], records[2].accesses);
+}
+
+#[test]
+fn test_ring_buffer_keeps_last() {
+    let (mut cpu, mut sys, tracer) = setup(&[0x00; 6]);
+
+    for _ in 0..6 {
+        cpu.execute_instruction(&mut sys);
+    }
+    let pcs: Vec<u16> = tracer.records().iter().map(|r| r.pc).collect();
+    assert_eq!(vec![2, 3, 4, 5], pcs);
+
+    tracer.clear();
+    assert!(tracer.records().is_empty());
+}
+
+#[test]
+fn test_formats() {
+    let (mut cpu, mut sys, tracer) = setup(&[
+        0x3e, 0x12, // LD A, 12h
+    ]);
+    cpu.registers().set16(Reg16::AF, 0x01b0);
+    cpu.registers().set16(Reg16::SP, 0xfffe);
+
+    cpu.execute_instruction(&mut sys);
+    let record = &tracer.records()[0];
+
+    assert_eq!("0000: ld a, 12h", MameFormat.format(record));
+    assert_eq!("A:01 F:B0 B:00 C:00 D:00 E:00 H:00 L:00 SP:FFFE PC:0000 PCMEM:3E,12,00,00",
+        GameboyDoctorFormat.format(record));
+    assert_eq!("==> 0000: LD A, 12h            PC:0002 AF:12b0 BC:0000 DE:0000 HL:0000 SP:fffe IX:0000 IY:0000 Flags:10110000 Cycle:0007 [3e 12]",
+        DefaultFormat.format(record));
+}
+
+#[test]
+fn test_writer_tracer() {
+    let (mut cpu, mut sys, tracer) = setup(&[
+        0x00, // NOP
+        0xc3, 0x00, 0x00, // JP 0000h
+    ]);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+
+    let mut writer = WriterTracer::new(Vec::new(), MameFormat);
+    for record in tracer.records() {
+        writer.trace(&record);
+    }
+    let text = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!("0000: nop\n0001: jp 0000h\n", text);
+}
+
+#[test]
+fn test_file_tracer() {
+    let path = std::env::temp_dir().join(format!("iz80_trace_{}.txt", std::process::id()));
+    let mut sys = PlainMachine::new();
+    let mut cpu = Cpu::new_8080();
+    cpu.set_syntax(Syntax::Intel);
+    sys.poke(0x0000, 0x78); // MOV A,B
+    cpu.set_tracer(Box::new(WriterTracer::create(&path, MameFormat).unwrap()));
+
+    cpu.execute_instruction(&mut sys);
+    // Dropping the tracer flushes the file
+    assert!(cpu.take_tracer().is_some());
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!("0000: mov a,b\n", text);
+}
+
+#[test]
+fn test_set_trace_false_removes_tracer() {
+    let (mut cpu, mut sys, tracer) = setup(&[0x00, 0x00]);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.set_trace(false);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(1, tracer.records().len());
+    assert!(cpu.take_tracer().is_none());
+}
@@ -0,0 +1,469 @@
+use std::collections::VecDeque;
+
+use iz80::*;
+
+struct Z180Machine {
+    mem: Vec<u8>,
+    io: [u8; 65536],
+    tx: Vec<(usize, u8)>,
+    rx: VecDeque<u8>,
+}
+
+impl Z180Machine {
+    fn new() -> Z180Machine {
+        Z180Machine {
+            mem: vec![0; 0x100000],
+            io: [0; 65536],
+            tx: Vec::new(),
+            rx: VecDeque::new(),
+        }
+    }
+
+    fn load(&mut self, address: u16, code: &[u8]) {
+        for (i, &value) in code.iter().enumerate() {
+            self.poke(address.wrapping_add(i as u16), value);
+        }
+    }
+}
+
+impl Machine for Z180Machine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.mem[address as usize]
+    }
+    fn poke(&mut self, address: u16, value: u8) {
+        self.mem[address as usize] = value;
+    }
+
+    fn peek_physical(&mut self, address: u32) -> u8 {
+        self.mem[address as usize]
+    }
+    fn poke_physical(&mut self, address: u32, value: u8) {
+        self.mem[address as usize] = value;
+    }
+
+    fn port_in(&mut self, address: u16) -> u8 {
+        self.io[address as usize]
+    }
+    fn port_out(&mut self, address: u16, value: u8) {
+        self.io[address as usize] = value;
+    }
+
+    fn asci_transmit(&mut self, channel: usize, value: u8) {
+        self.tx.push((channel, value));
+    }
+    fn asci_receive(&mut self, channel: usize) -> Option<u8> {
+        if channel == 0 {
+            self.rx.pop_front()
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn test_mlt() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[0xed, 0x5c]); // MLT DE
+    cpu.registers().set16(Reg16::DE, 0xfe12);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0xfe * 0x12, cpu.registers().get16(Reg16::DE));
+    assert_eq!(17, cpu.cycle_count());
+}
+
+#[test]
+fn test_tst() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0xed, 0x04, // TST B
+        0xed, 0x64, 0x80, // TST 80h
+        0xed, 0x34, // TST (HL)
+    ]);
+    sys.poke(0x1000, 0x0f);
+    cpu.registers().set_a(0xf0);
+    cpu.registers().set8(Reg8::B, 0x0f);
+    cpu.registers().set16(Reg16::HL, 0x1000);
+
+    cpu.execute_instruction(&mut sys);
+    assert!(cpu.registers().get_flag(Flag::Z));
+    assert!(cpu.registers().get_flag(Flag::H));
+    assert_eq!(7, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert!(!cpu.registers().get_flag(Flag::Z));
+    assert!(cpu.registers().get_flag(Flag::S));
+    assert_eq!(7 + 9, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert!(cpu.registers().get_flag(Flag::Z));
+    assert_eq!(0xf0, cpu.registers().a());
+    assert_eq!(7 + 9 + 10, cpu.cycle_count());
+}
+
+#[test]
+fn test_tstio() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[0xed, 0x74, 0x01]); // TSTIO 01h
+    sys.io[0x0080] = 0x03;
+    cpu.registers().set16(Reg16::BC, 0xff80);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert!(!cpu.registers().get_flag(Flag::Z));
+    assert_eq!(12, cpu.cycle_count());
+}
+
+#[test]
+fn test_in0_out0() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0xed, 0x08, 0x80, // IN0 C, (80h)
+        0xed, 0x39, 0x81, // OUT0 (81h), A
+    ]);
+    sys.io[0x0080] = 0x00;
+    cpu.registers().set_a(0x55);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x00, cpu.registers().get8(Reg8::C));
+    assert!(cpu.registers().get_flag(Flag::Z));
+    assert_eq!(12, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x55, sys.io[0x0081]);
+    assert_eq!(12 + 13, cpu.cycle_count());
+}
+
+#[test]
+fn test_otimr() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[0xed, 0x93]); // OTIMR
+    sys.load(0x1000, &[0x11, 0x22, 0x33]);
+    cpu.registers().set16(Reg16::HL, 0x1000);
+    cpu.registers().set16(Reg16::BC, 0x0380);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0x0002, cpu.registers().pc());
+    assert_eq!([0x11, 0x22, 0x33], sys.io[0x0080..0x0083]);
+    assert_eq!(0x1003, cpu.registers().get16(Reg16::HL));
+    assert_eq!(0x0083, cpu.registers().get16(Reg16::BC));
+    assert!(cpu.registers().get_flag(Flag::Z));
+    assert_eq!(16 + 16 + 14, cpu.cycle_count());
+}
+
+#[test]
+fn test_cycles() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0x00, // NOP
+        0xdd, 0x7e, 0x02, // LD A, (IX+2)
+        0xfd, 0x36, 0x01, 0x55, // LD (IY+1), 55h
+        0xdd, 0xcb, 0x01, 0x46, // BIT 0, (IX+1)
+        0xca, 0x00, 0x10, // JP Z, 1000h
+    ]);
+    cpu.registers().set16(Reg16::IX, 0x2000);
+    cpu.registers().set16(Reg16::IY, 0x2000);
+
+    let expected = [3, 14, 15, 15, 6];
+    for cycles in expected {
+        let before = cpu.cycle_count();
+        cpu.execute_instruction(&mut sys);
+        assert_eq!(cycles, cpu.cycle_count() - before);
+    }
+    assert_eq!(0x55, sys.peek(0x2001));
+    assert_eq!(0x000f, cpu.registers().pc());
+}
+
+#[test]
+fn test_trap_ed() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x1000, &[0xed, 0xff]); // Undefined
+    cpu.registers().set_pc(0x1000);
+    cpu.registers().set16(Reg16::SP, 0x8000);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0x0000, cpu.registers().pc());
+    assert_eq!(0x1001, sys.peek16(0x7ffe));
+
+    sys.load(0x0000, &[0xed, 0x00, 0x34]); // IN0 B, (34h)
+    cpu.execute_instruction(&mut sys);
+    // TRAP set, UFO clear, ITE0 set
+    assert_eq!(0x81, cpu.registers().get8(Reg8::B));
+}
+
+#[test]
+fn test_trap_indexed() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x1000, &[0xdd, 0xcb, 0x01, 0x30]); // SLL (IX+1), undefined
+    sys.load(0x0000, &[0xed, 0x00, 0x34]); // IN0 B, (34h)
+    cpu.registers().set_pc(0x1000);
+    cpu.registers().set16(Reg16::SP, 0x8000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0000, cpu.registers().pc());
+    assert_eq!(0x1002, sys.peek16(0x7ffe));
+
+    cpu.execute_instruction(&mut sys);
+    // TRAP and UFO set
+    assert_eq!(0xc1, cpu.registers().get8(Reg8::B));
+
+    // LD B, IXH is not available
+    sys.load(0x1000, &[0xdd, 0x44]);
+    cpu.registers().set_pc(0x1000);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0000, cpu.registers().pc());
+    assert_eq!(0x1001, sys.peek16(0x7ffc));
+}
+
+#[test]
+fn test_mmu() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0x3e, 0x84, // LD A, 84h
+        0xed, 0x39, 0x3a, // OUT0 (CBAR), A -- Bank at 4000h, common 1 at 8000h
+        0x3e, 0x10, // LD A, 10h
+        0xed, 0x39, 0x39, // OUT0 (BBR), A
+        0x3e, 0x20, // LD A, 20h
+        0xed, 0x39, 0x38, // OUT0 (CBR), A
+        0x32, 0x00, 0x40, // LD (4000h), A
+        0x32, 0x00, 0x80, // LD (8000h), A
+        0x3a, 0x01, 0x40, // LD A, (4001h)
+    ]);
+    sys.mem[0x14001] = 0x99;
+
+    for _ in 0..9 {
+        cpu.execute_instruction(&mut sys);
+    }
+
+    assert_eq!(0x20, sys.mem[0x14000]);
+    assert_eq!(0x20, sys.mem[0x28000]);
+    assert_eq!(0x00, sys.mem[0x4000]);
+    assert_eq!(0x99, cpu.registers().a());
+}
+
+#[test]
+fn test_icr_relocation() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0x3e, 0x40, // LD A, 40h
+        0xed, 0x39, 0x3f, // OUT0 (ICR), A -- Internal registers at 40h
+        0xed, 0x38, 0x7a, // IN0 A, (7Ah) -- CBAR
+        0xed, 0x00, 0x3a, // IN0 B, (3Ah) -- External
+    ]);
+    sys.io[0x003a] = 0x12;
+
+    for _ in 0..4 {
+        cpu.execute_instruction(&mut sys);
+    }
+
+    assert_eq!(0xf0, cpu.registers().a());
+    assert_eq!(0x12, cpu.registers().get8(Reg8::B));
+}
+
+#[test]
+fn test_internal_registers_need_high_byte_zero() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0xed, 0x78, // IN A, (C)
+    ]);
+    sys.io[0x013a] = 0x12;
+    cpu.registers().set16(Reg16::BC, 0x013a);
+
+    cpu.execute_instruction(&mut sys);
+
+    assert_eq!(0x12, cpu.registers().a());
+}
+
+#[test]
+fn test_asci() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0x3e, 0x64, // LD A, 64h
+        0xed, 0x39, 0x00, // OUT0 (CNTLA0), A -- RE and TE
+        0x3e, 0x41, // LD A, 'A'
+        0xed, 0x39, 0x06, // OUT0 (TDR0), A
+        0xed, 0x00, 0x04, // IN0 B, (STAT0)
+        0xed, 0x08, 0x08, // IN0 C, (RDR0)
+        0xed, 0x00, 0x04, // IN0 B, (STAT0)
+    ]);
+    sys.rx.push_back(0x5a);
+
+    for _ in 0..5 {
+        cpu.execute_instruction(&mut sys);
+    }
+    assert_eq!(vec![(0, 0x41)], sys.tx);
+    // RDRF and TDRE
+    assert_eq!(0x82, cpu.registers().get8(Reg8::B));
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x5a, cpu.registers().get8(Reg8::C));
+    assert_eq!(0x02, cpu.registers().get8(Reg8::B));
+}
+
+#[test]
+fn test_prt_interrupt() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0x3e, 0x12, // LD A, 12h
+        0xed, 0x47, // LD I, A
+        0x3e, 0x20, // LD A, 20h
+        0xed, 0x39, 0x33, // OUT0 (IL), A
+        0x3e, 0x50, // LD A, 50h
+        0xed, 0x39, 0x0c, // OUT0 (TMDR0L), A
+        0xed, 0x39, 0x0e, // OUT0 (RLDR0L), A
+        0xaf, // XOR A
+        0xed, 0x39, 0x0d, // OUT0 (TMDR0H), A
+        0xed, 0x39, 0x0f, // OUT0 (RLDR0H), A
+        0x3e, 0x11, // LD A, 11h
+        0xed, 0x39, 0x10, // OUT0 (TCR), A -- TIE0 and TDE0
+        0xed, 0x5e, // IM 2
+        0xfb, // EI
+        0x76, // HALT
+    ]);
+    sys.load(0x1224, &[0x00, 0x30]); // PRT0 vector
+    sys.load(0x3000, &[0x76]); // HALT
+    cpu.registers().set16(Reg16::SP, 0x8000);
+
+    for _ in 0..1000 {
+        cpu.execute_instruction(&mut sys);
+    }
+
+    assert_eq!(0x3001, cpu.registers().pc());
+    // Interrupted on the HALT
+    assert_eq!(0x0021, sys.peek16(0x7ffe));
+    // The timer expired after 80 counts of 20 cycles
+    assert!(cpu.cycle_count() >= 1600);
+}
+
+#[test]
+fn test_prt_count() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0x3e, 0x01, // LD A, 01h
+        0xed, 0x39, 0x10, // OUT0 (TCR), A -- TDE0
+    ]);
+    // 200 NOPs
+    for _ in 0..(2 + 200) {
+        cpu.execute_instruction(&mut sys);
+    }
+
+    sys.load(0x00cd, &[
+        0xed, 0x38, 0x0c, // IN0 A, (TMDR0L)
+    ]);
+    cpu.execute_instruction(&mut sys);
+    // 600 cycles of NOPs, 30 counts
+    assert_eq!(0xffff - 30, 0xff00 | cpu.registers().a() as u16);
+}
+
+#[test]
+fn test_dma_memory_to_memory() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0x21, 0x00, 0x10, // LD HL, 1000h
+        0xed, 0x29, 0x20, // OUT0 (SAR0L), L
+        0xed, 0x21, 0x21, // OUT0 (SAR0H), H
+        0x3e, 0x05, // LD A, 05h
+        0xed, 0x39, 0x22, // OUT0 (SAR0B), A
+        0xed, 0x29, 0x23, // OUT0 (DAR0L), L
+        0xed, 0x21, 0x24, // OUT0 (DAR0H), H
+        0x3e, 0x0a, // LD A, 0ah
+        0xed, 0x39, 0x25, // OUT0 (DAR0B), A
+        0x3e, 0x04, // LD A, 4
+        0xed, 0x39, 0x26, // OUT0 (BCR0L), A
+        0xaf, // XOR A
+        0xed, 0x39, 0x27, // OUT0 (BCR0H), A
+        0xed, 0x39, 0x31, // OUT0 (DMODE), A -- Memory to memory, increment
+        0x3e, 0x61, // LD A, 61h
+        0xed, 0x39, 0x30, // OUT0 (DSTAT), A -- DE0 and DME
+        0xed, 0x00, 0x30, // IN0 B, (DSTAT)
+    ]);
+    sys.mem[0x51000..0x51004].copy_from_slice(&[1, 2, 3, 4]);
+
+    for _ in 0..17 {
+        cpu.execute_instruction(&mut sys);
+    }
+
+    assert_eq!([1, 2, 3, 4], sys.mem[0xa1000..0xa1004]);
+    // DE0 cleared when done
+    assert_eq!(0x31, cpu.registers().get8(Reg8::B));
+}
+
+#[test]
+fn test_serialize_z180() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    sys.load(0x0000, &[
+        0x3e, 0x20, // LD A, 20h
+        0xed, 0x39, 0x38, // OUT0 (CBR), A
+    ]);
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    let data = cpu.serialize();
+
+    let mut other = Cpu::new_z180();
+    other.deserialize(&data).unwrap();
+    sys.load(0x0005, &[0xed, 0x00, 0x38]); // IN0 B, (CBR)
+    other.execute_instruction(&mut sys);
+    assert_eq!(0x20, other.registers().get8(Reg8::B));
+
+    assert!(Cpu::new_z180().deserialize(&Cpu::new_z80().serialize()).is_err());
+}
+
+#[test]
+fn test_disasm_z180() {
+    let mut sys = Z180Machine::new();
+    let mut cpu = Cpu::new_z180();
+
+    let cases: [(&[u8], &str); 9] = [
+        (&[0xed, 0x4c], "MLT BC"),
+        (&[0xed, 0x04], "TST B"),
+        (&[0xed, 0x64, 0x12], "TST 12h"),
+        (&[0xed, 0x74, 0x34], "TSTIO 34h"),
+        (&[0xed, 0x38, 0x3f], "IN0 A, (3fh)"),
+        (&[0xed, 0x39, 0x3f], "OUT0 (3fh), A"),
+        (&[0xed, 0x8b], "OTDM"),
+        (&[0xed, 0x9b], "OTDMR"),
+        (&[0xed, 0x76], "SLP"),
+    ];
+    for (code, expected) in cases {
+        sys.load(0x0000, code);
+        cpu.registers().set_pc(0x0000);
+        assert_eq!(expected, cpu.disasm_instruction(&mut sys));
+    }
+}
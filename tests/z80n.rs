@@ -0,0 +1,339 @@
+use iz80::*;
+
+mod common;
+
+fn setup(code: &[u8]) -> (Cpu, PlainMachine) {
+    common::setup(Cpu::new_z80n(), code)
+}
+
+struct NextMachine {
+    mem: [u8; 65536],
+    nextregs: Vec<(u8, u8)>,
+}
+
+impl Machine for NextMachine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.mem[address as usize]
+    }
+    fn poke(&mut self, address: u16, value: u8) {
+        self.mem[address as usize] = value;
+    }
+
+    fn port_in(&mut self, _address: u16) -> u8 {
+        0
+    }
+    fn port_out(&mut self, _address: u16, _value: u8) {}
+
+    fn nextreg(&mut self, register: u8, value: u8) {
+        self.nextregs.push((register, value));
+    }
+}
+
+#[test]
+fn test_z80_unchanged() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x23, // SWAPNIB
+    ]);
+    let mut z80 = Cpu::new_z80();
+    cpu.registers().set_a(0x12);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x21, cpu.registers().a());
+    assert_eq!(8, cpu.cycle_count());
+
+    // NONI + NOP on the Z80
+    assert_eq!("NONINOP", z80.disasm_instruction(&mut sys));
+}
+
+#[test]
+fn test_ldix() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0xa4, // LDIX
+        0xed, 0xa4, // LDIX
+    ]);
+    sys.poke(0x1000, 0x11);
+    sys.poke(0x1001, 0x22);
+    sys.poke(0x2001, 0x99);
+    cpu.registers().set16(Reg16::HL, 0x1000);
+    cpu.registers().set16(Reg16::DE, 0x2000);
+    cpu.registers().set16(Reg16::BC, 0x0002);
+    cpu.registers().set_a(0x22);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x11, sys.peek(0x2000));
+    assert_eq!(16, cpu.cycle_count());
+
+    // Equal to A, not copied
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x99, sys.peek(0x2001));
+    assert_eq!(0x1002, cpu.registers().get16(Reg16::HL));
+    assert_eq!(0x2002, cpu.registers().get16(Reg16::DE));
+    assert_eq!(0x0000, cpu.registers().get16(Reg16::BC));
+}
+
+#[test]
+fn test_lddrx() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0xbc, // LDDRX
+    ]);
+    sys.poke(0x1000, 0x11);
+    sys.poke(0x0fff, 0x22);
+    cpu.registers().set16(Reg16::HL, 0x1000);
+    cpu.registers().set16(Reg16::DE, 0x2000);
+    cpu.registers().set16(Reg16::BC, 0x0002);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0000, cpu.registers().pc());
+    assert_eq!(21, cpu.cycle_count());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0002, cpu.registers().pc());
+    assert_eq!(37, cpu.cycle_count());
+
+    // DE is incremented
+    assert_eq!(0x11, sys.peek(0x2000));
+    assert_eq!(0x22, sys.peek(0x2001));
+    assert_eq!(0x0ffe, cpu.registers().get16(Reg16::HL));
+    assert_eq!(0x2002, cpu.registers().get16(Reg16::DE));
+}
+
+#[test]
+fn test_ldpirx() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0xb7, // LDPIRX
+    ]);
+    for i in 0..8 {
+        sys.poke(0x1000 + i, 0x10 + i as u8);
+    }
+    cpu.registers().set16(Reg16::HL, 0x1003);
+    cpu.registers().set16(Reg16::DE, 0x2006);
+    cpu.registers().set16(Reg16::BC, 0x0004);
+    cpu.registers().set_a(0x17);
+
+    for _ in 0..4 {
+        cpu.execute_instruction(&mut sys);
+    }
+    assert_eq!(0x0002, cpu.registers().pc());
+    assert_eq!(0x16, sys.peek(0x2006));
+    assert_eq!(0x00, sys.peek(0x2007)); // Equal to A
+    assert_eq!(0x10, sys.peek(0x2008));
+    assert_eq!(0x11, sys.peek(0x2009));
+    assert_eq!(0x1003, cpu.registers().get16(Reg16::HL));
+}
+
+#[test]
+fn test_ldws() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0xa5, // LDWS
+    ]);
+    sys.poke(0x10ff, 0x55);
+    cpu.registers().set16(Reg16::HL, 0x10ff);
+    cpu.registers().set16(Reg16::DE, 0x7f00);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x55, sys.peek(0x7f00));
+    assert_eq!(0x1000, cpu.registers().get16(Reg16::HL));
+    assert_eq!(0x8000, cpu.registers().get16(Reg16::DE));
+    assert!(cpu.registers().get_flag(Flag::P)); // Overflow as INC D
+    assert!(cpu.registers().get_flag(Flag::S));
+    assert_eq!(14, cpu.cycle_count());
+}
+
+#[test]
+fn test_outinb() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x90, // OUTINB
+    ]);
+    sys.poke(0x1000, 0x5a);
+    cpu.registers().set16(Reg16::HL, 0x1000);
+    cpu.registers().set16(Reg16::BC, 0x0234);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x5a, sys.port_in(0x0234));
+    assert_eq!(0x1001, cpu.registers().get16(Reg16::HL));
+    assert_eq!(0x0234, cpu.registers().get16(Reg16::BC));
+    assert_eq!(16, cpu.cycle_count());
+}
+
+#[test]
+fn test_mul_add() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x30, // MUL D, E
+        0xed, 0x31, // ADD HL, A
+        0xed, 0x35, 0x34, 0x12, // ADD DE, 1234h
+    ]);
+    cpu.registers().set16(Reg16::DE, 0xff10);
+    cpu.registers().set16(Reg16::HL, 0x00ff);
+    cpu.registers().set_a(0x81);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0ff0, cpu.registers().get16(Reg16::DE));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x0180, cpu.registers().get16(Reg16::HL));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x2224, cpu.registers().get16(Reg16::DE));
+    assert_eq!(32, cpu.cycle_count());
+}
+
+#[test]
+fn test_test_n() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x27, 0x0f, // TEST 0fh
+    ]);
+    cpu.registers().set_a(0xf0);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xf0, cpu.registers().a());
+    assert!(cpu.registers().get_flag(Flag::Z));
+    assert!(cpu.registers().get_flag(Flag::H));
+    assert_eq!(11, cpu.cycle_count());
+}
+
+#[test]
+fn test_mirror_swapnib() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x24, // MIRROR A
+        0xed, 0x23, // SWAPNIB
+    ]);
+    cpu.registers().set_a(0x13);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0xc8, cpu.registers().a());
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x8c, cpu.registers().a());
+}
+
+#[test]
+fn test_barrel_shifts() {
+    let cases = [
+        (0x28, 0x04, 0x2340), // BSLA
+        (0x29, 0x04, 0xf823), // BSRA
+        (0x2a, 0x04, 0x0823), // BSRL
+        (0x2b, 0x04, 0xf823), // BSRF
+        (0x2c, 0x04, 0x2348), // BRLC
+        (0x28, 0x14, 0x0000), // BSLA, 20 bits
+        (0x2b, 0x08, 0xff82), // BSRF
+        (0x2c, 0x24, 0x2348), // BRLC, only 4 bits of B
+    ];
+    for (code, b, expected) in cases {
+        let (mut cpu, mut sys) = setup(&[0xed, code]);
+        cpu.registers().set16(Reg16::DE, 0x8234);
+        cpu.registers().set8(Reg8::B, b);
+
+        cpu.execute_instruction(&mut sys);
+        assert_eq!(expected, cpu.registers().get16(Reg16::DE), "ED {code:02x}, B={b}");
+        assert_eq!(8, cpu.cycle_count());
+    }
+}
+
+#[test]
+fn test_pixel() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x94, // PIXELAD
+        0xed, 0x95, // SETAE
+        0xed, 0x93, // PIXELDN
+    ]);
+    cpu.registers().set8(Reg8::D, 0x47); // y = 71
+    cpu.registers().set8(Reg8::E, 0x2b); // x = 43
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x4f05, cpu.registers().get16(Reg16::HL));
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x10, cpu.registers().a());
+
+    // Next character row on the same third
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x4825, cpu.registers().get16(Reg16::HL));
+}
+
+#[test]
+fn test_push_nn() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x8a, 0x12, 0x34, // PUSH 1234h
+    ]);
+    cpu.registers().set16(Reg16::SP, 0x8000);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x1234, sys.peek16(0x7ffe));
+    assert_eq!(0x0004, cpu.registers().pc());
+    assert_eq!(23, cpu.cycle_count());
+}
+
+#[test]
+fn test_nextreg() {
+    let mut sys = NextMachine {
+        mem: [0; 65536],
+        nextregs: Vec::new(),
+    };
+    let code = [
+        0xed, 0x91, 0x07, 0x02, // NEXTREG 07h, 02h
+        0xed, 0x92, 0x08, // NEXTREG 08h, A
+    ];
+    sys.mem[..code.len()].copy_from_slice(&code);
+    let mut cpu = Cpu::new_z80n();
+    cpu.registers().set_a(0x55);
+
+    cpu.execute_instruction(&mut sys);
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(vec![(0x07, 0x02), (0x08, 0x55)], sys.nextregs);
+    assert_eq!(37, cpu.cycle_count());
+}
+
+#[test]
+fn test_nextreg_default() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x91, 0x07, 0x02, // NEXTREG 07h, 02h
+    ]);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x07, sys.port_in(0x243b));
+    assert_eq!(0x02, sys.port_in(0x253b));
+}
+
+#[test]
+fn test_jp_c() {
+    let (mut cpu, mut sys) = setup(&[]);
+    sys.poke(0x4321, 0xed);
+    sys.poke(0x4322, 0x98); // JP (C)
+    sys.port_out(0x12fe, 0x85);
+    cpu.registers().set_pc(0x4321);
+    cpu.registers().set16(Reg16::BC, 0x12fe);
+
+    cpu.execute_instruction(&mut sys);
+    assert_eq!(0x6140, cpu.registers().pc());
+    assert_eq!(13, cpu.cycle_count());
+}
+
+#[test]
+fn test_disasm() {
+    let (mut cpu, mut sys) = setup(&[
+        0xed, 0x8a, 0x12, 0x34, // PUSH 1234h
+        0xed, 0x91, 0x07, 0x02, // NEXTREG 07h, 02h
+        0xed, 0x92, 0x08, // NEXTREG 08h, A
+        0xed, 0x27, 0x0f, // TEST 0fh
+        0xed, 0x36, 0x00, 0x80, // ADD BC, 8000h
+        0xed, 0x2b, // BSRF DE, B
+        0xed, 0x98, // JP (C)
+        0xed, 0xb7, // LDPIRX
+    ]);
+
+    let mut disasm = Vec::new();
+    for pc in [0x0000, 0x0004, 0x0008, 0x000b, 0x000e, 0x0012, 0x0014, 0x0016] {
+        cpu.registers().set_pc(pc);
+        disasm.push(cpu.disasm_instruction(&mut sys));
+    }
+    assert_eq!(vec![
+        "PUSH 1234h",
+        "NEXTREG 07h, 02h",
+        "NEXTREG 08h, A",
+        "TEST 0fh",
+        "ADD BC, 8000h",
+        "BSRF DE, B",
+        "JP (C)",
+        "LDPIRX",
+    ], disasm);
+}
@@ -1,47 +1,88 @@
 use iz80::*;
 
 // From https://github.com/raxoft/z80test
-// Not passing
 
-//static CODE: &[u8] = include_bytes!("res/z80doc.out");
-//static CODE: &[u8] = include_bytes!("res/z80ccf.out");
-//static CODE: &[u8] = include_bytes!("res/z80docflags.out");
-//static CODE: &[u8] = include_bytes!("res/z80flags.out");
-//static CODE: &[u8] = include_bytes!("res/z80memptr.out");
-static CODE: &[u8] = include_bytes!("res/z80full.out");
+static Z80FULL: &[u8] = include_bytes!("res/z80full.out");
+static Z80DOC: &[u8] = include_bytes!("res/z80doc.out");
+static Z80FLAGS: &[u8] = include_bytes!("res/z80flags.out");
+static Z80DOCFLAGS: &[u8] = include_bytes!("res/z80docflags.out");
+static Z80CCF: &[u8] = include_bytes!("res/z80ccf.out");
+static Z80MEMPTR: &[u8] = include_bytes!("res/z80memptr.out");
 
 const START: u16 = 0x8000;
 
 #[test]
 #[ignore]
-fn z80test() {
+fn test_z80full() {
+    z80test(Z80FULL);
+}
+
+#[test]
+#[ignore]
+fn test_z80doc() {
+    z80test(Z80DOC);
+}
+
+#[test]
+#[ignore]
+fn test_z80flags() {
+    z80test(Z80FLAGS);
+}
+
+#[test]
+#[ignore]
+fn test_z80docflags() {
+    z80test(Z80DOCFLAGS);
+}
+
+#[test]
+#[ignore]
+fn test_z80ccf() {
+    z80test(Z80CCF);
+}
+
+#[test]
+#[ignore]
+fn test_z80memptr() {
+    z80test(Z80MEMPTR);
+}
+
+/// The tests run on a ZX Spectrum. The IN tests expect to read 0xbf
+/// from the ULA port, as with no keys pressed.
+struct SpectrumMachine {
+    mem: PlainMachine,
+}
+
+impl Machine for SpectrumMachine {
+    fn peek(&mut self, address: u16) -> u8 {
+        self.mem.peek(address)
+    }
+    fn poke(&mut self, address: u16, value: u8) {
+        self.mem.poke(address, value);
+    }
+
+    fn port_in(&mut self, _address: u16) -> u8 {
+        0xbf
+    }
+    fn port_out(&mut self, _address: u16, _value: u8) {
+    }
+}
+
+fn z80test(code: &[u8]) {
     let mut cpu = Cpu::new_z80();
-    let mut machine = PlainMachine::new();
+    let mut machine = SpectrumMachine {
+        mem: PlainMachine::new(),
+    };
 
     // Load program
-    let code = CODE;
-    let size = code.len();
-    for i in 0..size {
-        machine.poke(START + i as u16, code[i]);
+    for (i, e) in code.iter().enumerate() {
+        machine.poke(START + i as u16, *e);
     }
 
     // Do nothing on 0x1601 and RST 0x10
     machine.poke(0x1601, 0xc9); // RET
     machine.poke(0x0010, 0xc9); // RET
 
-    // Patch to run a single test
-    let run_single_test = false;
-    let single_test = 148;
-    if run_single_test {
-        machine.poke16(0x802b, single_test); // ld bc, 0 to ld bc, test
-        let mut test_start = machine.peek16(0x802e);
-        println!("Test table {test_start:x}");
-        test_start += single_test*2;
-        println!("Test table {test_start:x}");
-        machine.poke16(0x802e, test_start); // Move start
-        machine.poke16(test_start + 2 , 0); // NUL terminate test
-    }
-
     cpu.registers().set_pc(START);
     let trace = false;
     cpu.set_trace(trace);
@@ -58,16 +99,14 @@ fn z80test() {
             let mut ch = cpu.registers().get8(Reg8::A) as char;
             if ch == '\r' {
                 ch = '\n';
-            } else if ch as u8 == 23 {
-                ch = ' ';
-            } else if ch as u8 == 26 {
+            } else if ch as u8 == 23 || ch as u8 == 26 {
                 ch = ' ';
-            } 
-            //print!("{}[{}]", ch, ch as u8);
+            }
             print!("{ch}");
             msg.push(ch);
         }
     }
 
-    assert!(msg.contains("CPU TESTS OK"));
-}
\ No newline at end of file
+    assert!(!msg.contains("FAILED"), "Some tests failed");
+    assert!(msg.contains("all tests passed"));
+}
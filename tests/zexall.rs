@@ -12,9 +12,8 @@ fn test_zexall() {
     // Load program
     //let code = ZEXDOC;
     let code = ZEXALL;
-    let size = code.len();
-    for i in 0..size {
-        machine.poke(0x100 + i as u16, code[i]);
+    for (i, e) in code.iter().enumerate() {
+        machine.poke(0x100 + i as u16, *e);
     }
 
     /*
@@ -25,8 +24,8 @@ fn test_zexall() {
         ret
     */
     let code = [0xD3, 0x00, 0xC9];
-    for i in 0..code.len() {
-        machine.poke(5 + i as u16, code[i]);
+    for (i, e) in code.iter().enumerate() {
+        machine.poke(5 + i as u16, *e);
     }
 
     // Patch to run a single test